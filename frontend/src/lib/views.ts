@@ -6,6 +6,12 @@ export enum Order {
 export enum Status {
 	Available = 'available',
 	Borrowed = 'borrowed',
+	Overdue = 'overdue',
+	OnHold = 'on_hold',
+	Withdrawn = 'withdrawn',
+	Lost = 'lost',
+	Damaged = 'damaged',
+	InRepair = 'in_repair',
 	All = 'all'
 }
 
@@ -14,6 +20,56 @@ export interface Book {
 	title: string;
 	author: string;
 	category?: string;
+	isbn_10?: string;
+	isbn_13?: string;
+	publisher?: string;
+	publication_year?: number;
+	status: Status;
+	available_copies: number;
+	total_copies: number;
+	// Set once the book is taken out of circulation
+	withdrawn_reason?: string;
+	withdrawn_at?: string;
+	// Only set on results of a `q` search
+	score?: number;
+}
+
+// One page of a list endpoint, `next` and `prev` link to the pages around it
+export interface Page<T> {
+	items: T[];
+	total: number;
+	// null when paging with a cursor
+	page: number | null;
+	limit: number;
+	next: string | null;
+	prev: string | null;
+}
+
+export interface BookList extends Page<Book> {
+	// "Did you mean" searches when a `q` search found nothing
+	suggestions?: string[];
+	// Only when asked for with `facets=true`
+	facets?: BookFacets;
+}
+
+export interface FacetCount<T> {
+	value: T | null;
+	count: number;
+}
+
+export interface BookFacets {
+	category: FacetCount<string>[];
+	status: FacetCount<Status>[];
+	author: FacetCount<string>[];
+	decade: FacetCount<number>[];
+}
+
+export interface Copy {
+	id: number;
+	book_id: number;
+	barcode?: string;
+	shelf_location?: string;
+	condition?: string;
 	status: Status;
 }
 
@@ -23,23 +79,133 @@ export interface Member {
 	last_name: string;
 	email: string;
 	phone?: string;
+	card_number?: string;
+	member_type: MemberType;
+	// Null when the membership never expires
+	membership_expires_at?: string;
+	deactivated_at?: string;
+	anonymised_at?: string;
 }
 
+export type MemberType = 'adult' | 'child' | 'student' | 'senior';
+
+export type BorrowingRule = 'membership_expired' | 'loan_limit' | 'overdue_loans' | 'unpaid_fines';
+
 export interface Borrowing {
 	id: number;
-	book_id?: number;
+	copy_id?: number;
 	member_id?: number;
 	borrow_date: string;
+	due_date: string;
 	return_date?: string;
+	renewal_count: number;
+	// Set when an admin voided the borrowing
+	voided_at?: string;
+	void_reason?: string;
+	// Set when an admin lent the book although the member broke these rules
+	override_staff_id?: number;
+	overridden_rules?: BorrowingRule[];
+	renewals?: Renewal[];
+	corrections?: Correction[];
+}
+
+// A change an admin made to a borrowing, with the old and new value of each field
+export interface Correction {
+	id: number;
+	borrowing_id: number;
+	staff_id?: number;
+	action: 'edit' | 'void';
+	changes: Record<string, { old: unknown; new: unknown }>;
+	reason?: string;
+	created_at: string;
+}
+
+export interface Renewal {
+	id: number;
+	borrowing_id: number;
+	previous_due_date: string;
+	new_due_date: string;
+	renewed_at: string;
 }
 
 export interface BorrowedBook {
 	id: number;
+	borrowing_id: number;
 	title: string;
 	author: string;
+	copy_id: number;
+	barcode?: string;
 	borrower: string;
 	borrower_id: number;
 	borrow_date: string;
+	due_date: string;
 	return_date?: string;
+	overdue_days: number;
+	renewal_count: number;
 	status: Status;
 }
+
+export enum Role {
+	ReadOnly = 'read_only',
+	Librarian = 'librarian',
+	Admin = 'admin'
+}
+
+export interface Staff {
+	id: number;
+	username: string;
+	role: Role;
+	created_at: string;
+}
+
+export interface ApiError {
+	code: string;
+	message: string;
+	// Per-field messages when a request fails validation
+	details?: Record<string, string[]> | null;
+}
+
+// A change to a record, `before` is null for a create and `after` for a delete
+export interface AuditEntry {
+	id: number;
+	staff_id?: number;
+	member_id?: number;
+	entity: 'book' | 'copy' | 'member' | 'borrowing';
+	entity_id: number;
+	action: 'create' | 'update' | 'delete';
+	before?: Record<string, unknown>;
+	after?: Record<string, unknown>;
+	created_at: string;
+}
+
+// Report rows, see /api/reports
+export interface TopBook {
+	book_id: number;
+	title: string;
+	author: string;
+	category?: string;
+	loans: number;
+	borrowers: number;
+}
+
+export interface TopMember {
+	member_id: number;
+	first_name: string;
+	last_name: string;
+	loans: number;
+	late_loans: number;
+}
+
+// `month` is written as 2024-03
+export interface CategoryLoans {
+	month: string;
+	category?: string;
+	loans: number;
+}
+
+export interface LoanLength {
+	month: string;
+	loans: number;
+	average_days: number;
+	longest_days: number;
+}
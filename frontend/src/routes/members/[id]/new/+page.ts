@@ -1,4 +1,4 @@
-import type { Book, Member } from '$lib/views';
+import type { BookList, Member } from '$lib/views';
 import type { PageLoad } from './$types';
 
 export const load: PageLoad = async ({ params, fetch }) => {
@@ -8,7 +8,7 @@ export const load: PageLoad = async ({ params, fetch }) => {
 	]);
 
 	const member = (await memberRes.json()) as Member;
-	const books = (await booksRes.json()) as Book[];
+	const books = ((await booksRes.json()) as BookList).items;
 
 	return {
 		member,
@@ -0,0 +1,5 @@
+DROP VIEW IF EXISTS BORROWED_BOOKS;
+
+DROP TABLE IF EXISTS BORROWINGS;
+DROP TABLE IF EXISTS MEMBERS;
+DROP TABLE IF EXISTS BOOKS;
@@ -0,0 +1,21 @@
+CREATE OR REPLACE VIEW BORROWED_BOOKS AS
+SELECT
+    b.id,
+    b.title,
+    b.author,
+    -- Simply concatenating the first and last name of the member -\(o-o)/-
+    CONCAT(m.first_name, " ", m.last_name) AS borrower,
+    m.id AS borrower_id,
+    br.borrow_date,
+    br.return_date,
+    -- This section was hard to figure out, but thanks to stack overflow
+    -- I was able to find a solution https://dub.sh/mix-collations
+    CASE
+        WHEN br.return_date IS NULL THEN 'borrowed' COLLATE utf8mb4_unicode_ci
+        ELSE 'returned' COLLATE utf8mb4_unicode_ci
+    END AS status
+FROM BOOKS b
+JOIN BORROWINGS br ON b.id = br.book_id
+JOIN MEMBERS m ON m.id = br.member_id;
+
+ALTER TABLE BORROWINGS DROP COLUMN due_date;
@@ -0,0 +1,32 @@
+-- Loans get a due date. Loans made before due dates existed are given the
+-- default loan period of 14 days.
+ALTER TABLE BORROWINGS ADD COLUMN due_date DATE NULL AFTER borrow_date;
+
+UPDATE BORROWINGS SET due_date = DATE_ADD(borrow_date, INTERVAL 14 DAY);
+
+ALTER TABLE BORROWINGS MODIFY due_date DATE NOT NULL;
+
+CREATE OR REPLACE VIEW BORROWED_BOOKS AS
+SELECT
+    b.id,
+    br.id AS borrowing_id,
+    b.title,
+    b.author,
+    -- Simply concatenating the first and last name of the member -\(o-o)/-
+    CONCAT(m.first_name, " ", m.last_name) AS borrower,
+    m.id AS borrower_id,
+    br.borrow_date,
+    br.due_date,
+    br.return_date,
+    -- Days past the due date, counted up to the return date once returned
+    GREATEST(DATEDIFF(COALESCE(br.return_date, CURRENT_DATE), br.due_date), 0) AS overdue_days,
+    -- This section was hard to figure out, but thanks to stack overflow
+    -- I was able to find a solution https://dub.sh/mix-collations
+    CASE
+        WHEN br.return_date IS NOT NULL THEN 'returned' COLLATE utf8mb4_unicode_ci
+        WHEN br.due_date < CURRENT_DATE THEN 'overdue' COLLATE utf8mb4_unicode_ci
+        ELSE 'borrowed' COLLATE utf8mb4_unicode_ci
+    END AS status
+FROM BOOKS b
+JOIN BORROWINGS br ON b.id = br.book_id
+JOIN MEMBERS m ON m.id = br.member_id;
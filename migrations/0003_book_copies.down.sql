@@ -0,0 +1,51 @@
+-- Loans go back to pointing at the book. Books with several copies are
+-- merged into one, borrowed while any of their copies was.
+ALTER TABLE BOOKS ADD COLUMN status VARCHAR(9) DEFAULT 'available';
+
+UPDATE BOOKS b
+SET b.status = CASE
+    WHEN EXISTS (
+        SELECT 1 FROM COPIES c WHERE c.book_id = b.id AND c.status = 'borrowed'
+    ) THEN 'borrowed'
+    ELSE 'available'
+END;
+
+ALTER TABLE BORROWINGS ADD COLUMN book_id INT NULL AFTER id;
+
+UPDATE BORROWINGS br
+JOIN COPIES c ON c.id = br.copy_id
+SET br.book_id = c.book_id;
+
+ALTER TABLE BORROWINGS DROP FOREIGN KEY borrowings_copy;
+
+ALTER TABLE BORROWINGS
+    DROP COLUMN copy_id,
+    ADD FOREIGN KEY (book_id) REFERENCES BOOKS(id) ON DELETE CASCADE;
+
+CREATE OR REPLACE VIEW BORROWED_BOOKS AS
+SELECT
+    b.id,
+    br.id AS borrowing_id,
+    b.title,
+    b.author,
+    -- Simply concatenating the first and last name of the member -\(o-o)/-
+    CONCAT(m.first_name, " ", m.last_name) AS borrower,
+    m.id AS borrower_id,
+    br.borrow_date,
+    br.due_date,
+    br.return_date,
+    -- Days past the due date, counted up to the return date once returned
+    GREATEST(DATEDIFF(COALESCE(br.return_date, CURRENT_DATE), br.due_date), 0) AS overdue_days,
+    -- This section was hard to figure out, but thanks to stack overflow
+    -- I was able to find a solution https://dub.sh/mix-collations
+    CASE
+        WHEN br.return_date IS NOT NULL THEN 'returned' COLLATE utf8mb4_unicode_ci
+        WHEN br.due_date < CURRENT_DATE THEN 'overdue' COLLATE utf8mb4_unicode_ci
+        ELSE 'borrowed' COLLATE utf8mb4_unicode_ci
+    END AS status
+FROM BOOKS b
+JOIN BORROWINGS br ON b.id = br.book_id
+JOIN MEMBERS m ON m.id = br.member_id;
+
+DROP VIEW IF EXISTS BOOK_COPY_COUNTS;
+DROP TABLE COPIES;
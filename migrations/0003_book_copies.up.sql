@@ -0,0 +1,91 @@
+-- Physical copies of a book, each with its own barcode, shelf and status
+CREATE TABLE COPIES (
+    id INT PRIMARY KEY AUTO_INCREMENT,
+    book_id INT NOT NULL,
+    barcode VARCHAR(20) UNIQUE,
+    shelf_location VARCHAR(50),
+    `condition` VARCHAR(20) DEFAULT 'good',
+    status VARCHAR(9) DEFAULT 'available',
+    FOREIGN KEY (book_id) REFERENCES BOOKS(id) ON DELETE CASCADE
+);
+
+-- Every book used to be a single copy, which is out while it has an open loan
+INSERT INTO COPIES (book_id, status)
+SELECT
+    b.id,
+    CASE
+        WHEN EXISTS (
+            SELECT 1 FROM BORROWINGS br WHERE br.book_id = b.id AND br.return_date IS NULL
+        ) THEN 'borrowed'
+        ELSE 'available'
+    END
+FROM BOOKS b
+ORDER BY b.id;
+
+-- Loans now point at the copy that was lent instead of the book
+ALTER TABLE BORROWINGS ADD COLUMN copy_id INT NULL AFTER id;
+
+UPDATE BORROWINGS br
+JOIN COPIES c ON c.book_id = br.book_id
+SET br.copy_id = c.id;
+
+-- The old constraint's name depends on how the table was created, so it is
+-- looked up rather than assumed
+SET @book_constraint = (
+    SELECT CONSTRAINT_NAME
+    FROM information_schema.KEY_COLUMN_USAGE
+    WHERE TABLE_SCHEMA = DATABASE()
+        AND TABLE_NAME = 'BORROWINGS'
+        AND COLUMN_NAME = 'book_id'
+        AND REFERENCED_TABLE_NAME = 'BOOKS'
+);
+SET @drop_book_constraint = CONCAT(
+    'ALTER TABLE BORROWINGS DROP FOREIGN KEY `', @book_constraint, '`'
+);
+PREPARE drop_book_constraint FROM @drop_book_constraint;
+EXECUTE drop_book_constraint;
+DEALLOCATE PREPARE drop_book_constraint;
+
+ALTER TABLE BORROWINGS
+    DROP COLUMN book_id,
+    ADD CONSTRAINT borrowings_copy
+        FOREIGN KEY (copy_id) REFERENCES COPIES(id) ON DELETE CASCADE;
+
+-- Whether a book is available now follows from its copies
+ALTER TABLE BOOKS DROP COLUMN status;
+
+CREATE OR REPLACE VIEW BOOK_COPY_COUNTS AS
+SELECT
+    book_id,
+    COUNT(*) AS total_copies,
+    CAST(SUM(status = 'available') AS SIGNED) AS available_copies
+FROM COPIES
+GROUP BY book_id;
+
+CREATE OR REPLACE VIEW BORROWED_BOOKS AS
+SELECT
+    b.id,
+    br.id AS borrowing_id,
+    b.title,
+    b.author,
+    c.id AS copy_id,
+    c.barcode,
+    -- Simply concatenating the first and last name of the member -\(o-o)/-
+    CONCAT(m.first_name, " ", m.last_name) AS borrower,
+    m.id AS borrower_id,
+    br.borrow_date,
+    br.due_date,
+    br.return_date,
+    -- Days past the due date, counted up to the return date once returned
+    GREATEST(DATEDIFF(COALESCE(br.return_date, CURRENT_DATE), br.due_date), 0) AS overdue_days,
+    -- This section was hard to figure out, but thanks to stack overflow
+    -- I was able to find a solution https://dub.sh/mix-collations
+    CASE
+        WHEN br.return_date IS NOT NULL THEN 'returned' COLLATE utf8mb4_unicode_ci
+        WHEN br.due_date < CURRENT_DATE THEN 'overdue' COLLATE utf8mb4_unicode_ci
+        ELSE 'borrowed' COLLATE utf8mb4_unicode_ci
+    END AS status
+FROM BOOKS b
+JOIN COPIES c ON b.id = c.book_id
+JOIN BORROWINGS br ON c.id = br.copy_id
+JOIN MEMBERS m ON m.id = br.member_id;
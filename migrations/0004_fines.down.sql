@@ -0,0 +1,3 @@
+DROP VIEW IF EXISTS FINE_BALANCES;
+DROP TABLE FINE_PAYMENTS;
+DROP TABLE FINES;
@@ -0,0 +1,40 @@
+-- Charges against a member. Late fees are added automatically on return,
+-- lost and damaged items are charged by staff. Amounts are in cents.
+CREATE TABLE FINES (
+    id INT PRIMARY KEY AUTO_INCREMENT,
+    member_id INT NOT NULL,
+    borrowing_id INT,
+    kind VARCHAR(10) NOT NULL,
+    amount_cents INT NOT NULL,
+    reason VARCHAR(255),
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (member_id) REFERENCES MEMBERS(id) ON DELETE CASCADE,
+    FOREIGN KEY (borrowing_id) REFERENCES BORROWINGS(id) ON DELETE SET NULL
+);
+
+-- Payments and waivers made against a fine
+CREATE TABLE FINE_PAYMENTS (
+    id INT PRIMARY KEY AUTO_INCREMENT,
+    fine_id INT NOT NULL,
+    kind VARCHAR(10) NOT NULL,
+    amount_cents INT NOT NULL,
+    reason VARCHAR(255),
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (fine_id) REFERENCES FINES(id) ON DELETE CASCADE
+);
+
+CREATE OR REPLACE VIEW FINE_BALANCES AS
+SELECT
+    f.id,
+    f.member_id,
+    f.borrowing_id,
+    f.kind,
+    f.amount_cents,
+    CAST(COALESCE(SUM(CASE WHEN p.kind = 'payment' THEN p.amount_cents END), 0) AS SIGNED) AS paid_cents,
+    CAST(COALESCE(SUM(CASE WHEN p.kind = 'waiver' THEN p.amount_cents END), 0) AS SIGNED) AS waived_cents,
+    CAST(f.amount_cents - COALESCE(SUM(p.amount_cents), 0) AS SIGNED) AS balance_cents,
+    f.reason,
+    f.created_at
+FROM FINES f
+LEFT JOIN FINE_PAYMENTS p ON p.fine_id = f.id
+GROUP BY f.id, f.member_id, f.borrowing_id, f.kind, f.amount_cents, f.reason, f.created_at;
@@ -0,0 +1,17 @@
+-- Members waiting for a borrowed book. Waiting holds are served in position
+-- order; once a copy is returned it is set aside (copy_id) for the first
+-- waiting member until expires_at.
+CREATE TABLE HOLDS (
+    id INT PRIMARY KEY AUTO_INCREMENT,
+    book_id INT NOT NULL,
+    member_id INT NOT NULL,
+    copy_id INT,
+    position INT NOT NULL,
+    status VARCHAR(10) NOT NULL DEFAULT 'waiting',
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    ready_at DATETIME,
+    expires_at DATETIME,
+    FOREIGN KEY (book_id) REFERENCES BOOKS(id) ON DELETE CASCADE,
+    FOREIGN KEY (member_id) REFERENCES MEMBERS(id) ON DELETE CASCADE,
+    FOREIGN KEY (copy_id) REFERENCES COPIES(id) ON DELETE SET NULL
+);
@@ -0,0 +1,40 @@
+ALTER TABLE BORROWINGS ADD COLUMN renewal_count INT NOT NULL DEFAULT 0;
+
+-- Every time a loan was extended, and by how much
+CREATE TABLE RENEWALS (
+    id INT PRIMARY KEY AUTO_INCREMENT,
+    borrowing_id INT NOT NULL,
+    previous_due_date DATE NOT NULL,
+    new_due_date DATE NOT NULL,
+    renewed_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (borrowing_id) REFERENCES BORROWINGS(id) ON DELETE CASCADE
+);
+
+CREATE OR REPLACE VIEW BORROWED_BOOKS AS
+SELECT
+    b.id,
+    br.id AS borrowing_id,
+    b.title,
+    b.author,
+    c.id AS copy_id,
+    c.barcode,
+    -- Simply concatenating the first and last name of the member -\(o-o)/-
+    CONCAT(m.first_name, " ", m.last_name) AS borrower,
+    m.id AS borrower_id,
+    br.borrow_date,
+    br.due_date,
+    br.return_date,
+    -- Days past the due date, counted up to the return date once returned
+    GREATEST(DATEDIFF(COALESCE(br.return_date, CURRENT_DATE), br.due_date), 0) AS overdue_days,
+    br.renewal_count,
+    -- This section was hard to figure out, but thanks to stack overflow
+    -- I was able to find a solution https://dub.sh/mix-collations
+    CASE
+        WHEN br.return_date IS NOT NULL THEN 'returned' COLLATE utf8mb4_unicode_ci
+        WHEN br.due_date < CURRENT_DATE THEN 'overdue' COLLATE utf8mb4_unicode_ci
+        ELSE 'borrowed' COLLATE utf8mb4_unicode_ci
+    END AS status
+FROM BOOKS b
+JOIN COPIES c ON b.id = c.book_id
+JOIN BORROWINGS br ON c.id = br.copy_id
+JOIN MEMBERS m ON m.id = br.member_id;
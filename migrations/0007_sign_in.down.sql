@@ -0,0 +1,8 @@
+DROP TABLE MEMBER_SESSIONS;
+
+ALTER TABLE MEMBERS
+    DROP COLUMN pin_hash,
+    DROP COLUMN card_number;
+
+DROP TABLE STAFF_SESSIONS;
+DROP TABLE STAFF;
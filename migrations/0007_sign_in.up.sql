@@ -0,0 +1,31 @@
+-- Library staff who can sign in to the API
+CREATE TABLE STAFF (
+    id INT PRIMARY KEY AUTO_INCREMENT,
+    username VARCHAR(100) UNIQUE NOT NULL,
+    password_hash VARCHAR(255) NOT NULL,
+    role VARCHAR(10) NOT NULL DEFAULT 'read_only',
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Signed in staff sessions, only a SHA-256 hash of the token is stored
+CREATE TABLE STAFF_SESSIONS (
+    token_hash CHAR(64) PRIMARY KEY,
+    staff_id INT NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at DATETIME NOT NULL,
+    FOREIGN KEY (staff_id) REFERENCES STAFF(id) ON DELETE CASCADE
+);
+
+-- Library card number and PIN used to sign in to /api/me
+ALTER TABLE MEMBERS
+    ADD COLUMN card_number VARCHAR(20) UNIQUE,
+    ADD COLUMN pin_hash VARCHAR(255);
+
+-- Members signed in to the self-service portal
+CREATE TABLE MEMBER_SESSIONS (
+    token_hash CHAR(64) PRIMARY KEY,
+    member_id INT NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at DATETIME NOT NULL,
+    FOREIGN KEY (member_id) REFERENCES MEMBERS(id) ON DELETE CASCADE
+);
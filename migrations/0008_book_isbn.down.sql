@@ -0,0 +1,5 @@
+ALTER TABLE BOOKS
+    DROP INDEX books_isbn_13,
+    DROP INDEX books_isbn_10,
+    DROP COLUMN isbn_13,
+    DROP COLUMN isbn_10;
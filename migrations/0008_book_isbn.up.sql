@@ -0,0 +1,8 @@
+-- Both forms are stored so either can be scanned or searched for. The
+-- ISBN-13 is the one books are matched on; ISBN-10 only exists for 978
+-- prefixed numbers.
+ALTER TABLE BOOKS
+    ADD COLUMN isbn_10 CHAR(10) NULL,
+    ADD COLUMN isbn_13 CHAR(13) NULL,
+    ADD UNIQUE INDEX books_isbn_10 (isbn_10),
+    ADD UNIQUE INDEX books_isbn_13 (isbn_13);
@@ -0,0 +1,3 @@
+ALTER TABLE BOOKS
+    DROP COLUMN publication_year,
+    DROP COLUMN publisher;
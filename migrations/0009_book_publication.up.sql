@@ -0,0 +1,5 @@
+-- Publication details, filled in from the 260/264 fields of imported MARC
+-- records or entered by hand
+ALTER TABLE BOOKS
+    ADD COLUMN publisher VARCHAR(100) NULL,
+    ADD COLUMN publication_year SMALLINT NULL;
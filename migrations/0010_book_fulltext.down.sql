@@ -0,0 +1,2 @@
+ALTER TABLE BOOKS
+    DROP INDEX books_search;
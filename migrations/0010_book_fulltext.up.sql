@@ -0,0 +1,3 @@
+-- Backs the catalog search, see src/search.rs
+ALTER TABLE BOOKS
+    ADD FULLTEXT INDEX books_search (title, author, category);
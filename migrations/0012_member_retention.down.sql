@@ -0,0 +1,8 @@
+ALTER TABLE BORROWINGS DROP FOREIGN KEY borrowings_member;
+
+ALTER TABLE BORROWINGS
+    ADD FOREIGN KEY (member_id) REFERENCES MEMBERS(id) ON DELETE CASCADE;
+
+ALTER TABLE MEMBERS
+    DROP COLUMN anonymised_at,
+    DROP COLUMN deactivated_at;
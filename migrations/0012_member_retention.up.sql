@@ -0,0 +1,28 @@
+-- Deactivated members keep their history but can't borrow, place holds or
+-- sign in. Anonymised members have also had their personal details scrubbed.
+ALTER TABLE MEMBERS
+    ADD COLUMN deactivated_at DATETIME NULL,
+    ADD COLUMN anonymised_at DATETIME NULL;
+
+-- Deleting a member used to delete their borrowings along with them. The
+-- borrowings are now kept with no member, so the circulation history stays.
+-- The old constraint's name depends on how the table was created, so it is
+-- looked up rather than assumed.
+SET @member_constraint = (
+    SELECT CONSTRAINT_NAME
+    FROM information_schema.KEY_COLUMN_USAGE
+    WHERE TABLE_SCHEMA = DATABASE()
+        AND TABLE_NAME = 'BORROWINGS'
+        AND COLUMN_NAME = 'member_id'
+        AND REFERENCED_TABLE_NAME = 'MEMBERS'
+);
+SET @drop_member_constraint = CONCAT(
+    'ALTER TABLE BORROWINGS DROP FOREIGN KEY `', @member_constraint, '`'
+);
+PREPARE drop_member_constraint FROM @drop_member_constraint;
+EXECUTE drop_member_constraint;
+DEALLOCATE PREPARE drop_member_constraint;
+
+ALTER TABLE BORROWINGS
+    ADD CONSTRAINT borrowings_member
+    FOREIGN KEY (member_id) REFERENCES MEMBERS(id) ON DELETE SET NULL;
@@ -0,0 +1,4 @@
+ALTER TABLE BOOKS
+    DROP COLUMN withdrawn_at,
+    DROP COLUMN withdrawn_reason,
+    DROP COLUMN withdrawn_status;
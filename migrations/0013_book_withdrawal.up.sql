@@ -0,0 +1,7 @@
+-- Books taken out of circulation are kept, along with their loan history,
+-- instead of being deleted. The status is one of withdrawn, lost, damaged or
+-- in_repair, and is NULL while the book is in circulation.
+ALTER TABLE BOOKS
+    ADD COLUMN withdrawn_status VARCHAR(9) NULL,
+    ADD COLUMN withdrawn_reason VARCHAR(255) NULL,
+    ADD COLUMN withdrawn_at DATE NULL;
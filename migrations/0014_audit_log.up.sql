@@ -0,0 +1,18 @@
+-- Every change made to a book, copy, member or borrowing through the API,
+-- with the record as it was before and after. A create has no `before_state`
+-- and a delete no `after_state`. The actor is the staff account, or the
+-- member when they made the change through the portal.
+CREATE TABLE AUDIT_LOG (
+    id INT PRIMARY KEY AUTO_INCREMENT,
+    staff_id INT,
+    member_id INT,
+    entity VARCHAR(10) NOT NULL,
+    entity_id INT NOT NULL,
+    action VARCHAR(10) NOT NULL,
+    before_state JSON,
+    after_state JSON,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    INDEX audit_log_entity (entity, entity_id),
+    FOREIGN KEY (staff_id) REFERENCES STAFF(id) ON DELETE SET NULL,
+    FOREIGN KEY (member_id) REFERENCES MEMBERS(id) ON DELETE SET NULL
+);
@@ -0,0 +1,9 @@
+ALTER TABLE BORROWINGS DROP FOREIGN KEY borrowings_override_staff;
+
+ALTER TABLE BORROWINGS
+    DROP COLUMN overridden_rules,
+    DROP COLUMN override_staff_id;
+
+ALTER TABLE MEMBERS
+    DROP COLUMN membership_expires_at,
+    DROP COLUMN member_type;
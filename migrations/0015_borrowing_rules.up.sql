@@ -0,0 +1,13 @@
+-- The member type sets how many loans a member can have out at once.
+-- Memberships without an expiry date never expire.
+ALTER TABLE MEMBERS
+    ADD COLUMN member_type VARCHAR(10) NOT NULL DEFAULT 'adult',
+    ADD COLUMN membership_expires_at DATE NULL;
+
+-- Loans an admin let through although the member broke the borrowing rules,
+-- with who allowed it and the rules that were overridden
+ALTER TABLE BORROWINGS
+    ADD COLUMN override_staff_id INT NULL,
+    ADD COLUMN overridden_rules JSON NULL,
+    ADD CONSTRAINT borrowings_override_staff
+        FOREIGN KEY (override_staff_id) REFERENCES STAFF(id) ON DELETE SET NULL;
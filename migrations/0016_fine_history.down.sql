@@ -0,0 +1,8 @@
+ALTER TABLE FINES DROP FOREIGN KEY fines_member;
+
+-- Fines of deleted members have nobody to belong to under the old schema
+DELETE FROM FINES WHERE member_id IS NULL;
+
+ALTER TABLE FINES
+    MODIFY member_id INT NOT NULL,
+    ADD FOREIGN KEY (member_id) REFERENCES MEMBERS(id) ON DELETE CASCADE;
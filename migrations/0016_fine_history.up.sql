@@ -0,0 +1,23 @@
+-- Deleting a member used to delete their fines and, with them, every payment
+-- and waiver made against those fines. The fines are now kept with no member,
+-- so the ledger outlives the member. The old constraint's name depends on how
+-- the table was created, so it is looked up rather than assumed.
+SET @member_constraint = (
+    SELECT CONSTRAINT_NAME
+    FROM information_schema.KEY_COLUMN_USAGE
+    WHERE TABLE_SCHEMA = DATABASE()
+        AND TABLE_NAME = 'FINES'
+        AND COLUMN_NAME = 'member_id'
+        AND REFERENCED_TABLE_NAME = 'MEMBERS'
+);
+SET @drop_member_constraint = CONCAT(
+    'ALTER TABLE FINES DROP FOREIGN KEY `', @member_constraint, '`'
+);
+PREPARE drop_member_constraint FROM @drop_member_constraint;
+EXECUTE drop_member_constraint;
+DEALLOCATE PREPARE drop_member_constraint;
+
+ALTER TABLE FINES
+    MODIFY member_id INT NULL,
+    ADD CONSTRAINT fines_member
+    FOREIGN KEY (member_id) REFERENCES MEMBERS(id) ON DELETE SET NULL;
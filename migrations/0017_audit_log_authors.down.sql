@@ -0,0 +1 @@
+ALTER TABLE AUDIT_LOG DROP COLUMN staff_username;
@@ -0,0 +1,8 @@
+-- Entries keep the username of the staff account that made the change, so
+-- they still name their author after the account is deleted and `staff_id`
+-- is cleared
+ALTER TABLE AUDIT_LOG ADD COLUMN staff_username VARCHAR(100) NULL AFTER staff_id;
+
+UPDATE AUDIT_LOG a
+JOIN STAFF s ON s.id = a.staff_id
+SET a.staff_username = s.username;
@@ -1,15 +1,23 @@
-INSERT INTO BOOKS (title, author, category, status) VALUES
-('The Great Gatsby', 'F. Scott Fitzgerald', 'Fiction', 'available'),
-('To Kill a Mockingbird', 'Harper Lee', 'Fiction', 'borrowed'),
-('Introduction to SQL', 'John Smith', 'Technology', 'available'),
-('The Art of Programming', 'Jane Doe', 'Technology', 'available');
+INSERT INTO BOOKS (title, author, category) VALUES
+('The Great Gatsby', 'F. Scott Fitzgerald', 'Fiction'),
+('To Kill a Mockingbird', 'Harper Lee', 'Fiction'),
+('Introduction to SQL', 'John Smith', 'Technology'),
+('The Art of Programming', 'Jane Doe', 'Technology');
+
+INSERT INTO COPIES (book_id, barcode, shelf_location, `condition`, status) VALUES
+(1, 'LIB-000001', 'FIC-FIT', 'good', 'available'),
+(1, 'LIB-000002', 'FIC-FIT', 'fair', 'available'),
+(1, 'LIB-000003', 'FIC-FIT', 'new', 'available'),
+(2, 'LIB-000004', 'FIC-LEE', 'good', 'borrowed'),
+(3, 'LIB-000005', 'TEC-SMI', 'good', 'available'),
+(4, 'LIB-000006', 'TEC-DOE', 'good', 'available');
 
 INSERT INTO MEMBERS (first_name, last_name, email, phone) VALUES
 ('John', 'Doe', 'john@email.com', '123-456-7890'),
 ('Jane', 'Smith', 'jane@email.com', '123-456-7891'),
 ('Bob', 'Wilson', 'bob@email.com', '123-456-7892');
 
-INSERT INTO BORROWINGS (book_id, member_id, borrow_date, return_date) VALUES
-(2, 1, '2023-01-15', NULL),
-(1, 2, '2023-01-10', '2023-01-20'),
-(3, 3, '2023-02-01', '2023-02-15');
+INSERT INTO BORROWINGS (copy_id, member_id, borrow_date, due_date, return_date) VALUES
+(4, 1, '2023-01-15', '2023-02-05', NULL),
+(1, 2, '2023-01-10', '2023-01-31', '2023-01-20'),
+(5, 3, '2023-02-01', '2023-02-15', '2023-02-15');
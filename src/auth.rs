@@ -0,0 +1,364 @@
+use std::future::{ready, Ready};
+use std::sync::LazyLock;
+
+use actix_web::{
+    body::MessageBody,
+    dev::{Payload, ServiceRequest, ServiceResponse},
+    http::header,
+    middleware::Next,
+    web, FromRequest, HttpMessage, HttpRequest,
+};
+use argon2::{
+    password_hash::{
+        rand_core::{OsRng, RngCore},
+        PasswordHash, PasswordHasher, PasswordVerifier, SaltString,
+    },
+    Argon2,
+};
+use chrono::NaiveDateTime;
+use sha2::{Digest, Sha256};
+use sqlx::MySqlPool;
+use tracing::info;
+
+use crate::error::ApiError;
+use crate::views::staff::{Role, Staff};
+
+/// Name of the cookie holding the staff session token
+pub const SESSION_COOKIE: &str = "session";
+
+/// Name of the cookie holding a member's session token
+pub const MEMBER_SESSION_COOKIE: &str = "member_session";
+
+// A hash of a password nobody has, checked when there is no account to sign
+// in to. It is made with the same settings as real hashes so it takes as
+// long to check.
+static DUMMY_HASH: LazyLock<String> = LazyLock::new(|| {
+    hash_password("no account has this password").expect("Failed to hash the dummy password")
+});
+
+// Paths under /api that can be reached without signing in
+const PUBLIC_PATHS: [&str; 3] = ["/api/health_check", "/api/auth/login", "/api/me/login"];
+
+// Members sign in to the self-service portal under /api/me, everything else
+// is for staff
+fn is_patron_path(path: &str) -> bool {
+    path == "/api/me" || path.starts_with("/api/me/")
+}
+
+pub fn hash_password(password: &str) -> Result<String, argon2::password_hash::Error> {
+    let salt = SaltString::generate(&mut OsRng);
+
+    Ok(Argon2::default()
+        .hash_password(password.as_bytes(), &salt)?
+        .to_string())
+}
+
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    match PasswordHash::new(hash) {
+        Ok(hash) => Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// Checks a password given at sign in against the account's hash. Without an
+/// account (or a hash) the password is checked against a dummy hash anyway
+/// and refused, so unknown accounts can't be told apart by how long the
+/// answer takes.
+pub fn verify_login(password: &str, hash: Option<&str>) -> bool {
+    match hash {
+        Some(hash) => verify_password(password, hash),
+        None => {
+            verify_password(password, &DUMMY_HASH);
+            false
+        }
+    }
+}
+
+/// Session tokens are only ever stored hashed, so a leaked table can't be
+/// used to sign in
+pub fn hash_token(token: &str) -> String {
+    to_hex(&Sha256::digest(token.as_bytes()))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn new_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+
+    to_hex(&bytes)
+}
+
+/// Starts a new session for a staff member, returning the token to hand to
+/// the client and when it expires
+pub async fn create_session(
+    pool: &MySqlPool,
+    staff_id: i32,
+    hours: i64,
+) -> Result<(String, NaiveDateTime), sqlx::Error> {
+    let token = new_token();
+    let token_hash = hash_token(&token);
+
+    sqlx::query!(
+        r#"
+        INSERT INTO STAFF_SESSIONS (token_hash, staff_id, expires_at)
+        VALUES (?, ?, DATE_ADD(NOW(), INTERVAL ? HOUR))
+        "#,
+        token_hash,
+        staff_id,
+        hours
+    )
+    .execute(pool)
+    .await?;
+
+    let expires_at = sqlx::query_scalar!(
+        r#"SELECT expires_at FROM STAFF_SESSIONS WHERE token_hash = ?"#,
+        token_hash
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok((token, expires_at))
+}
+
+/// Same as [`create_session`] but for a member signing in to `/api/me`
+pub async fn create_member_session(
+    pool: &MySqlPool,
+    member_id: i32,
+    hours: i64,
+) -> Result<(String, NaiveDateTime), sqlx::Error> {
+    let token = new_token();
+    let token_hash = hash_token(&token);
+
+    sqlx::query!(
+        r#"
+        INSERT INTO MEMBER_SESSIONS (token_hash, member_id, expires_at)
+        VALUES (?, ?, DATE_ADD(NOW(), INTERVAL ? HOUR))
+        "#,
+        token_hash,
+        member_id,
+        hours
+    )
+    .execute(pool)
+    .await?;
+
+    let expires_at = sqlx::query_scalar!(
+        r#"SELECT expires_at FROM MEMBER_SESSIONS WHERE token_hash = ?"#,
+        token_hash
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok((token, expires_at))
+}
+
+/// The session token from the given cookie or an `Authorization: Bearer`
+/// header
+pub fn session_token(req: &HttpRequest, cookie_name: &str) -> Option<String> {
+    if let Some(cookie) = req.cookie(cookie_name) {
+        return Some(cookie.value().to_string());
+    }
+
+    req.headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.trim().to_string())
+}
+
+async fn find_session(pool: &MySqlPool, token: &str) -> Result<Option<Staff>, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+        SELECT s.id, s.username, s.role, s.created_at
+        FROM STAFF_SESSIONS ss
+        JOIN STAFF s ON s.id = ss.staff_id
+        WHERE ss.token_hash = ? AND ss.expires_at > NOW()
+        "#,
+    )
+    .bind(hash_token(token))
+    .fetch_optional(pool)
+    .await
+}
+
+async fn find_member_session(pool: &MySqlPool, token: &str) -> Result<Option<i32>, sqlx::Error> {
+    sqlx::query_scalar(
+        r#"
+        SELECT m.id
+        FROM MEMBER_SESSIONS ms
+        JOIN MEMBERS m ON m.id = ms.member_id
+        WHERE ms.token_hash = ? AND ms.expires_at > NOW()
+        "#,
+    )
+    .bind(hash_token(token))
+    .fetch_optional(pool)
+    .await
+}
+
+/// Middleware for the `/api` scope. Every request apart from the public
+/// paths needs a valid session: a member session for `/api/me` and a staff
+/// session for everything else. Whoever is signed in is stored in the
+/// request extensions for the extractors below.
+pub async fn authenticate(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    if PUBLIC_PATHS.contains(&req.path().trim_end_matches('/')) {
+        return next.call(req).await;
+    }
+
+    let patron = is_patron_path(req.path());
+    let cookie_name = if patron {
+        MEMBER_SESSION_COOKIE
+    } else {
+        SESSION_COOKIE
+    };
+
+    let token = session_token(req.request(), cookie_name)
+        .ok_or_else(|| ApiError::Unauthorized("Not signed in".to_string()))?;
+
+    let pool = req
+        .app_data::<web::Data<MySqlPool>>()
+        .ok_or(ApiError::Internal)?
+        .clone();
+
+    let signed_in = if patron {
+        match find_member_session(pool.get_ref(), &token)
+            .await
+            .map_err(ApiError::from)?
+        {
+            Some(member_id) => {
+                req.extensions_mut().insert(Patron { member_id });
+                true
+            }
+            None => false,
+        }
+    } else {
+        match find_session(pool.get_ref(), &token)
+            .await
+            .map_err(ApiError::from)?
+        {
+            Some(staff) => {
+                req.extensions_mut().insert(staff);
+                true
+            }
+            None => false,
+        }
+    };
+
+    if !signed_in {
+        return Err(ApiError::Unauthorized("Session has expired or is invalid".to_string()).into());
+    }
+
+    next.call(req).await
+}
+
+fn signed_in(req: &HttpRequest, role: Role) -> Result<Staff, ApiError> {
+    let staff = req
+        .extensions()
+        .get::<Staff>()
+        .cloned()
+        .ok_or_else(|| ApiError::Unauthorized("Not signed in".to_string()))?;
+
+    if staff.role < role {
+        return Err(ApiError::Forbidden(format!(
+            "This action needs the {} role",
+            role.as_str()
+        )));
+    }
+
+    Ok(staff)
+}
+
+/// Any signed in staff member
+impl FromRequest for Staff {
+    type Error = ApiError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(signed_in(req, Role::ReadOnly))
+    }
+}
+
+/// A signed in staff member with at least the librarian role
+pub struct Librarian(pub Staff);
+
+impl FromRequest for Librarian {
+    type Error = ApiError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(signed_in(req, Role::Librarian).map(Librarian))
+    }
+}
+
+/// A signed in staff member with the admin role
+pub struct Admin(pub Staff);
+
+impl FromRequest for Admin {
+    type Error = ApiError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(signed_in(req, Role::Admin).map(Admin))
+    }
+}
+
+/// A member signed in to the self-service portal
+#[derive(Clone, Copy)]
+pub struct Patron {
+    pub member_id: i32,
+}
+
+impl FromRequest for Patron {
+    type Error = ApiError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(
+            req.extensions()
+                .get::<Patron>()
+                .copied()
+                .ok_or_else(|| ApiError::Unauthorized("Not signed in".to_string())),
+        )
+    }
+}
+
+/// Creates the first admin account from `ADMIN_USERNAME` and
+/// `ADMIN_PASSWORD` when there are no staff accounts yet
+pub async fn bootstrap_admin(pool: &MySqlPool) -> Result<(), sqlx::Error> {
+    let (Ok(username), Ok(password)) = (
+        std::env::var("ADMIN_USERNAME"),
+        std::env::var("ADMIN_PASSWORD"),
+    ) else {
+        return Ok(());
+    };
+
+    let count = sqlx::query_scalar!(r#"SELECT COUNT(*) FROM STAFF"#)
+        .fetch_one(pool)
+        .await?;
+
+    if count > 0 {
+        return Ok(());
+    }
+
+    let password_hash = hash_password(&password).expect("Failed to hash ADMIN_PASSWORD");
+
+    sqlx::query!(
+        r#"
+        INSERT INTO STAFF (username, password_hash, role)
+        VALUES (?, ?, 'admin')
+        "#,
+        username,
+        password_hash
+    )
+    .execute(pool)
+    .await?;
+
+    info!("Created admin account {}", username);
+
+    Ok(())
+}
@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+
+use crate::views::members::MemberType;
+
+/// Library policy settings, read once from the environment at startup.
+#[derive(Clone)]
+pub struct LibraryConfig {
+    pub default_loan_days: u32,
+    pub category_loan_days: HashMap<String, u32>,
+    pub fine_daily_rate_cents: i64,
+    pub fine_grace_days: i64,
+    pub fine_cap_cents: i64,
+    pub hold_pickup_days: u32,
+    pub max_renewals: i32,
+    pub default_max_loans: u32,
+    pub member_type_max_loans: HashMap<String, u32>,
+    // Members owing more than this in fines can't borrow
+    pub max_balance_cents: i64,
+    pub session_hours: i64,
+}
+
+impl Default for LibraryConfig {
+    fn default() -> Self {
+        LibraryConfig {
+            default_loan_days: 14,
+            category_loan_days: HashMap::new(),
+            fine_daily_rate_cents: 25,
+            fine_grace_days: 0,
+            fine_cap_cents: 1000,
+            hold_pickup_days: 7,
+            max_renewals: 2,
+            default_max_loans: 10,
+            member_type_max_loans: HashMap::new(),
+            max_balance_cents: 500,
+            session_hours: 12,
+        }
+    }
+}
+
+impl LibraryConfig {
+    /// Reads the settings from the environment, falling back to the defaults
+    /// for anything unset. `CATEGORY_LOAN_DAYS` is a comma separated list of
+    /// `category:days` pairs, e.g. `Fiction:21,Technology:7`, and
+    /// `MEMBER_TYPE_MAX_LOANS` one of `type:loans` pairs, e.g. `child:5`.
+    pub fn from_env() -> Self {
+        let defaults = LibraryConfig::default();
+
+        let default_loan_days = env_or("DEFAULT_LOAN_DAYS", defaults.default_loan_days);
+
+        let category_loan_days = std::env::var("CATEGORY_LOAN_DAYS")
+            .map(|value| parse_pairs(&value))
+            .unwrap_or(defaults.category_loan_days);
+
+        let member_type_max_loans = std::env::var("MEMBER_TYPE_MAX_LOANS")
+            .map(|value| parse_pairs(&value))
+            .unwrap_or(defaults.member_type_max_loans);
+
+        LibraryConfig {
+            default_loan_days,
+            category_loan_days,
+            fine_daily_rate_cents: env_or("FINE_DAILY_RATE_CENTS", defaults.fine_daily_rate_cents),
+            fine_grace_days: env_or("FINE_GRACE_DAYS", defaults.fine_grace_days),
+            fine_cap_cents: env_or("FINE_CAP_CENTS", defaults.fine_cap_cents),
+            hold_pickup_days: env_or("HOLD_PICKUP_DAYS", defaults.hold_pickup_days),
+            max_renewals: env_or("MAX_RENEWALS", defaults.max_renewals),
+            default_max_loans: env_or("DEFAULT_MAX_LOANS", defaults.default_max_loans),
+            member_type_max_loans,
+            max_balance_cents: env_or("MAX_BALANCE_CENTS", defaults.max_balance_cents),
+            session_hours: env_or("SESSION_HOURS", defaults.session_hours),
+        }
+    }
+
+    /// Loan period in days for a book of the given category
+    pub fn loan_days(&self, category: Option<&str>) -> u32 {
+        category
+            .and_then(|category| self.category_loan_days.get(&category.to_lowercase()))
+            .copied()
+            .unwrap_or(self.default_loan_days)
+    }
+
+    /// Most loans a member of the given type can have out at once
+    pub fn max_loans(&self, member_type: MemberType) -> u32 {
+        self.member_type_max_loans
+            .get(member_type.as_str())
+            .copied()
+            .unwrap_or(self.default_max_loans)
+    }
+
+    /// Late fee for a book returned `days_late` days after its due date. The
+    /// first `fine_grace_days` are free and the total never exceeds the cap.
+    pub fn late_fine_cents(&self, days_late: i64) -> i64 {
+        let chargeable_days = days_late - self.fine_grace_days;
+
+        if chargeable_days <= 0 {
+            return 0;
+        }
+
+        (chargeable_days * self.fine_daily_rate_cents).min(self.fine_cap_cents)
+    }
+}
+
+fn env_or<T: std::str::FromStr>(key: &str, default: T) -> T {
+    std::env::var(key)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+/// Parses `key:value` pairs, lowercasing the keys and skipping malformed entries
+fn parse_pairs(value: &str) -> HashMap<String, u32> {
+    value
+        .split(',')
+        .filter_map(|pair| {
+            let (key, value) = pair.split_once(':')?;
+            Some((key.trim().to_lowercase(), value.trim().parse().ok()?))
+        })
+        .collect()
+}
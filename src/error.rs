@@ -0,0 +1,178 @@
+use std::fmt;
+
+use actix_web::{
+    error::{BlockingError, JsonPayloadError, PathError, QueryPayloadError},
+    http::StatusCode,
+    HttpRequest, HttpResponse, ResponseError,
+};
+use serde_json::{json, Value};
+use sqlx::mysql::MySqlDatabaseError;
+use tracing::warn;
+use validator::ValidationErrors;
+
+// MariaDB error numbers we turn into client errors
+const DUPLICATE_KEY: u16 = 1062;
+const ROW_IS_REFERENCED: u16 = 1451;
+const NO_REFERENCED_ROW: u16 = 1452;
+
+/// Error returned by every handler. Renders as a JSON body of the form
+/// `{"code": "not_found", "message": "Member not found", "details": null}`.
+#[derive(Debug)]
+pub enum ApiError {
+    BadRequest(String),
+    Unauthorized(String),
+    Forbidden(String),
+    NotFound(String),
+    Conflict(String),
+    // The request was understood but refers to data that isn't valid,
+    // `details` says which parts
+    Unprocessable(String, Option<Value>),
+    // Anything unexpected, the cause is logged rather than sent to the client
+    Internal,
+}
+
+impl ApiError {
+    pub fn code(&self) -> &str {
+        match self {
+            ApiError::BadRequest(_) => "bad_request",
+            ApiError::Unauthorized(_) => "unauthorized",
+            ApiError::Forbidden(_) => "forbidden",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::Conflict(_) => "conflict",
+            ApiError::Unprocessable(_, _) => "unprocessable_entity",
+            ApiError::Internal => "internal_error",
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            ApiError::BadRequest(message)
+            | ApiError::Unauthorized(message)
+            | ApiError::Forbidden(message)
+            | ApiError::NotFound(message)
+            | ApiError::Conflict(message)
+            | ApiError::Unprocessable(message, _) => message,
+            ApiError::Internal => "Something went wrong",
+        }
+    }
+
+    pub fn details(&self) -> Option<&Value> {
+        match self {
+            ApiError::Unprocessable(_, details) => details.as_ref(),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message())
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::Unprocessable(_, _) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).json(json!({
+            "code": self.code(),
+            "message": self.message(),
+            "details": self.details(),
+        }))
+    }
+}
+
+impl From<sqlx::Error> for ApiError {
+    fn from(error: sqlx::Error) -> Self {
+        if let sqlx::Error::RowNotFound = error {
+            return ApiError::NotFound("Record not found".to_string());
+        }
+
+        let number = error
+            .as_database_error()
+            .and_then(|error| error.try_downcast_ref::<MySqlDatabaseError>())
+            .map(|error| error.number());
+
+        match number {
+            Some(DUPLICATE_KEY) => {
+                ApiError::Conflict("A record with the same value already exists".to_string())
+            }
+            Some(ROW_IS_REFERENCED) => {
+                ApiError::Conflict("Record is still referenced by other records".to_string())
+            }
+            Some(NO_REFERENCED_ROW) => ApiError::Unprocessable(
+                "Record refers to something that does not exist".to_string(),
+                None,
+            ),
+            _ => {
+                warn!("Database error: {:?}", error);
+                ApiError::Internal
+            }
+        }
+    }
+}
+
+/// Failed validation becomes a 422 listing the messages for each field, e.g.
+/// `"details": {"email": ["Email is not valid"]}`
+impl From<ValidationErrors> for ApiError {
+    fn from(errors: ValidationErrors) -> Self {
+        let details: serde_json::Map<String, Value> = errors
+            .field_errors()
+            .into_iter()
+            .map(|(field, errors)| {
+                let messages = errors
+                    .iter()
+                    .map(|error| match &error.message {
+                        Some(message) => Value::String(message.to_string()),
+                        None => Value::String(format!("{} is not valid", field)),
+                    })
+                    .collect();
+
+                (field.to_string(), Value::Array(messages))
+            })
+            .collect();
+
+        ApiError::Unprocessable(
+            "Request failed validation".to_string(),
+            Some(Value::Object(details)),
+        )
+    }
+}
+
+impl From<BlockingError> for ApiError {
+    fn from(error: BlockingError) -> Self {
+        warn!("Blocking task failed: {:?}", error);
+        ApiError::Internal
+    }
+}
+
+impl From<argon2::password_hash::Error> for ApiError {
+    fn from(error: argon2::password_hash::Error) -> Self {
+        warn!("Failed to hash password: {:?}", error);
+        ApiError::Internal
+    }
+}
+
+/// Used for the Json, Query and Path extractor configs so malformed requests
+/// get the same error body as everything else
+pub fn json_error(error: JsonPayloadError, _: &HttpRequest) -> actix_web::Error {
+    ApiError::BadRequest(error.to_string()).into()
+}
+
+pub fn query_error(error: QueryPayloadError, _: &HttpRequest) -> actix_web::Error {
+    ApiError::BadRequest(error.to_string()).into()
+}
+
+pub fn path_error(error: PathError, _: &HttpRequest) -> actix_web::Error {
+    ApiError::NotFound(error.to_string()).into()
+}
@@ -0,0 +1,83 @@
+/// A checked ISBN in both forms. Books are matched on the ISBN-13, which
+/// every ISBN has; only those starting with 978 also have an ISBN-10.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Isbn {
+    pub isbn_10: Option<String>,
+    pub isbn_13: String,
+}
+
+impl Isbn {
+    /// Parses an ISBN-10 or ISBN-13, ignoring hyphens and spaces. Returns
+    /// `None` when it has the wrong length or the check digit doesn't match.
+    pub fn parse(value: &str) -> Option<Isbn> {
+        let value: String = value
+            .chars()
+            .filter(|c| *c != '-' && *c != ' ')
+            .map(|c| c.to_ascii_uppercase())
+            .collect();
+
+        if !value.is_ascii() {
+            return None;
+        }
+
+        match value.len() {
+            10 => {
+                let digits = &value[..9];
+                if !digits.chars().all(|c| c.is_ascii_digit())
+                    || value[9..] != isbn_10_check(digits)
+                {
+                    return None;
+                }
+
+                let isbn_13 = format!("978{}", digits);
+                Some(Isbn {
+                    isbn_10: Some(value),
+                    isbn_13: format!("{}{}", isbn_13, isbn_13_check(&isbn_13)),
+                })
+            }
+            13 => {
+                if !value.chars().all(|c| c.is_ascii_digit())
+                    || !(value.starts_with("978") || value.starts_with("979"))
+                    || value[12..] != isbn_13_check(&value[..12])
+                {
+                    return None;
+                }
+
+                let isbn_10 = value
+                    .strip_prefix("978")
+                    .map(|digits| format!("{}{}", &digits[..9], isbn_10_check(&digits[..9])));
+
+                Some(Isbn {
+                    isbn_10,
+                    isbn_13: value,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+// Weights 10 down to 2, the check digit makes the sum a multiple of 11
+fn isbn_10_check(digits: &str) -> String {
+    let sum: u32 = digits
+        .chars()
+        .zip((2..=10).rev())
+        .map(|(c, weight)| c.to_digit(10).unwrap_or(0) * weight)
+        .sum();
+
+    match (11 - sum % 11) % 11 {
+        10 => "X".to_string(),
+        check => check.to_string(),
+    }
+}
+
+// Alternating weights of 1 and 3, the check digit makes the sum a multiple of 10
+fn isbn_13_check(digits: &str) -> String {
+    let sum: u32 = digits
+        .chars()
+        .zip([1, 3].into_iter().cycle())
+        .map(|(c, weight)| c.to_digit(10).unwrap_or(0) * weight)
+        .sum();
+
+    ((10 - sum % 10) % 10).to_string()
+}
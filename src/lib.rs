@@ -1,2 +1,10 @@
+pub mod auth;
+pub mod config;
+pub mod error;
+pub mod isbn;
+pub mod marc;
+pub mod migrations;
 pub mod routes;
+pub mod search;
+pub mod validation;
 pub mod views;
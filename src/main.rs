@@ -1,13 +1,19 @@
 use actix_files as fs;
 use actix_web::{
-    middleware::Logger,
+    middleware::{from_fn, Logger},
     web::{self, scope, Data},
     App, HttpServer, Responder,
 };
 use env_logger::Env;
 use tracing::info;
 
-use csci211_project::routes::{books, borrowings, health_check, members};
+use csci211_project::auth;
+use csci211_project::config::LibraryConfig;
+use csci211_project::error;
+use csci211_project::migrations;
+use csci211_project::routes::{
+    audit, books, borrowings, copies, fines, health_check, holds, me, members, reports, staff,
+};
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
@@ -24,18 +30,56 @@ async fn main() -> std::io::Result<()> {
 
     let pool = sqlx::MySqlPool::connect(&database_url).await.unwrap();
 
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    if args.first().map(String::as_str) == Some("migrate") {
+        return migrate(&pool, args.get(1).map(String::as_str).unwrap_or("up")).await;
+    }
+
+    // Pending migrations are applied on startup unless turned off, e.g. when
+    // they are run as a separate deployment step
+    let auto_migrate = !args.iter().any(|arg| arg == "--no-migrate")
+        && std::env::var("AUTO_MIGRATE").map_or(true, |value| value != "false");
+
+    if auto_migrate {
+        migrations::run(&pool)
+            .await
+            .expect("Failed to apply database migrations");
+    }
+
+    let config = LibraryConfig::from_env();
+
+    auth::bootstrap_admin(&pool)
+        .await
+        .expect("Failed to create the admin account");
+
     info!("Starting server at {}", address);
 
     HttpServer::new(move || {
         App::new()
             .wrap(Logger::default())
             .app_data(Data::new(pool.clone()))
+            .app_data(Data::new(config.clone()))
+            // Malformed bodies, query strings and paths get the same JSON
+            // error body as the handlers return
+            .app_data(web::JsonConfig::default().error_handler(error::json_error))
+            .app_data(web::QueryConfig::default().error_handler(error::query_error))
+            .app_data(web::PathConfig::default().error_handler(error::path_error))
             .service(
                 scope("/api")
+                    .wrap(from_fn(auth::authenticate))
                     .service(health_check)
+                    .configure(staff::auth_config)
+                    .configure(staff::staff_config)
                     .configure(books::books_config)
+                    .configure(copies::copies_config)
                     .configure(members::members_config)
-                    .configure(borrowings::borrowings_config),
+                    .configure(borrowings::borrowings_config)
+                    .configure(fines::fines_config)
+                    .configure(holds::holds_config)
+                    .configure(me::me_config)
+                    .configure(audit::audit_config)
+                    .configure(reports::reports_config),
             )
             .service(fs::Files::new("/", "./frontend/build").index_file("index.html"))
             .default_service(web::route().to(spa_index))
@@ -45,6 +89,46 @@ async fn main() -> std::io::Result<()> {
     .await
 }
 
+/// `projectone migrate [up|down|status]`
+async fn migrate(pool: &sqlx::MySqlPool, command: &str) -> std::io::Result<()> {
+    match command {
+        "up" => {
+            migrations::run(pool).await.map_err(std::io::Error::other)?;
+            info!("Database is up to date");
+        }
+        "down" => match migrations::revert_last(pool)
+            .await
+            .map_err(std::io::Error::other)?
+        {
+            Some(version) => info!("Reverted migration {}", version),
+            None => info!("No migrations to revert"),
+        },
+        "status" => {
+            for migration in migrations::status(pool)
+                .await
+                .map_err(std::io::Error::other)?
+            {
+                println!(
+                    "{:>6}  {:<8} {}",
+                    migration.version,
+                    if migration.applied {
+                        "applied"
+                    } else {
+                        "pending"
+                    },
+                    migration.description
+                );
+            }
+        }
+        _ => {
+            eprintln!("Usage: projectone migrate [up|down|status]");
+            std::process::exit(2);
+        }
+    }
+
+    Ok(())
+}
+
 async fn spa_index() -> impl Responder {
     actix_web::HttpResponse::PermanentRedirect()
         .append_header(("Location", "/"))
@@ -0,0 +1,476 @@
+use quick_xml::escape::escape;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+use crate::isbn::Isbn;
+use crate::views::books::{Book, NewBook};
+
+// ISO 2709 separators
+const RECORD_TERMINATOR: u8 = 0x1d;
+const FIELD_TERMINATOR: u8 = 0x1e;
+const SUBFIELD_DELIMITER: u8 = 0x1f;
+
+const LEADER_LENGTH: usize = 24;
+const DIRECTORY_ENTRY_LENGTH: usize = 12;
+
+/// Written before the records of a MARCXML export
+pub const COLLECTION_START: &str = concat!(
+    r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+    "\n",
+    r#"<collection xmlns="http://www.loc.gov/MARC21/slim">"#,
+    "\n"
+);
+
+/// Written after the records of a MARCXML export
+pub const COLLECTION_END: &str = "</collection>\n";
+
+/// A MARC 21 bibliographic record
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Record {
+    pub leader: String,
+    pub control_fields: Vec<ControlField>,
+    pub data_fields: Vec<DataField>,
+}
+
+/// A 00X field, which has a value but no indicators or subfields
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControlField {
+    pub tag: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataField {
+    pub tag: String,
+    pub ind1: char,
+    pub ind2: char,
+    pub subfields: Vec<Subfield>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Subfield {
+    pub code: char,
+    pub value: String,
+}
+
+impl DataField {
+    fn new(tag: &str, ind1: char, ind2: char, subfields: &[(char, &str)]) -> DataField {
+        DataField {
+            tag: tag.to_string(),
+            ind1,
+            ind2,
+            subfields: subfields
+                .iter()
+                .map(|(code, value)| Subfield {
+                    code: *code,
+                    value: value.to_string(),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn subfield(&self, code: char) -> Option<&str> {
+        self.subfields
+            .iter()
+            .find(|subfield| subfield.code == code)
+            .map(|subfield| subfield.value.as_str())
+    }
+}
+
+impl Record {
+    pub fn control_field(&self, tag: &str) -> Option<&str> {
+        self.control_fields
+            .iter()
+            .find(|field| field.tag == tag)
+            .map(|field| field.value.as_str())
+    }
+
+    pub fn data_fields<'a>(&'a self, tag: &'a str) -> impl Iterator<Item = &'a DataField> + 'a {
+        self.data_fields
+            .iter()
+            .filter(move |field| field.tag == tag)
+    }
+
+    /// The first non-empty `code` subfield of any of the `tags` fields
+    fn first_subfield(&self, tags: &[&str], code: char) -> Option<&str> {
+        tags.iter()
+            .flat_map(|tag| {
+                self.data_fields
+                    .iter()
+                    .filter(move |field| field.tag == *tag)
+            })
+            .filter_map(|field| field.subfield(code))
+            .find(|value| !value.trim().is_empty())
+    }
+
+    /// Maps the record onto a book. The title comes from 245 $a and $b, the
+    /// author from 100 $a, the category from the first 650 $a, the ISBN from
+    /// the first valid 020 $a and the publisher and year from 264 or 260,
+    /// falling back to the date in 008. ISBD punctuation is stripped.
+    pub fn to_book(&self) -> NewBook {
+        let title = match (
+            self.first_subfield(&["245"], 'a'),
+            self.first_subfield(&["245"], 'b'),
+        ) {
+            (Some(title), Some(subtitle)) => {
+                format!(
+                    "{}: {}",
+                    strip_punctuation(title),
+                    strip_punctuation(subtitle)
+                )
+            }
+            (Some(title), None) => strip_punctuation(title),
+            _ => String::new(),
+        };
+
+        // 020 $a often carries a qualifier after the number, e.g. "0306406152 (pbk.)"
+        let isbn = self
+            .data_fields("020")
+            .filter_map(|field| field.subfield('a'))
+            .filter_map(|value| value.split_whitespace().next())
+            .find_map(Isbn::parse)
+            .map(|isbn| isbn.isbn_13);
+
+        let publication_year = self
+            .first_subfield(&["264", "260"], 'c')
+            .and_then(find_year)
+            .or_else(|| {
+                self.control_field("008")
+                    .and_then(|value| value.get(7..11))
+                    .and_then(find_year)
+            });
+
+        NewBook {
+            title,
+            author: self
+                .first_subfield(&["100"], 'a')
+                .map(strip_punctuation)
+                .unwrap_or_default(),
+            category: self.first_subfield(&["650"], 'a').map(strip_punctuation),
+            isbn,
+            publisher: self
+                .first_subfield(&["264", "260"], 'b')
+                .map(strip_punctuation),
+            publication_year,
+            copies: None,
+        }
+    }
+}
+
+impl From<&Book> for Record {
+    fn from(book: &Book) -> Record {
+        let mut data_fields = vec![];
+
+        for isbn in [&book.isbn_13, &book.isbn_10].into_iter().flatten() {
+            data_fields.push(DataField::new("020", ' ', ' ', &[('a', isbn)]));
+        }
+
+        data_fields.push(DataField::new("100", '1', ' ', &[('a', &book.author)]));
+        data_fields.push(DataField::new("245", '1', '0', &[('a', &book.title)]));
+
+        let year = book.publication_year.map(|year| year.to_string());
+        let publication: Vec<(char, &str)> =
+            [('b', book.publisher.as_deref()), ('c', year.as_deref())]
+                .into_iter()
+                .filter_map(|(code, value)| value.map(|value| (code, value)))
+                .collect();
+        if !publication.is_empty() {
+            data_fields.push(DataField::new("264", ' ', '1', &publication));
+        }
+
+        if let Some(category) = &book.category {
+            data_fields.push(DataField::new("650", ' ', '4', &[('a', category)]));
+        }
+
+        Record {
+            // New, language material, monograph, UTF-8
+            leader: "00000nam a2200000 i 4500".to_string(),
+            control_fields: vec![ControlField {
+                tag: "001".to_string(),
+                value: book.id.to_string(),
+            }],
+            data_fields,
+        }
+    }
+}
+
+/// Splits an ISO 2709 file into its records. Each record is parsed on its
+/// own so one damaged record doesn't stop the rest from being read. Records
+/// are read as UTF-8, MARC-8 characters outside ASCII are replaced.
+pub fn read_iso2709(data: &[u8]) -> Vec<Result<Record, String>> {
+    data.split(|byte| *byte == RECORD_TERMINATOR)
+        .filter(|record| !record.iter().all(u8::is_ascii_whitespace))
+        .map(|record| {
+            // Files often have a line break between records
+            let start = record
+                .iter()
+                .position(|byte| !byte.is_ascii_whitespace())
+                .unwrap_or(0);
+            parse_iso2709(&record[start..])
+        })
+        .collect()
+}
+
+fn parse_iso2709(record: &[u8]) -> Result<Record, String> {
+    let leader = record
+        .get(..LEADER_LENGTH)
+        .filter(|leader| leader.is_ascii())
+        .ok_or("Record is too short to have a leader")?;
+    let leader = String::from_utf8_lossy(leader).to_string();
+
+    let base_address: usize = leader[12..17]
+        .parse()
+        .map_err(|_| "Leader has an invalid base address of data")?;
+
+    let directory = record
+        .get(LEADER_LENGTH..base_address.saturating_sub(1))
+        .filter(|directory| directory.is_ascii() && directory.len() % DIRECTORY_ENTRY_LENGTH == 0)
+        .ok_or("Record directory is damaged")?;
+
+    let mut parsed = Record {
+        leader,
+        ..Default::default()
+    };
+
+    for entry in directory.chunks(DIRECTORY_ENTRY_LENGTH) {
+        let entry = String::from_utf8_lossy(entry);
+        let tag = &entry[..3];
+        let length: usize = entry[3..7]
+            .parse()
+            .map_err(|_| format!("Field {} has an invalid length", tag))?;
+        let start: usize = entry[7..]
+            .parse()
+            .map_err(|_| format!("Field {} has an invalid starting position", tag))?;
+
+        let field = record
+            .get(base_address + start..base_address + start + length)
+            .ok_or_else(|| format!("Field {} runs past the end of the record", tag))?;
+        let field = field.strip_suffix(&[FIELD_TERMINATOR]).unwrap_or(field);
+
+        if tag.starts_with("00") {
+            parsed.control_fields.push(ControlField {
+                tag: tag.to_string(),
+                value: String::from_utf8_lossy(field).to_string(),
+            });
+            continue;
+        }
+
+        let mut parts = field.split(|byte| *byte == SUBFIELD_DELIMITER);
+        let indicators = parts.next().unwrap_or_default();
+        let indicator = |i: usize| indicators.get(i).map_or(' ', |byte| *byte as char);
+
+        parsed.data_fields.push(DataField {
+            tag: tag.to_string(),
+            ind1: indicator(0),
+            ind2: indicator(1),
+            subfields: parts
+                .filter_map(|part| {
+                    let (code, value) = part.split_first()?;
+                    Some(Subfield {
+                        code: *code as char,
+                        value: String::from_utf8_lossy(value).to_string(),
+                    })
+                })
+                .collect(),
+        });
+    }
+
+    Ok(parsed)
+}
+
+/// Reads the records of a MARCXML `<collection>` or a single `<record>`.
+/// Unlike ISO 2709 a damaged document can't be read past the damage, so any
+/// error fails the whole file.
+pub fn read_marcxml(data: &[u8]) -> Result<Vec<Record>, String> {
+    let mut reader = Reader::from_reader(data);
+    let mut buf = vec![];
+
+    let mut records = vec![];
+    let mut record: Option<Record> = None;
+    // Text is collected for the leader, control fields and subfields
+    let mut text: Option<String> = None;
+
+    loop {
+        let position = reader.buffer_position();
+        let event = reader
+            .read_event_into(&mut buf)
+            .map_err(|error| format!("Invalid XML at byte {}: {}", position, error))?;
+
+        match event {
+            Event::Start(element) => {
+                let name = element.local_name();
+
+                if name.as_ref() == b"record" {
+                    record = Some(Record::default());
+                }
+
+                let Some(current) = record.as_mut() else {
+                    buf.clear();
+                    continue;
+                };
+
+                match name.as_ref() {
+                    b"leader" => text = Some(String::new()),
+                    b"controlfield" => {
+                        current.control_fields.push(ControlField {
+                            tag: attribute(&element, "tag")?,
+                            value: String::new(),
+                        });
+                        text = Some(String::new());
+                    }
+                    b"datafield" => current.data_fields.push(DataField {
+                        tag: attribute(&element, "tag")?,
+                        ind1: indicator(&element, "ind1")?,
+                        ind2: indicator(&element, "ind2")?,
+                        subfields: vec![],
+                    }),
+                    b"subfield" => {
+                        let code = attribute(&element, "code")?.chars().next().unwrap_or(' ');
+                        if let Some(field) = current.data_fields.last_mut() {
+                            field.subfields.push(Subfield {
+                                code,
+                                value: String::new(),
+                            });
+                        }
+                        text = Some(String::new());
+                    }
+                    _ => {}
+                }
+            }
+            Event::Text(value) => {
+                if let Some(text) = text.as_mut() {
+                    let value = value
+                        .unescape()
+                        .map_err(|error| format!("Invalid XML text: {}", error))?;
+                    text.push_str(&value);
+                }
+            }
+            Event::CData(value) => {
+                if let Some(text) = text.as_mut() {
+                    text.push_str(&String::from_utf8_lossy(&value));
+                }
+            }
+            Event::End(element) => {
+                if let Some(current) = record.as_mut() {
+                    let value = text.take().unwrap_or_default();
+
+                    match element.local_name().as_ref() {
+                        b"leader" => current.leader = value,
+                        b"controlfield" => {
+                            if let Some(field) = current.control_fields.last_mut() {
+                                field.value = value;
+                            }
+                        }
+                        b"subfield" => {
+                            let subfield = current
+                                .data_fields
+                                .last_mut()
+                                .and_then(|field| field.subfields.last_mut());
+                            if let Some(subfield) = subfield {
+                                subfield.value = value;
+                            }
+                        }
+                        b"record" => records.extend(record.take()),
+                        _ => {}
+                    }
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+
+        buf.clear();
+    }
+
+    Ok(records)
+}
+
+fn attribute(element: &BytesStart, name: &str) -> Result<String, String> {
+    let local_name = String::from_utf8_lossy(element.local_name().as_ref()).to_string();
+
+    element
+        .try_get_attribute(name)
+        .map_err(|error| format!("Invalid XML attribute: {}", error))?
+        .ok_or_else(|| format!("<{}> is missing the {} attribute", local_name, name))?
+        .unescape_value()
+        .map(|value| value.to_string())
+        .map_err(|error| format!("Invalid XML attribute: {}", error))
+}
+
+fn indicator(element: &BytesStart, name: &str) -> Result<char, String> {
+    Ok(attribute(element, name)?.chars().next().unwrap_or(' '))
+}
+
+/// Writes records as MARCXML `<record>` elements, to go between
+/// [`COLLECTION_START`] and [`COLLECTION_END`]
+pub fn write_marcxml(records: &[Record]) -> String {
+    let mut xml = String::new();
+
+    for record in records {
+        xml.push_str("  <record>\n");
+        xml.push_str(&format!(
+            "    <leader>{}</leader>\n",
+            escape(&record.leader)
+        ));
+
+        for field in &record.control_fields {
+            xml.push_str(&format!(
+                "    <controlfield tag=\"{}\">{}</controlfield>\n",
+                escape(&field.tag),
+                escape(&field.value)
+            ));
+        }
+
+        for field in &record.data_fields {
+            xml.push_str(&format!(
+                "    <datafield tag=\"{}\" ind1=\"{}\" ind2=\"{}\">\n",
+                escape(&field.tag),
+                escape(field.ind1.to_string()),
+                escape(field.ind2.to_string())
+            ));
+
+            for subfield in &field.subfields {
+                xml.push_str(&format!(
+                    "      <subfield code=\"{}\">{}</subfield>\n",
+                    escape(subfield.code.to_string()),
+                    escape(&subfield.value)
+                ));
+            }
+
+            xml.push_str("    </datafield>\n");
+        }
+
+        xml.push_str("  </record>\n");
+    }
+
+    xml
+}
+
+// Drops the trailing ISBD punctuation cataloguers put between elements,
+// e.g. "Gone with the wind /" or "New York :"
+fn strip_punctuation(value: &str) -> String {
+    let value = value.trim_end_matches(|c: char| c.is_whitespace() || "/:;,=".contains(c));
+
+    // A full stop is punctuation unless it ends an initial or abbreviation
+    let value = match value.strip_suffix('.') {
+        Some(rest) if !ends_with_initial(rest) => rest,
+        _ => value,
+    };
+
+    value.trim().to_string()
+}
+
+fn ends_with_initial(value: &str) -> bool {
+    let last_word = value.rsplit(' ').next().unwrap_or_default();
+    last_word.chars().count() == 1 && last_word.chars().all(char::is_uppercase)
+}
+
+// The first four digit run, so "c1998." and "[2004?]" both give a year
+fn find_year(value: &str) -> Option<i16> {
+    value
+        .as_bytes()
+        .windows(4)
+        .find(|window| window.iter().all(u8::is_ascii_digit))
+        .and_then(|window| std::str::from_utf8(window).ok())
+        .and_then(|year| year.parse().ok())
+}
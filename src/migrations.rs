@@ -0,0 +1,61 @@
+use sqlx::migrate::{Migrate, MigrateError, Migrator};
+use sqlx::MySqlPool;
+
+/// The migrations in `migrations/`, embedded in the binary when it is built
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub applied: bool,
+}
+
+/// Applies every migration that has not been run yet
+pub async fn run(pool: &MySqlPool) -> Result<(), MigrateError> {
+    MIGRATOR.run(pool).await
+}
+
+/// Reverts the most recently applied migration, returning its version, or
+/// `None` when nothing has been applied
+pub async fn revert_last(pool: &MySqlPool) -> Result<Option<i64>, MigrateError> {
+    let mut applied = applied_versions(pool).await?;
+    applied.sort_unstable();
+
+    let Some(last) = applied.pop() else {
+        return Ok(None);
+    };
+
+    // undo reverts everything newer than the target version
+    MIGRATOR
+        .undo(pool, applied.last().copied().unwrap_or(0))
+        .await?;
+
+    Ok(Some(last))
+}
+
+/// Every known migration in order and whether it has been applied
+pub async fn status(pool: &MySqlPool) -> Result<Vec<MigrationStatus>, MigrateError> {
+    let applied = applied_versions(pool).await?;
+
+    Ok(MIGRATOR
+        .iter()
+        .filter(|migration| migration.migration_type.is_up_migration())
+        .map(|migration| MigrationStatus {
+            version: migration.version,
+            description: migration.description.to_string(),
+            applied: applied.contains(&migration.version),
+        })
+        .collect())
+}
+
+async fn applied_versions(pool: &MySqlPool) -> Result<Vec<i64>, MigrateError> {
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+
+    Ok(conn
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|migration| migration.version)
+        .collect())
+}
@@ -0,0 +1,234 @@
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use serde_json::{json, Value};
+use sqlx::{mysql::MySqlArguments, query::QueryAs, types::Json, MySql, Transaction};
+
+use super::pagination::Paging;
+use crate::auth::Admin;
+use crate::error::ApiError;
+use crate::views::audit::{AuditAction, AuditEntity, AuditEntry, AuditSearchParams};
+
+pub fn audit_config(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::scope("/audit").service(get_audit));
+}
+
+// Filters for the audit log, see AuditFilters::bind
+const AUDIT_FILTERS: &str = r#"
+    (? IS NULL OR entity = ?)
+    AND (? IS NULL OR entity_id = ?)
+    AND (? IS NULL OR staff_id = ?)
+"#;
+
+// Personal details taken out of a member's snapshots when they leave
+const MEMBER_PERSONAL_FIELDS: &str =
+    "'$.first_name', '$.last_name', '$.email', '$.phone', '$.card_number'";
+
+/// Who made a change
+#[derive(Clone, Copy)]
+pub(crate) enum Actor {
+    Staff(i32),
+    // A member acting on their own account through the portal
+    Member(i32),
+}
+
+/// The record as JSON, or None when it doesn't exist. Members are snapshotted
+/// without their PIN hash.
+pub(crate) async fn snapshot(
+    tx: &mut Transaction<'_, MySql>,
+    entity: AuditEntity,
+    id: i32,
+) -> Result<Option<Json<Value>>, sqlx::Error> {
+    let q = match entity {
+        AuditEntity::Book => {
+            r#"
+            SELECT JSON_OBJECT(
+                'id', id, 'title', title, 'author', author, 'category', category,
+                'isbn_10', isbn_10, 'isbn_13', isbn_13, 'publisher', publisher,
+                'publication_year', publication_year, 'withdrawn_status', withdrawn_status,
+                'withdrawn_reason', withdrawn_reason, 'withdrawn_at', withdrawn_at)
+            FROM BOOKS WHERE id = ?
+            "#
+        }
+        AuditEntity::Copy => {
+            r#"
+            SELECT JSON_OBJECT(
+                'id', id, 'book_id', book_id, 'barcode', barcode,
+                'shelf_location', shelf_location, 'condition', `condition`, 'status', status)
+            FROM COPIES WHERE id = ?
+            "#
+        }
+        AuditEntity::Member => {
+            r#"
+            SELECT JSON_OBJECT(
+                'id', id, 'first_name', first_name, 'last_name', last_name, 'email', email,
+                'phone', phone, 'card_number', card_number, 'member_type', member_type,
+                'membership_expires_at', membership_expires_at,
+                'deactivated_at', deactivated_at, 'anonymised_at', anonymised_at)
+            FROM MEMBERS WHERE id = ?
+            "#
+        }
+        AuditEntity::Borrowing => {
+            r#"
+            SELECT JSON_OBJECT(
+                'id', id, 'copy_id', copy_id, 'member_id', member_id,
+                'borrow_date', borrow_date, 'due_date', due_date, 'return_date', return_date,
+                'renewal_count', renewal_count, 'voided_at', voided_at,
+                'void_reason', void_reason, 'override_staff_id', override_staff_id,
+                'overridden_rules', overridden_rules)
+            FROM BORROWINGS WHERE id = ?
+            "#
+        }
+    };
+
+    sqlx::query_scalar(q)
+        .bind(id)
+        .fetch_optional(&mut **tx)
+        .await
+}
+
+/// Adds an entry for a change to a record, given its snapshot from before
+/// the change. The record is read again for the snapshot after it, and
+/// whether it was created, updated or deleted follows from which of the two
+/// exist.
+pub(crate) async fn record(
+    tx: &mut Transaction<'_, MySql>,
+    actor: Actor,
+    entity: AuditEntity,
+    id: i32,
+    before: Option<Json<Value>>,
+) -> Result<(), sqlx::Error> {
+    let after = snapshot(tx, entity, id).await?;
+
+    let action = match (&before, &after) {
+        (None, None) => return Ok(()),
+        (None, Some(_)) => AuditAction::Create,
+        (Some(_), None) => AuditAction::Delete,
+        (Some(_), Some(_)) => AuditAction::Update,
+    };
+
+    let (staff_id, member_id) = match actor {
+        Actor::Staff(id) => (Some(id), None),
+        Actor::Member(id) => (None, Some(id)),
+    };
+
+    // The username is kept alongside the id so the entry still names its
+    // author once the staff account is deleted
+    sqlx::query!(
+        r#"
+        INSERT INTO AUDIT_LOG
+            (staff_id, staff_username, member_id, entity, entity_id, action,
+             before_state, after_state)
+        VALUES (?, (SELECT username FROM STAFF WHERE id = ?), ?, ?, ?, ?, ?, ?)
+        "#,
+        staff_id,
+        staff_id,
+        member_id,
+        entity.as_str(),
+        id,
+        action.as_str(),
+        before,
+        after
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Removes a member's personal details from their snapshots, so anonymising
+/// or deleting them doesn't leave a copy behind in the log
+pub(crate) async fn forget_member(
+    tx: &mut Transaction<'_, MySql>,
+    id: i32,
+) -> Result<(), sqlx::Error> {
+    let q = format!(
+        r#"
+        UPDATE AUDIT_LOG
+        SET before_state = JSON_REMOVE(before_state, {0}),
+            after_state = JSON_REMOVE(after_state, {0})
+        WHERE entity = 'member' AND entity_id = ?
+        "#,
+        MEMBER_PERSONAL_FIELDS
+    );
+
+    sqlx::query(&q).bind(id).execute(&mut **tx).await?;
+
+    Ok(())
+}
+
+#[derive(Clone)]
+struct AuditFilters {
+    entity: Option<String>,
+    id: Option<i32>,
+    staff_id: Option<i32>,
+}
+
+impl AuditFilters {
+    fn new(query: &AuditSearchParams) -> Result<Self, ApiError> {
+        if query.id.is_some() && query.entity.is_none() {
+            return Err(ApiError::BadRequest(
+                "An entity is required to look up a record by id".to_string(),
+            ));
+        }
+
+        Ok(AuditFilters {
+            entity: query.entity.map(|entity| entity.as_str().to_string()),
+            id: query.id,
+            staff_id: query.staff_id,
+        })
+    }
+
+    /// Binds the parameters for AUDIT_FILTERS in order
+    fn bind<'q, O>(
+        self,
+        query: QueryAs<'q, MySql, O, MySqlArguments>,
+    ) -> QueryAs<'q, MySql, O, MySqlArguments> {
+        query
+            .bind(self.entity.clone())
+            .bind(self.entity)
+            .bind(self.id)
+            .bind(self.id)
+            .bind(self.staff_id)
+            .bind(self.staff_id)
+    }
+}
+
+/// Changes to books, copies, members and borrowings, oldest first
+#[get("/")]
+async fn get_audit(
+    _staff: Admin,
+    req: HttpRequest,
+    query: web::Query<AuditSearchParams>,
+    pool: web::Data<sqlx::MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    let paging = Paging::new(query.page, query.limit, query.cursor.as_deref())?;
+    let filters = AuditFilters::new(&query)?;
+
+    let q = format!(
+        r#"
+        SELECT id, staff_id, staff_username, member_id, entity, entity_id, action,
+            before_state AS `before`, after_state AS `after`, created_at
+        FROM AUDIT_LOG
+        WHERE {} {}
+        ORDER BY {} id
+        LIMIT ? OFFSET ?
+        "#,
+        AUDIT_FILTERS,
+        paging.keyset("id"),
+        paging.key_order("id")
+    );
+
+    let entries: Vec<AuditEntry> = paging
+        .bind(filters.clone().bind(sqlx::query_as(&q)))
+        .bind(paging.limit())
+        .bind(paging.offset())
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    let total_query = format!("SELECT COUNT(*) FROM AUDIT_LOG WHERE {}", AUDIT_FILTERS);
+    let (total,): (i64,) = filters
+        .bind(sqlx::query_as(&total_query))
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(json!(paging.page(&req, entries, total, |entry| entry.id))))
+}
@@ -1,212 +1,1006 @@
-use actix_web::{delete, get, post, put, web, HttpResponse, Responder};
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+
+use actix_web::{delete, get, post, put, web, web::Bytes, HttpRequest, HttpResponse};
+use chrono::Utc;
+use futures::{stream, StreamExt};
 use serde_json::json;
-use tracing::warn;
+use sqlx::{mysql::MySqlArguments, query::QueryAs, MySql, QueryBuilder, Transaction};
+use validator::Validate;
 
-use crate::views::books::{Book, NewBook, SearchParams, UpdateBook};
+use super::audit::{self, Actor};
+use super::pagination::Paging;
+use super::{bulk, holds};
+use crate::auth::{Admin, Librarian};
+use crate::error::ApiError;
+use crate::isbn::Isbn;
+use crate::marc::{self, Record};
+use crate::search::{self, Vocabulary};
+use crate::views::{
+    audit::AuditEntity,
+    books::{
+        Book, BookCsvRow, BookFacets, BookList, BookMatch, FacetCount, NewBook, SearchParams,
+        UpdateBook, WithdrawBook,
+    },
+    bulk::{ExportParams, Format, ImportAction, ImportParams, ImportReport},
+    copies::{Copy, NewCopy},
+    Status,
+};
 
 pub fn books_config(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/books")
+            .app_data(web::PayloadConfig::new(bulk::IMPORT_LIMIT))
             .service(get_books)
+            .service(export_books)
+            .service(import_books)
             .service(create_book)
             .service(update_book)
+            .service(get_book_by_isbn)
             .service(get_book_by_id)
+            .service(get_copies)
+            .service(create_copy)
+            .service(withdraw_book)
+            .service(reinstate_book)
             .service(delete_by_id),
     );
 }
 
+// A title is available as long as at least one of its copies is on the shelf,
+// unless it has been withdrawn
+const BOOK_COLUMNS: &str = r#"
+    b.id,
+    b.title,
+    b.author,
+    b.category,
+    b.isbn_10,
+    b.isbn_13,
+    b.publisher,
+    b.publication_year,
+    COALESCE(b.withdrawn_status, CASE
+        WHEN COALESCE(c.available_copies, 0) > 0 THEN 'available'
+        ELSE 'borrowed'
+    END) AS status,
+    COALESCE(c.available_copies, 0) AS available_copies,
+    COALESCE(c.total_copies, 0) AS total_copies,
+    b.withdrawn_reason,
+    b.withdrawn_at
+"#;
+
+// Filters shared by the book list and the export, see BookFilters::bind. A
+// status of `current` is every book in circulation.
+const BOOK_FILTERS: &str = r#"
+    (? IS NULL OR b.title LIKE CONCAT('%', ?, '%'))
+    AND (? IS NULL OR b.author LIKE CONCAT('%', ?, '%'))
+    AND (? IS NULL OR b.category = ?)
+    AND (? IS NULL OR b.isbn_13 = ?)
+    AND (CASE ?
+        WHEN 'all' THEN TRUE
+        WHEN 'current' THEN b.withdrawn_status IS NULL
+        WHEN 'available' THEN
+            b.withdrawn_status IS NULL AND COALESCE(c.available_copies, 0) > 0
+        WHEN 'borrowed' THEN
+            b.withdrawn_status IS NULL AND COALESCE(c.available_copies, 0) = 0
+        ELSE b.withdrawn_status = ?
+        END)
+"#;
+
+// Relevance of a book to a boolean mode search, backed by the books_search
+// FULLTEXT index
+const BOOK_SEARCH: &str = "MATCH(b.title, b.author, b.category) AGAINST (? IN BOOLEAN MODE)";
+
+// Most values listed for a facet
+const FACET_LIMIT: u32 = 20;
+
+// Column order of the CSV export, matching the fields of Book
+const BOOK_EXPORT_HEADERS: &[&str] = &[
+    "id",
+    "title",
+    "author",
+    "category",
+    "isbn_10",
+    "isbn_13",
+    "publisher",
+    "publication_year",
+    "status",
+    "available_copies",
+    "total_copies",
+    "withdrawn_reason",
+    "withdrawn_at",
+];
+
+// Words of the catalog for fuzzy searches, along with the checksum of the
+// books they were read from
+static VOCABULARY: Mutex<Option<((i64, u64), Arc<Vocabulary>)>> = Mutex::new(None);
+
+#[derive(Clone)]
+struct BookFilters {
+    title: Option<String>,
+    author: Option<String>,
+    category: Option<String>,
+    isbn_13: Option<String>,
+    status: String,
+    search: Option<String>,
+}
+
+impl BookFilters {
+    fn new(query: &SearchParams) -> Result<Self, ApiError> {
+        // Either form of the ISBN finds the book
+        let isbn_13 = match query.isbn.as_deref() {
+            Some(isbn) => Some(parse_isbn(isbn)?.isbn_13),
+            None => None,
+        };
+
+        Ok(BookFilters {
+            title: query.title.clone(),
+            author: query.author.clone(),
+            category: query.category.clone(),
+            isbn_13,
+            status: match &query.status {
+                None => "current",
+                // Overdue and held books are the ones with no copy on the shelf
+                Some(Status::Overdue | Status::OnHold) => "borrowed",
+                Some(status) => status.as_str(),
+            }
+            .to_string(),
+            search: query.q.as_deref().and_then(search::boolean_query),
+        })
+    }
+
+    /// BOOK_FILTERS, plus the search when there is one. The search is only
+    /// added when needed because MySQL can't use the FULLTEXT index for an
+    /// `? IS NULL OR MATCH(...)` condition.
+    fn sql(&self) -> String {
+        match self.search {
+            Some(_) => format!("{} AND {}", BOOK_FILTERS, BOOK_SEARCH),
+            None => BOOK_FILTERS.to_string(),
+        }
+    }
+
+    /// Binds the parameters for `sql` in order
+    fn bind<'q, O>(
+        self,
+        query: QueryAs<'q, MySql, O, MySqlArguments>,
+    ) -> QueryAs<'q, MySql, O, MySqlArguments> {
+        let query = query
+            .bind(self.title.clone())
+            .bind(self.title.unwrap_or_default())
+            .bind(self.author.clone())
+            .bind(self.author.unwrap_or_default())
+            .bind(self.category.clone())
+            .bind(self.category.unwrap_or_default())
+            .bind(self.isbn_13.clone())
+            .bind(self.isbn_13)
+            .bind(self.status.clone())
+            .bind(self.status);
+
+        match self.search {
+            Some(search) => query.bind(search),
+            None => query,
+        }
+    }
+}
+
+fn parse_isbn(value: &str) -> Result<Isbn, ApiError> {
+    Isbn::parse(value).ok_or_else(|| {
+        ApiError::Unprocessable(
+            "ISBN is not valid".to_string(),
+            Some(json!({ "isbn": ["ISBN is not valid"] })),
+        )
+    })
+}
+
+/// The words of every title and author. They are read again only when a
+/// checksum of the books shows they have changed.
+async fn vocabulary(pool: &sqlx::MySqlPool) -> Result<Arc<Vocabulary>, ApiError> {
+    let checksum: (i64, u64) = sqlx::query_as(
+        r#"
+        SELECT
+            COUNT(*),
+            CAST(COALESCE(SUM(CRC32(CONCAT_WS('|', id, title, author))), 0) AS UNSIGNED)
+        FROM BOOKS
+        "#,
+    )
+    .fetch_one(pool)
+    .await?;
+
+    if let Some((cached, vocabulary)) = VOCABULARY.lock().unwrap().as_ref() {
+        if *cached == checksum {
+            return Ok(vocabulary.clone());
+        }
+    }
+
+    let books: Vec<(String, String)> = sqlx::query_as("SELECT title, author FROM BOOKS")
+        .fetch_all(pool)
+        .await?;
+
+    let vocabulary = Arc::new(Vocabulary::new(
+        books
+            .iter()
+            .flat_map(|(title, author)| [title.as_str(), author.as_str()]),
+    ));
+    *VOCABULARY.lock().unwrap() = Some((checksum, vocabulary.clone()));
+
+    Ok(vocabulary)
+}
+
+/// Counts of the books matching the filters by category, status, author and
+/// publication decade
+async fn book_facets(
+    pool: &sqlx::MySqlPool,
+    filters: &BookFilters,
+) -> Result<BookFacets, ApiError> {
+    let (category, status, author, decade) = futures::try_join!(
+        facet(pool, filters, "b.category"),
+        facet(
+            pool,
+            filters,
+            r#"
+            COALESCE(b.withdrawn_status, CASE
+                WHEN COALESCE(c.available_copies, 0) > 0 THEN 'available'
+                ELSE 'borrowed'
+            END)
+            "#
+        ),
+        facet(pool, filters, "b.author"),
+        facet(pool, filters, "b.publication_year DIV 10 * 10"),
+    )?;
+
+    Ok(BookFacets {
+        category,
+        status,
+        author,
+        decade,
+    })
+}
+
+/// Counts the books matching the filters for each value of `column`
+async fn facet<T>(
+    pool: &sqlx::MySqlPool,
+    filters: &BookFilters,
+    column: &str,
+) -> Result<Vec<FacetCount<T>>, sqlx::Error>
+where
+    T: for<'r> sqlx::Decode<'r, MySql> + sqlx::Type<MySql> + Send + Unpin,
+{
+    let q = format!(
+        r#"
+        SELECT {} AS value, COUNT(*) AS count
+        FROM BOOKS b
+        LEFT JOIN BOOK_COPY_COUNTS c ON c.book_id = b.id
+        WHERE {}
+        GROUP BY value
+        ORDER BY count DESC, value
+        LIMIT ?
+        "#,
+        column,
+        filters.sql()
+    );
+
+    let counts: Vec<(Option<T>, i64)> = filters
+        .clone()
+        .bind(sqlx::query_as(&q))
+        .bind(FACET_LIMIT)
+        .fetch_all(pool)
+        .await?;
+
+    Ok(counts
+        .into_iter()
+        .map(|(value, count)| FacetCount { value, count })
+        .collect())
+}
+
+// Two titles can't share an ISBN
+fn duplicate_isbn(error: sqlx::Error) -> ApiError {
+    match error {
+        sqlx::Error::Database(error) if error.is_unique_violation() => {
+            ApiError::Conflict("A book with this ISBN already exists".to_string())
+        }
+        error => error.into(),
+    }
+}
+
 #[get("/")]
 pub async fn get_books(
+    req: HttpRequest,
     query: web::Query<SearchParams>,
     pool: web::Data<sqlx::MySqlPool>,
-) -> impl Responder {
+) -> Result<HttpResponse, ApiError> {
     let order_by = match query.get_order_by().as_str() {
         "id" | "title" | "author" | "category" | "status" | "created_at" => query.get_order_by(),
         _ => "id".to_string(),
     };
 
-    let status = query.get_status();
-    let status_value = status.as_str();
+    let paging = Paging::new(query.page, query.limit, query.cursor.as_deref())?;
+    let mut filters = BookFilters::new(&query)?;
+    if let (Some(true), Some(q)) = (query.fuzzy, query.q.as_deref()) {
+        let vocabulary = vocabulary(pool.get_ref()).await?;
+        filters.search = search::fuzzy_query(q, &vocabulary);
+    }
+    let search = filters.search.clone();
+
+    let facets = match query.facets {
+        Some(true) => Some(book_facets(pool.get_ref(), &filters).await?),
+        _ => None,
+    };
+
+    // Searches are sorted by relevance unless asked otherwise, with the
+    // usual sort breaking ties
+    let by_score = search.is_some() && matches!(query.order_by.as_deref(), None | Some("score"));
+    let score_order = match (by_score, &query.order) {
+        (false, _) => String::new(),
+        (true, Some(order)) => format!("score {},", order.as_str()),
+        (true, None) => "score DESC,".to_string(),
+    };
 
     let q = format!(
         r#"
-        SELECT
-            id,
-            title,
-            author,
-            category,
-            status
-        FROM BOOKS
-        WHERE
-            (? IS NULL OR title LIKE CONCAT('%', ?, '%'))
-            AND (? IS NULL OR author LIKE CONCAT('%', ?, '%'))
-            AND (? IS NULL OR category = ?)
-            AND (CASE
-                WHEN ? IS NULL THEN TRUE
-                WHEN ? = 'all' THEN TRUE
-                ELSE status = ?
-                END)
+        SELECT {}, IF(? IS NULL, NULL, {}) AS score
+        FROM BOOKS b
+        LEFT JOIN BOOK_COPY_COUNTS c ON c.book_id = b.id
+        WHERE {} {}
         ORDER BY
+            {} {}
             CASE
-                WHEN ? = 'id' THEN id
-                WHEN ? = 'title' THEN title
-                WHEN ? = 'author' THEN author
-                WHEN ? = 'category' THEN category
-                WHEN ? = 'status' THEN status
-                ELSE id
+                WHEN ? = 'id' THEN b.id
+                WHEN ? = 'title' THEN b.title
+                WHEN ? = 'author' THEN b.author
+                WHEN ? = 'category' THEN b.category
+                WHEN ? = 'status' THEN COALESCE(c.available_copies, 0) = 0
+                ELSE b.id
             END
             {}
         LIMIT ? OFFSET ?
         "#,
+        BOOK_COLUMNS,
+        BOOK_SEARCH,
+        filters.sql(),
+        paging.keyset("b.id"),
+        paging.key_order("b.id"),
+        score_order,
         query.get_order().as_str()
     );
 
-    let results: Vec<Book> = match sqlx::query_as(&q)
-        .bind(query.title.clone())
-        .bind(query.title.clone().unwrap_or("".to_string()))
-        .bind(query.author.clone())
-        .bind(query.author.clone().unwrap_or("".to_string()))
-        .bind(query.category.clone())
-        .bind(query.category.clone().unwrap_or("".to_string()))
-        .bind(status_value)
-        .bind(status_value)
-        .bind(status_value)
+    let books: Vec<BookMatch> = paging
+        .bind(
+            filters.clone().bind(
+                sqlx::query_as(&q)
+                    .bind(search.clone())
+                    .bind(search.unwrap_or_default()),
+            ),
+        )
         .bind(order_by.as_str())
         .bind(order_by.as_str())
         .bind(order_by.as_str())
         .bind(order_by.as_str())
         .bind(order_by.as_str())
-        .bind(query.limit.unwrap_or(10))
-        .bind(query.get_offset())
+        .bind(paging.limit())
+        .bind(paging.offset())
         .fetch_all(pool.get_ref())
-        .await
-    {
-        Ok(books) => books,
-        Err(error) => {
-            warn!("Failed to fetch books: {:?}", error);
-            return HttpResponse::InternalServerError().finish();
+        .await?;
+
+    let total_query = format!(
+        r#"
+        SELECT COUNT(*)
+        FROM BOOKS b
+        LEFT JOIN BOOK_COPY_COUNTS c ON c.book_id = b.id
+        WHERE {}
+        "#,
+        filters.sql()
+    );
+    let (total,): (i64,) = filters
+        .bind(sqlx::query_as(&total_query))
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    let suggestions = match query.q.as_deref() {
+        Some(q) if books.is_empty() => {
+            let vocabulary = vocabulary(pool.get_ref()).await?;
+            search::suggestions(q, &vocabulary)
         }
+        _ => vec![],
     };
 
-    HttpResponse::Ok().json(json!(results))
+    Ok(HttpResponse::Ok().json(json!(BookList {
+        page: paging.page(&req, books, total, |book| book.book.id),
+        suggestions,
+        facets
+    })))
 }
 
 #[post("/")]
-async fn create_book(data: web::Json<NewBook>, pool: web::Data<sqlx::MySqlPool>) -> impl Responder {
-    let result = sqlx::query!(
+async fn create_book(
+    Librarian(staff): Librarian,
+    data: web::Json<NewBook>,
+    pool: web::Data<sqlx::MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    data.validate()?;
+
+    let mut tx = pool.begin().await?;
+
+    let id = insert_book(&mut tx, &data).await.map_err(duplicate_isbn)?;
+    audit::record(&mut tx, Actor::Staff(staff.id), AuditEntity::Book, id, None).await?;
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::Created().finish())
+}
+
+/// Adds a validated book along with its copies, returning its id
+async fn insert_book(tx: &mut Transaction<'_, MySql>, data: &NewBook) -> Result<i32, sqlx::Error> {
+    let isbn = data.isbn.as_deref().and_then(Isbn::parse);
+
+    let book_id = sqlx::query!(
         r#"
-            INSERT INTO BOOKS (title, author, category)
-            VALUES (?, ?, ?)
+            INSERT INTO BOOKS
+                (title, author, category, isbn_10, isbn_13, publisher, publication_year)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
         "#,
         data.title,
         data.author,
-        data.category
+        data.category,
+        isbn.as_ref().and_then(|isbn| isbn.isbn_10.clone()),
+        isbn.as_ref().map(|isbn| isbn.isbn_13.clone()),
+        data.publisher,
+        data.publication_year
     )
-    .execute(pool.get_ref())
-    .await;
+    .execute(&mut **tx)
+    .await?
+    .last_insert_id();
+
+    // Copies added this way have no barcode yet, they can be labelled later.
+    // They all go in with a single INSERT.
+    let mut copies = QueryBuilder::<MySql>::new("INSERT INTO COPIES (book_id) ");
+    copies.push_values(0..data.copies.unwrap_or(1), |mut row, _| {
+        row.push_bind(book_id);
+    });
+    copies.build().execute(&mut **tx).await?;
+
+    Ok(book_id as i32)
+}
+
+/// Adds the books in an uploaded CSV file with the columns `title`,
+/// `author`, `category`, `isbn`, `publisher`, `publication_year` and
+/// `copies`, or from MARC records. A file from the export can be imported as
+/// it is. A row whose ISBN is already in the catalog updates that book
+/// instead of adding a copy of it.
+#[post("/import")]
+async fn import_books(
+    Librarian(staff): Librarian,
+    params: web::Query<ImportParams>,
+    body: web::Bytes,
+    pool: web::Data<sqlx::MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    let format = bulk::check_format(params.format, &[Format::Csv, Format::Marc, Format::Marcxml])?;
+
+    // MARC records are numbered from 1 in place of line numbers
+    let rows: Vec<bulk::Row<NewBook>> = match format {
+        Format::Marc => marc::read_iso2709(&body)
+            .into_iter()
+            .zip(1..)
+            .map(|(record, number)| (number, record.map(|record| record.to_book())))
+            .collect(),
+        Format::Marcxml => marc::read_marcxml(&body)
+            .map_err(ApiError::BadRequest)?
+            .into_iter()
+            .zip(1..)
+            .map(|(record, number)| (number, Ok(record.to_book())))
+            .collect(),
+        Format::Csv => bulk::read_rows::<BookCsvRow>(&body)?
+            .into_iter()
+            .map(|(line, row)| (line, row.map(NewBook::from)))
+            .collect(),
+    };
+
+    let mut report = ImportReport {
+        dry_run: params.dry_run.unwrap_or(false),
+        ..Default::default()
+    };
+
+    let mut tx = pool.begin().await?;
+
+    for (line, row) in rows {
+        let book = match row {
+            Ok(book) => book,
+            Err(error) => {
+                report.error(line, error);
+                continue;
+            }
+        };
+
+        if let Err(errors) = book.validate() {
+            report.invalid(line, &errors);
+            continue;
+        }
 
-    match result {
-        Ok(_) => HttpResponse::Created().finish(),
-        Err(_) => HttpResponse::InternalServerError().finish(),
+        let existing = match book.isbn.as_deref().and_then(Isbn::parse) {
+            Some(isbn) => {
+                sqlx::query_scalar!(
+                    r#"SELECT id FROM BOOKS WHERE isbn_13 = ? FOR UPDATE"#,
+                    isbn.isbn_13
+                )
+                .fetch_optional(&mut *tx)
+                .await?
+            }
+            None => None,
+        };
+        let before = match existing {
+            Some(id) => audit::snapshot(&mut tx, AuditEntity::Book, id).await?,
+            None => None,
+        };
+
+        let result = match existing {
+            Some(id) => sqlx::query!(
+                r#"
+                    UPDATE BOOKS
+                    SET
+                        title = ?,
+                        author = ?,
+                        category = COALESCE(?, category),
+                        publisher = COALESCE(?, publisher),
+                        publication_year = COALESCE(?, publication_year)
+                    WHERE id = ?
+                "#,
+                book.title,
+                book.author,
+                book.category,
+                book.publisher,
+                book.publication_year,
+                id
+            )
+            .execute(&mut *tx)
+            .await
+            .map(|_| (ImportAction::Update, id)),
+            None => insert_book(&mut tx, &book)
+                .await
+                .map(|id| (ImportAction::Create, id)),
+        };
+
+        match result {
+            Ok((action, id)) => {
+                audit::record(
+                    &mut tx,
+                    Actor::Staff(staff.id),
+                    AuditEntity::Book,
+                    id,
+                    before,
+                )
+                .await?;
+                report.record(line, action, &book);
+            }
+            Err(error) => report.database_error(line, error)?,
+        }
     }
+
+    bulk::finish_import(tx, report).await
+}
+
+/// Streams every book matching the same filters as the book list, ignoring
+/// paging and sorting, as CSV or MARCXML
+#[get("/export")]
+async fn export_books(
+    query: web::Query<SearchParams>,
+    params: web::Query<ExportParams>,
+    pool: web::Data<sqlx::MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    let format = bulk::check_format(params.format, &[Format::Csv, Format::Marcxml])?;
+
+    let filters = BookFilters::new(&query)?;
+    let pool = pool.into_inner();
+
+    let fetch_page = move |after: i32, limit: usize| {
+        let pool = pool.clone();
+        let filters = filters.clone();
+
+        async move {
+            let q = format!(
+                r#"
+                SELECT {}
+                FROM BOOKS b
+                LEFT JOIN BOOK_COPY_COUNTS c ON c.book_id = b.id
+                WHERE {} AND b.id > ?
+                ORDER BY b.id
+                LIMIT ?
+                "#,
+                BOOK_COLUMNS,
+                filters.sql()
+            );
+
+            filters
+                .bind(sqlx::query_as::<_, Book>(&q))
+                .bind(after)
+                .bind(limit as u32)
+                .fetch_all(pool.as_ref())
+                .await
+        }
+    };
+
+    let response = match format {
+        Format::Marcxml => {
+            let records = bulk::page_stream(
+                |book: &Book| book.id,
+                fetch_page,
+                |books: &[Book], first| {
+                    let records: Vec<Record> = books.iter().map(Record::from).collect();
+                    let start = if first { marc::COLLECTION_START } else { "" };
+                    Ok::<_, Infallible>(
+                        format!("{}{}", start, marc::write_marcxml(&records)).into_bytes(),
+                    )
+                },
+            )
+            .chain(stream::once(async {
+                Ok(Bytes::from_static(marc::COLLECTION_END.as_bytes()))
+            }));
+
+            bulk::file_response("application/marcxml+xml", "books.xml", records)
+        }
+        _ => bulk::csv_response(
+            "books.csv",
+            bulk::csv_stream(BOOK_EXPORT_HEADERS, |book: &Book| book.id, fetch_page),
+        ),
+    };
+
+    Ok(response)
 }
 
 #[put("/{id}")]
 async fn update_book(
+    Librarian(staff): Librarian,
     id: web::Path<i32>,
     data: web::Json<UpdateBook>,
     pool: web::Data<sqlx::MySqlPool>,
-) -> impl Responder {
-    let book = match sqlx::query_as!(
-        Book,
+) -> Result<HttpResponse, ApiError> {
+    data.validate()?;
+
+    let mut tx = pool.begin().await?;
+
+    let book = sqlx::query!(
         r#"
-            SELECT id, title, author, category, status
+            SELECT id, title, author, category, isbn_10, isbn_13, publisher, publication_year
             FROM BOOKS
             WHERE id = ?
+            FOR UPDATE
         "#,
         id.clone()
     )
-    .fetch_one(pool.get_ref())
-    .await
-    {
-        Ok(book) => book,
-        Err(_) => return HttpResponse::NotFound().finish(),
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Book not found".to_string()))?;
+    let before = audit::snapshot(&mut tx, AuditEntity::Book, book.id).await?;
+
+    let (isbn_10, isbn_13) = match data.isbn.as_deref().and_then(Isbn::parse) {
+        Some(isbn) => (isbn.isbn_10, Some(isbn.isbn_13)),
+        None => (book.isbn_10, book.isbn_13),
     };
 
-    let result = sqlx::query!(
+    sqlx::query!(
         r#"
             UPDATE BOOKS
-            SET title = ?, author = ?, category = ?, status = ?
+            SET
+                title = ?,
+                author = ?,
+                category = ?,
+                isbn_10 = ?,
+                isbn_13 = ?,
+                publisher = ?,
+                publication_year = ?
             WHERE id = ?
         "#,
-        if data.0.title.is_none() {
-            book.title
-        } else {
-            data.0.title.unwrap()
-        },
-        if data.0.author.is_none() {
-            book.author
-        } else {
-            data.0.author.unwrap()
-        },
-        if data.0.category.is_none() {
-            book.category
-        } else {
-            data.0.category
-        },
-        if data.0.status.is_none() {
-            None
-        } else {
-            Some(data.0.status.unwrap().to_string())
-        },
+        data.title.clone().unwrap_or(book.title),
+        data.author.clone().unwrap_or(book.author),
+        data.category.clone().or(book.category),
+        isbn_10,
+        isbn_13,
+        data.publisher.clone().or(book.publisher),
+        data.publication_year.or(book.publication_year),
         id.clone()
     )
-    .execute(pool.get_ref())
-    .await;
+    .execute(&mut *tx)
+    .await
+    .map_err(duplicate_isbn)?;
 
-    match result {
-        Ok(_) => HttpResponse::NoContent().finish(),
-        Err(error) => {
-            warn!("Failed to update book: {:?}", error);
-            HttpResponse::InternalServerError().finish()
-        }
-    }
+    audit::record(
+        &mut tx,
+        Actor::Staff(staff.id),
+        AuditEntity::Book,
+        book.id,
+        before,
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[get("/isbn/{isbn}")]
+async fn get_book_by_isbn(
+    isbn: web::Path<String>,
+    pool: web::Data<sqlx::MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    let isbn = parse_isbn(&isbn)?;
+
+    let q = format!(
+        r#"
+        SELECT {}
+        FROM BOOKS b
+        LEFT JOIN BOOK_COPY_COUNTS c ON c.book_id = b.id
+        WHERE b.isbn_13 = ?
+        "#,
+        BOOK_COLUMNS
+    );
+
+    let book: Book = sqlx::query_as(&q)
+        .bind(isbn.isbn_13)
+        .fetch_optional(pool.get_ref())
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Book not found".to_string()))?;
+
+    Ok(HttpResponse::Ok().json(json!(book)))
 }
 
 #[get("/{id}")]
-async fn get_book_by_id(id: web::Path<i32>, pool: web::Data<sqlx::MySqlPool>) -> impl Responder {
-    let result = sqlx::query_as!(
-        Book,
+async fn get_book_by_id(
+    id: web::Path<i32>,
+    pool: web::Data<sqlx::MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    let q = format!(
         r#"
-            SELECT id, title, author, category, status
-            FROM BOOKS
-            WHERE id = ?
+        SELECT {}
+        FROM BOOKS b
+        LEFT JOIN BOOK_COPY_COUNTS c ON c.book_id = b.id
+        WHERE b.id = ?
         "#,
-        id.clone()
+        BOOK_COLUMNS
+    );
+
+    let book: Book = sqlx::query_as(&q)
+        .bind(id.into_inner())
+        .fetch_optional(pool.get_ref())
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Book not found".to_string()))?;
+
+    Ok(HttpResponse::Ok().json(json!(book)))
+}
+
+#[get("/{id}/copies")]
+async fn get_copies(
+    id: web::Path<i32>,
+    pool: web::Data<sqlx::MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    let copies: Vec<Copy> = sqlx::query_as(
+        r#"
+        SELECT id, book_id, barcode, shelf_location, `condition`, status
+        FROM COPIES
+        WHERE book_id = ?
+        ORDER BY id
+        "#,
+    )
+    .bind(id.into_inner())
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    Ok(HttpResponse::Ok().json(json!(copies)))
+}
+
+#[post("/{id}/copies")]
+async fn create_copy(
+    Librarian(staff): Librarian,
+    id: web::Path<i32>,
+    data: web::Json<NewCopy>,
+    pool: web::Data<sqlx::MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    let id = id.into_inner();
+    let mut tx = pool.begin().await?;
+
+    sqlx::query!(r#"SELECT id FROM BOOKS WHERE id = ?"#, id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Book not found".to_string()))?;
+
+    let copy_id = sqlx::query!(
+        r#"
+            INSERT INTO COPIES (book_id, barcode, shelf_location, `condition`)
+            VALUES (?, ?, ?, COALESCE(?, 'good'))
+        "#,
+        id,
+        data.barcode,
+        data.shelf_location,
+        data.condition
+    )
+    .execute(&mut *tx)
+    .await?
+    .last_insert_id();
+
+    audit::record(
+        &mut tx,
+        Actor::Staff(staff.id),
+        AuditEntity::Copy,
+        copy_id as i32,
+        None,
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::Created().finish())
+}
+
+/// Takes a book out of circulation, keeping it and its loan history. It drops
+/// out of the catalog unless asked for by status, and can't be borrowed or
+/// held. Holds on the book are cancelled. Withdrawing a withdrawn book again
+/// changes its status and reason.
+#[post("/{id}/withdraw")]
+async fn withdraw_book(
+    Librarian(staff): Librarian,
+    id: web::Path<i32>,
+    data: web::Json<WithdrawBook>,
+    pool: web::Data<sqlx::MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    data.validate()?;
+
+    let date = data.date.unwrap_or_else(|| Utc::now().date_naive());
+    if date > Utc::now().date_naive() {
+        return Err(ApiError::BadRequest(
+            "Withdrawal date can't be in the future".to_string(),
+        ));
+    }
+
+    let id = id.into_inner();
+    let mut tx = pool.begin().await?;
+
+    lock_book(&mut tx, id).await?;
+    let before = audit::snapshot(&mut tx, AuditEntity::Book, id).await?;
+
+    let loans = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*)
+        FROM BORROWINGS br
+        JOIN COPIES c ON c.id = br.copy_id
+        WHERE c.book_id = ? AND br.return_date IS NULL AND br.voided_at IS NULL
+        "#,
+        id
     )
-    .fetch_one(pool.get_ref())
-    .await;
+    .fetch_one(&mut *tx)
+    .await?;
+
+    if loans > 0 {
+        return Err(ApiError::Conflict(format!(
+            "Book still has {} copy(ies) on loan",
+            loans
+        )));
+    }
+
+    sqlx::query!(
+        r#"
+        UPDATE BOOKS
+        SET withdrawn_status = ?, withdrawn_reason = ?, withdrawn_at = ?
+        WHERE id = ?
+        "#,
+        data.status.as_str(),
+        data.reason,
+        date,
+        id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    holds::cancel_book_holds(&mut tx, id).await?;
+    audit::record(
+        &mut tx,
+        Actor::Staff(staff.id),
+        AuditEntity::Book,
+        id,
+        before,
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Puts a withdrawn book back into circulation
+#[post("/{id}/reinstate")]
+async fn reinstate_book(
+    Librarian(staff): Librarian,
+    id: web::Path<i32>,
+    pool: web::Data<sqlx::MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    let id = id.into_inner();
+    let mut tx = pool.begin().await?;
 
-    match result {
-        Ok(book) => HttpResponse::Ok().json(json!(book)),
-        Err(_) => HttpResponse::NotFound().finish(),
+    if lock_book(&mut tx, id).await?.is_none() {
+        return Err(ApiError::Conflict("Book is not withdrawn".to_string()));
     }
+    let before = audit::snapshot(&mut tx, AuditEntity::Book, id).await?;
+
+    sqlx::query!(
+        r#"
+        UPDATE BOOKS
+        SET withdrawn_status = NULL, withdrawn_reason = NULL, withdrawn_at = NULL
+        WHERE id = ?
+        "#,
+        id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    audit::record(
+        &mut tx,
+        Actor::Staff(staff.id),
+        AuditEntity::Book,
+        id,
+        before,
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Locks a book for a change to its status, returning the status it was
+/// withdrawn with, if any
+async fn lock_book(tx: &mut Transaction<'_, MySql>, id: i32) -> Result<Option<String>, ApiError> {
+    let withdrawn_status = sqlx::query_scalar!(
+        r#"SELECT withdrawn_status FROM BOOKS WHERE id = ? FOR UPDATE"#,
+        id
+    )
+    .fetch_optional(&mut **tx)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Book not found".to_string()))?;
+
+    Ok(withdrawn_status)
 }
 
+/// Deletes a book and its copies for good. Only books that have never been
+/// lent can go, anything with a history should be withdrawn instead.
 #[delete("/{id}")]
-async fn delete_by_id(id: web::Path<i32>, pool: web::Data<sqlx::MySqlPool>) -> impl Responder {
-    let result = sqlx::query!(
+async fn delete_by_id(
+    Admin(staff): Admin,
+    id: web::Path<i32>,
+    pool: web::Data<sqlx::MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    let id = id.into_inner();
+    let mut tx = pool.begin().await?;
+
+    lock_book(&mut tx, id).await?;
+
+    let borrowings = sqlx::query_scalar!(
         r#"
-            DELETE FROM BOOKS
-            WHERE id = ?
+        SELECT COUNT(*)
+        FROM BORROWINGS br
+        JOIN COPIES c ON c.id = br.copy_id
+        WHERE c.book_id = ?
         "#,
-        id.clone()
+        id
     )
-    .execute(pool.get_ref())
-    .await;
+    .fetch_one(&mut *tx)
+    .await?;
 
-    match result {
-        Ok(_) => HttpResponse::Ok().finish(),
-        Err(_) => HttpResponse::NotFound().finish(),
+    if borrowings > 0 {
+        return Err(ApiError::Conflict(
+            "Book has a loan history, withdraw it instead".to_string(),
+        ));
     }
+
+    let before = audit::snapshot(&mut tx, AuditEntity::Book, id).await?;
+
+    sqlx::query!(r#"DELETE FROM BOOKS WHERE id = ?"#, id)
+        .execute(&mut *tx)
+        .await?;
+
+    audit::record(
+        &mut tx,
+        Actor::Staff(staff.id),
+        AuditEntity::Book,
+        id,
+        before,
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::NoContent().finish())
 }
@@ -1,11 +1,26 @@
-use actix_web::{delete, get, post, web, HttpResponse, Responder};
-use chrono::Utc;
-use serde_json::json;
+use actix_web::{get, post, put, web, HttpRequest, HttpResponse};
+use chrono::{NaiveDate, Utc};
+use serde_json::{json, Map, Value};
+use sqlx::{mysql::MySqlArguments, query::QueryAs, types::Json, MySql, Transaction};
+use validator::Validate;
 
+use super::audit::{self, Actor};
+use super::eligibility;
+use super::members::MEMBER_COLUMNS;
+use super::pagination::Paging;
+use crate::auth::{Admin, Librarian};
+use crate::config::LibraryConfig;
+use crate::error::ApiError;
+use crate::routes::{fines, holds};
 use crate::views::{
-    books::Book,
-    borrowings::{BorrowParams, BorrowRequest, BorrowedBook},
+    audit::AuditEntity,
+    borrowings::{
+        BorrowParams, BorrowRequest, BorrowedBook, Borrowing, BorrowingSearchParams,
+        CorrectBorrowing, CorrectionAction, ReturnRequest, VoidBorrowing,
+    },
     members::Member,
+    staff::Role,
+    Order,
 };
 
 pub fn borrowings_config(cfg: &mut web::ServiceConfig) {
@@ -13,95 +28,310 @@ pub fn borrowings_config(cfg: &mut web::ServiceConfig) {
         web::scope("/borrows")
             .service(new_borrowing)
             .service(return_books)
+            .service(get_overdue)
+            .service(get_borrowing)
+            .service(renew_borrowing)
+            .service(correct_borrowing)
+            .service(void_borrowing)
             .service(get_borrows_by_id)
             .service(get_all_borrowings),
     );
 }
 
+// Filters for the borrowing list, see BorrowingFilters::bind
+const BORROWING_FILTERS: &str = r#"
+    (CASE
+        WHEN ? = 'all' THEN TRUE
+        WHEN ? = 'borrowed' THEN status IN ('borrowed', 'overdue')
+        ELSE status = ?
+    END)
+    AND (? IS NULL OR id = ?)
+    AND (? IS NULL OR borrower_id = ?)
+    AND (? IS NULL OR borrow_date >= ?)
+    AND (? IS NULL OR borrow_date <= ?)
+    AND (? IS NULL OR due_date >= ?)
+    AND (? IS NULL OR due_date <= ?)
+    AND (? IS NULL OR (status = 'overdue') = ?)
+    AND (? IS NULL OR overdue_days >= ?)
+"#;
+
+#[derive(Clone)]
+struct BorrowingFilters {
+    status: String,
+    book_id: Option<i32>,
+    member_id: Option<i32>,
+    from_date: Option<NaiveDate>,
+    to_date: Option<NaiveDate>,
+    due_from: Option<NaiveDate>,
+    due_to: Option<NaiveDate>,
+    overdue: Option<bool>,
+    min_overdue_days: Option<u32>,
+}
+
+impl BorrowingFilters {
+    fn new(params: &BorrowingSearchParams) -> Self {
+        BorrowingFilters {
+            status: params.get_status().as_borrowing_str().to_string(),
+            book_id: params.book_id,
+            member_id: params.member_id,
+            from_date: params.from_date,
+            to_date: params.to_date,
+            due_from: params.due_from,
+            due_to: params.due_to,
+            overdue: params.overdue,
+            min_overdue_days: params.min_overdue_days,
+        }
+    }
+
+    /// Binds the parameters for BORROWING_FILTERS in order
+    fn bind<'q, O>(
+        self,
+        query: QueryAs<'q, MySql, O, MySqlArguments>,
+    ) -> QueryAs<'q, MySql, O, MySqlArguments> {
+        query
+            .bind(self.status.clone())
+            .bind(self.status.clone())
+            .bind(self.status)
+            .bind(self.book_id)
+            .bind(self.book_id)
+            .bind(self.member_id)
+            .bind(self.member_id)
+            .bind(self.from_date)
+            .bind(self.from_date)
+            .bind(self.to_date)
+            .bind(self.to_date)
+            .bind(self.due_from)
+            .bind(self.due_from)
+            .bind(self.due_to)
+            .bind(self.due_to)
+            .bind(self.overdue)
+            .bind(self.overdue)
+            .bind(self.min_overdue_days)
+            .bind(self.min_overdue_days)
+    }
+}
+
+/// Looks up the book a copy belongs to, by copy id or by barcode
+async fn find_copy(
+    tx: &mut Transaction<'_, MySql>,
+    copy_id: Option<i32>,
+    barcode: Option<&str>,
+) -> Result<Option<(i32, i32)>, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+        SELECT id, book_id FROM COPIES
+        WHERE (? IS NOT NULL AND id = ?) OR (? IS NOT NULL AND barcode = ?)
+        "#,
+    )
+    .bind(copy_id)
+    .bind(copy_id)
+    .bind(barcode)
+    .bind(barcode)
+    .fetch_optional(&mut **tx)
+    .await
+}
+
 #[post("/{id}")]
 async fn new_borrowing(
+    Librarian(staff): Librarian,
     id: web::Path<i32>,
     data: web::Json<BorrowRequest>,
     pool: web::Data<sqlx::MySqlPool>,
-) -> impl Responder {
+    config: web::Data<LibraryConfig>,
+) -> Result<HttpResponse, ApiError> {
     let id = id.into_inner();
 
-    // Check if member exists
-    let member = match sqlx::query_as!(
-        Member,
-        r#"SELECT id, first_name, last_name, email, phone FROM MEMBERS WHERE id = ?"#,
-        id
-    )
-    .fetch_one(pool.get_ref())
-    .await
-    {
-        Ok(member) => member,
-        Err(_) => return HttpResponse::NotFound().json("Member not found"),
+    data.validate()?;
+
+    if data.override_rules && staff.role < Role::Admin {
+        return Err(ApiError::Forbidden(
+            "Only admins can override the borrowing rules".to_string(),
+        ));
+    }
+
+    // Everything below runs in one transaction so the COPIES status and the
+    // BORROWINGS row can never disagree, even if a statement fails halfway.
+    let mut tx = pool.begin().await?;
+
+    // Check if member exists. The row stays locked so two checkouts for the
+    // same member can't both get in under the loan limit.
+    let q = format!(
+        "SELECT {} FROM MEMBERS WHERE id = ? FOR UPDATE",
+        MEMBER_COLUMNS
+    );
+    let member: Member = sqlx::query_as(&q)
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Member not found".to_string()))?;
+
+    if member.deactivated_at.is_some() {
+        return Err(ApiError::Conflict("Member is deactivated".to_string()));
+    }
+
+    // An admin can lend anyway, the rules they overrode are kept on the loan
+    let refusals = eligibility::check(&mut tx, &config, &member).await?;
+    let overridden_rules = if refusals.is_empty() {
+        None
+    } else if data.override_rules {
+        Some(Json(
+            refusals
+                .iter()
+                .map(|refusal| refusal.rule)
+                .collect::<Vec<_>>(),
+        ))
+    } else {
+        return Err(eligibility::refused(&refusals));
     };
 
-    // Check if book exists and is available
-    match sqlx::query!(
-        r#"SELECT id, title, author, category, status FROM BOOKS WHERE id = ? AND status = 'available'"#,
-        id
-    )
-    .fetch_one(pool.get_ref())
-    .await
-    {
-        Ok(_) => {},
-        Err(_) => return HttpResponse::NotFound().json("Book not found or not available"),
+    // A specific copy can be requested, otherwise any copy of the book will do
+    let (requested_copy, book_id) = if data.copy_id.is_some() || data.barcode.is_some() {
+        let (copy_id, book_id) = find_copy(&mut tx, data.copy_id, data.barcode.as_deref())
+            .await?
+            .ok_or_else(|| ApiError::NotFound("Copy not found".to_string()))?;
+        (Some(copy_id), book_id)
+    } else if let Some(book_id) = data.book_id {
+        (None, book_id)
+    } else {
+        return Err(ApiError::BadRequest(
+            "A book_id, copy_id or barcode is required".to_string(),
+        ));
     };
 
-    // Check if book is already borrowed by the member (just in case)
-    let existing_borrow = sqlx::query!(
-        r#"SELECT id FROM BORROWINGS
-           WHERE member_id = ? AND book_id = ? AND return_date IS NULL"#,
-        member.id,
-        data.0.book_id
+    // Lock the book row so concurrent checkouts of the same title queue up
+    // behind this one instead of both seeing the same copy as available
+    let book = sqlx::query!(
+        r#"SELECT id, category, withdrawn_status FROM BOOKS WHERE id = ? FOR UPDATE"#,
+        book_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Book not found".to_string()))?;
+
+    if book.withdrawn_status.is_some() {
+        return Err(ApiError::Conflict("Book has been withdrawn".to_string()));
+    }
+
+    holds::expire_holds(&mut tx, &config, book.id).await?;
+
+    // A copy set aside for this member's hold can only be lent to them
+    let hold = sqlx::query!(
+        r#"
+        SELECT id, copy_id, status FROM HOLDS
+        WHERE book_id = ? AND member_id = ? AND status IN ('waiting', 'ready')
+        FOR UPDATE
+        "#,
+        book.id,
+        member.id
     )
-    .fetch_optional(pool.get_ref())
-    .await;
+    .fetch_optional(&mut *tx)
+    .await?;
+    let held_copy = hold
+        .as_ref()
+        .filter(|hold| hold.status == "ready")
+        .and_then(|hold| hold.copy_id);
+
+    let copies = sqlx::query!(
+        r#"SELECT id, status FROM COPIES WHERE book_id = ? ORDER BY id FOR UPDATE"#,
+        book.id
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let lendable = |copy_id: i32, status: Option<&str>| {
+        status == Some("available") || (status == Some("on_hold") && held_copy == Some(copy_id))
+    };
 
-    if let Ok(Some(_)) = existing_borrow {
-        return HttpResponse::BadRequest().json("Book is already borrowed by this member");
+    // Prefer the requested copy, then the member's held copy, then any copy on the shelf
+    let copy = match requested_copy.or(held_copy) {
+        Some(wanted) => copies
+            .iter()
+            .find(|copy| copy.id == wanted && lendable(copy.id, copy.status.as_deref())),
+        None => copies
+            .iter()
+            .find(|copy| lendable(copy.id, copy.status.as_deref())),
+    };
+
+    let copy = match copy {
+        Some(copy) => copy,
+        None if requested_copy.is_some() => {
+            return Err(ApiError::Conflict("Copy is not available".to_string()))
+        }
+        None => {
+            return Err(ApiError::Conflict(
+                "No copies of this book are available".to_string(),
+            ))
+        }
+    };
+
+    // Borrowing the book takes the member out of the queue, and a copy held
+    // for them that they didn't take goes to whoever is next
+    if let Some(hold) = hold {
+        sqlx::query!(
+            r#"UPDATE HOLDS SET status = 'fulfilled' WHERE id = ?"#,
+            hold.id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        if let Some(held_copy) = held_copy.filter(|held_copy| *held_copy != copy.id) {
+            holds::assign_copy(&mut tx, &config, book.id, held_copy).await?;
+        }
     }
 
+    let loan_days = data
+        .loan_days
+        .unwrap_or_else(|| config.loan_days(book.category.as_deref()));
+
     // Create a new borrowing record
-    match sqlx::query!(
-        r#"INSERT INTO BORROWINGS (member_id, book_id, borrow_date)
-           VALUES (?, ?, CURRENT_TIMESTAMP)"#,
+    let borrowing_id = sqlx::query!(
+        r#"INSERT INTO BORROWINGS
+               (member_id, copy_id, borrow_date, due_date, override_staff_id, overridden_rules)
+           VALUES (?, ?, CURRENT_DATE, DATE_ADD(CURRENT_DATE, INTERVAL ? DAY), ?, ?)"#,
         member.id,
-        data.0.book_id
+        copy.id,
+        loan_days,
+        overridden_rules.as_ref().map(|_| staff.id),
+        overridden_rules
     )
-    .execute(pool.get_ref())
-    .await
-    {
-        Ok(_) => (),
-        Err(_) => return HttpResponse::InternalServerError().finish(),
-    }
+    .execute(&mut *tx)
+    .await?
+    .last_insert_id();
 
-    // Update book availability
-    match sqlx::query!(
-        r#"UPDATE BOOKS SET status = 'borrowed' WHERE id = ?"#,
-        data.0.book_id
+    audit::record(
+        &mut tx,
+        Actor::Staff(staff.id),
+        AuditEntity::Borrowing,
+        borrowing_id as i32,
+        None,
     )
-    .execute(pool.get_ref())
-    .await
-    {
-        Ok(_) => HttpResponse::Ok().json("Book borrowed successfully"),
-        Err(_) => HttpResponse::InternalServerError().finish(),
-    }
-}
+    .await?;
 
-#[get("/{id}")]
-async fn get_borrows_by_id(
-    id: web::Path<i32>,
-    params: web::Query<BorrowParams>,
-    pool: web::Data<sqlx::MySqlPool>,
-) -> impl Responder {
-    let id = id.into_inner();
+    // Update copy availability
+    sqlx::query!(
+        r#"UPDATE COPIES SET status = 'borrowed' WHERE id = ?"#,
+        copy.id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let borrowing = fetch_borrowing(&mut tx, borrowing_id as i32)
+        .await?
+        .ok_or(ApiError::Internal)?;
 
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().json(json!(borrowing)))
+}
+
+/// Books a member has borrowed, filtered by the borrowing status
+pub(crate) async fn member_borrowings(
+    pool: &sqlx::MySqlPool,
+    member_id: i32,
+    params: &BorrowParams,
+) -> Result<Vec<BorrowedBook>, sqlx::Error> {
     let status = params.get_status();
-    let status_value = status.as_str();
+    let status_value = status.as_borrowing_str();
 
     // Get all borrowed books by the member
     // If current is true, get only the books that are not returned yet
@@ -109,190 +339,750 @@ async fn get_borrows_by_id(
     // orders the results by borrow date but allows the user to change the order
     let q = format!(
         r#"
-        SELECT id, title, author, borrower, borrower_id, borrow_date, return_date, status FROM BORROWED_BOOKS
+        SELECT id, borrowing_id, title, author, copy_id, barcode, borrower, borrower_id, borrow_date, due_date, return_date, overdue_days, renewal_count, status
+        FROM BORROWED_BOOKS
         WHERE borrower_id = ?
         AND (CASE
             WHEN ? IS NULL THEN TRUE
             WHEN ? = 'all' THEN TRUE
+            WHEN ? = 'borrowed' THEN status IN ('borrowed', 'overdue')
             ELSE status = ?
             END)
         ORDER BY borrow_date {}
     "#,
-        params.0.get_order().as_str()
+        params.get_order().as_str()
     );
 
-    let results: Vec<BorrowedBook> = match sqlx::query_as(&q)
-        .bind(id)
+    sqlx::query_as(&q)
+        .bind(member_id)
         .bind(status_value)
         .bind(status_value)
         .bind(status_value)
-        .fetch_all(pool.get_ref())
+        .bind(status_value)
+        .fetch_all(pool)
         .await
-    {
-        Ok(results) => results,
-        Err(_) => return HttpResponse::InternalServerError().finish(),
-    };
+}
+
+#[get("/{id}")]
+async fn get_borrows_by_id(
+    id: web::Path<i32>,
+    params: web::Query<BorrowParams>,
+    pool: web::Data<sqlx::MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    let results = member_borrowings(pool.get_ref(), id.into_inner(), &params).await?;
 
-    HttpResponse::Ok().json(json!(results))
+    Ok(HttpResponse::Ok().json(json!(results)))
 }
 
 #[post("/return/")]
 async fn return_books(
-    data: web::Json<BorrowRequest>,
+    Librarian(staff): Librarian,
+    data: web::Json<ReturnRequest>,
     pool: web::Data<sqlx::MySqlPool>,
-) -> impl Responder {
-    let book = match sqlx::query_as!(
-        Book,
-        r#"
-            SELECT id, title, author, category, status
-            FROM BOOKS
-            WHERE id = ?
-        "#,
-        data.book_id
-    )
-    .fetch_one(pool.get_ref())
-    .await
-    {
-        Ok(book) => book,
-        Err(_) => return HttpResponse::NotFound().finish(),
-    };
+    config: web::Data<LibraryConfig>,
+) -> Result<HttpResponse, ApiError> {
+    let mut tx = pool.begin().await?;
 
-    tracing::info!("Book: {:?}", json!(book));
+    let (copy_id, book_id) = find_copy(&mut tx, data.copy_id, data.barcode.as_deref())
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Copy not found".to_string()))?;
 
-    if book.status.as_str() != "borrowed" {
-        return HttpResponse::BadRequest().json(json!("Book is not borrowed"));
-    }
+    // Same lock order as new_borrowing (book, copy, then the borrowing) so a
+    // checkout and a return of the same title can't deadlock each other
+    sqlx::query!(r#"SELECT id FROM BOOKS WHERE id = ? FOR UPDATE"#, book_id)
+        .fetch_one(&mut *tx)
+        .await?;
 
-    let borrowed = match sqlx::query!(
-        r#"
-        SELECT id, book_id, member_id, borrow_date, return_date FROM BORROWINGS WHERE book_id = ? AND return_date IS NULL
-        "#,
-        book.id
+    holds::expire_holds(&mut tx, &config, book_id).await?;
+
+    let copy = sqlx::query!(
+        r#"SELECT id, status FROM COPIES WHERE id = ? FOR UPDATE"#,
+        copy_id
     )
-    .fetch_one(pool.get_ref())
-    .await
-    {
-        Ok(borrowed) => borrowed,
-        Err(_) => return HttpResponse::NotFound().json(json!("Book is not borrowed")),
-    };
+    .fetch_one(&mut *tx)
+    .await?;
 
-    match sqlx::query!(
+    if copy.status.as_deref() != Some("borrowed") {
+        return Err(ApiError::Conflict("Copy is not borrowed".to_string()));
+    }
+
+    let borrowed = sqlx::query!(
         r#"
-            UPDATE BOOKS
-            SET status = 'available'
-            WHERE id = ?
+        SELECT id, member_id, DATEDIFF(CURRENT_DATE, due_date) AS `days_late!: i64`
+        FROM BORROWINGS
+        WHERE copy_id = ? AND return_date IS NULL AND voided_at IS NULL
+        FOR UPDATE
         "#,
-        book.id
+        copy.id
     )
-    .execute(pool.get_ref())
-    .await
-    {
-        Ok(_) => (),
-        Err(_) => return HttpResponse::InternalServerError().finish(),
-    }
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| ApiError::Conflict("Copy is not borrowed".to_string()))?;
+    let before = audit::snapshot(&mut tx, AuditEntity::Borrowing, borrowed.id).await?;
 
-    match sqlx::query!(
+    sqlx::query!(
         r#"
             UPDATE BORROWINGS
-            SET return_date = ?
+            SET return_date = CURRENT_DATE
             WHERE id = ?
         "#,
-        Utc::now(),
         borrowed.id
     )
-    .execute(pool.get_ref())
-    .await
-    {
-        Ok(_) => HttpResponse::Ok().json(json!("Books returned")),
-        Err(_) => HttpResponse::InternalServerError().finish(),
+    .execute(&mut *tx)
+    .await?;
+
+    audit::record(
+        &mut tx,
+        Actor::Staff(staff.id),
+        AuditEntity::Borrowing,
+        borrowed.id,
+        before,
+    )
+    .await?;
+
+    // The copy goes to the next member waiting for it, or back on the shelf
+    holds::assign_copy(&mut tx, &config, book_id, copy.id).await?;
+
+    if let Some(member_id) = borrowed.member_id {
+        fines::accrue_late_fine(&mut tx, &config, member_id, borrowed.id, borrowed.days_late)
+            .await?;
     }
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().json(json!("Books returned")))
 }
 
 #[get("/")]
 async fn get_all_borrowings(
-    params: web::Query<BorrowParams>,
+    req: HttpRequest,
+    params: web::Query<BorrowingSearchParams>,
     pool: web::Data<sqlx::MySqlPool>,
-) -> impl Responder {
-    let status = params.get_status();
-    let status_value = status.as_borrowing_str();
+) -> Result<HttpResponse, ApiError> {
+    let paging = Paging::new(params.page, params.limit, params.cursor.as_deref())?;
+    let filters = BorrowingFilters::new(&params);
 
+    // The order column comes from a fixed list, so it can go in the query as is
     let q = format!(
         r#"
-        SELECT id, title, author, borrower, borrower_id, borrow_date, return_date, status
+        SELECT id, borrowing_id, title, author, copy_id, barcode, borrower, borrower_id, borrow_date, due_date, return_date, overdue_days, renewal_count, status
         FROM BORROWED_BOOKS
-        WHERE (CASE
-            WHEN ? = 'all' THEN TRUE
-            ELSE status = ?
-            END)
-        ORDER BY borrow_date {}
+        WHERE {} {}
+        ORDER BY {} {} {}, borrowing_id {}
+        LIMIT ? OFFSET ?
         "#,
+        BORROWING_FILTERS,
+        paging.keyset("borrowing_id"),
+        paging.key_order("borrowing_id"),
+        params.get_order_by(),
+        params.get_order(),
         params.get_order()
     );
 
-    let results: Vec<BorrowedBook> = match sqlx::query_as(&q)
-        .bind(status_value)
-        .bind(status_value)
+    let results: Vec<BorrowedBook> = paging
+        .bind(filters.clone().bind(sqlx::query_as(&q)))
+        .bind(paging.limit())
+        .bind(paging.offset())
         .fetch_all(pool.get_ref())
-        .await
-    {
-        Ok(results) => results,
-        Err(e) => {
-            tracing::warn!("Error: {:?}", e);
-            return HttpResponse::InternalServerError().finish();
-        }
+        .await?;
+
+    let total_query = format!(
+        "SELECT COUNT(*) FROM BORROWED_BOOKS WHERE {}",
+        BORROWING_FILTERS
+    );
+    let (total,): (i64,) = filters
+        .bind(sqlx::query_as(&total_query))
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(json!(
+        paging.page(&req, results, total, |borrowed| borrowed.borrowing_id)
+    )))
+}
+
+#[get("/overdue")]
+async fn get_overdue(
+    req: HttpRequest,
+    params: web::Query<BorrowParams>,
+    pool: web::Data<sqlx::MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    let paging = Paging::new(params.page, params.limit, params.cursor.as_deref())?;
+
+    // Most overdue first unless the caller asks otherwise
+    let q = format!(
+        r#"
+        SELECT id, borrowing_id, title, author, copy_id, barcode, borrower, borrower_id, borrow_date, due_date, return_date, overdue_days, renewal_count, status
+        FROM BORROWED_BOOKS
+        WHERE status = 'overdue' {}
+        ORDER BY {} due_date {}, borrowing_id
+        LIMIT ? OFFSET ?
+        "#,
+        paging.keyset("borrowing_id"),
+        paging.key_order("borrowing_id"),
+        params.order.clone().unwrap_or(Order::ASC)
+    );
+
+    let results: Vec<BorrowedBook> = paging
+        .bind(sqlx::query_as(&q))
+        .bind(paging.limit())
+        .bind(paging.offset())
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    let (total,): (i64,) =
+        sqlx::query_as("SELECT COUNT(*) FROM BORROWED_BOOKS WHERE status = 'overdue'")
+            .fetch_one(pool.get_ref())
+            .await?;
+
+    Ok(HttpResponse::Ok().json(json!(
+        paging.page(&req, results, total, |borrowed| borrowed.borrowing_id)
+    )))
+}
+
+/// Loads a borrowing together with its renewal history
+async fn fetch_borrowing(
+    executor: &mut sqlx::MySqlConnection,
+    id: i32,
+) -> Result<Option<Borrowing>, sqlx::Error> {
+    let borrowing: Option<Borrowing> = sqlx::query_as(
+        r#"
+        SELECT id, copy_id, member_id, borrow_date, due_date, return_date, renewal_count,
+            voided_at, void_reason, override_staff_id, overridden_rules
+        FROM BORROWINGS
+        WHERE id = ?
+        "#,
+    )
+    .bind(id)
+    .fetch_optional(&mut *executor)
+    .await?;
+
+    let Some(mut borrowing) = borrowing else {
+        return Ok(None);
     };
 
-    HttpResponse::Ok().json(json!(results))
+    borrowing.renewals = sqlx::query_as(
+        r#"
+        SELECT id, borrowing_id, previous_due_date, new_due_date, renewed_at
+        FROM RENEWALS
+        WHERE borrowing_id = ?
+        ORDER BY renewed_at
+        "#,
+    )
+    .bind(id)
+    .fetch_all(&mut *executor)
+    .await?;
+
+    borrowing.corrections = sqlx::query_as(
+        r#"
+        SELECT id, borrowing_id, staff_id, action, changes, reason, created_at
+        FROM BORROWING_CORRECTIONS
+        WHERE borrowing_id = ?
+        ORDER BY created_at, id
+        "#,
+    )
+    .bind(id)
+    .fetch_all(&mut *executor)
+    .await?;
+
+    Ok(Some(borrowing))
 }
 
-#[delete("/{id}")]
-async fn delete_borrowing(id: web::Path<i32>, pool: web::Data<sqlx::MySqlPool>) -> impl Responder {
-    let id = id.into_inner();
+/// Pushes the due date of an open loan forward by another loan period. When a
+/// member renews, the loan must belong to them. Renewals are refused once the
+/// limit is reached or while someone is waiting for the book.
+pub(crate) async fn renew_loan(
+    pool: &sqlx::MySqlPool,
+    config: &LibraryConfig,
+    id: i32,
+    actor: Actor,
+) -> Result<Borrowing, ApiError> {
+    let mut tx = pool.begin().await?;
 
-    // grab the borrowed book entry
-    let borrowing = match sqlx::query!(
+    let book = sqlx::query!(
         r#"
-        SELECT id, book_id, member_id, borrow_date, return_date
+        SELECT b.id, b.category, br.member_id
+        FROM BORROWINGS br
+        JOIN COPIES c ON c.id = br.copy_id
+        JOIN BOOKS b ON b.id = c.book_id
+        WHERE br.id = ?
+        "#,
+        id
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .filter(|book| match actor {
+        Actor::Member(member_id) => book.member_id == Some(member_id),
+        Actor::Staff(_) => true,
+    })
+    .ok_or_else(|| ApiError::NotFound("Borrowing not found".to_string()))?;
+
+    // Lock the book first, same as checkouts and returns, so a hold placed
+    // at the same time is either seen here or placed after the renewal
+    sqlx::query!(r#"SELECT id FROM BOOKS WHERE id = ? FOR UPDATE"#, book.id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+    let borrowing = sqlx::query!(
+        r#"
+        SELECT id, due_date, return_date, renewal_count, voided_at
         FROM BORROWINGS
         WHERE id = ?
+        FOR UPDATE
         "#,
         id
     )
-    .fetch_one(pool.get_ref())
-    .await
-    {
-        Ok(borrowing) => borrowing,
-        Err(_) => return HttpResponse::NotFound().finish(),
-    };
+    .fetch_one(&mut *tx)
+    .await?;
+
+    if borrowing.voided_at.is_some() {
+        return Err(ApiError::Conflict("Borrowing has been voided".to_string()));
+    }
+
+    if borrowing.return_date.is_some() {
+        return Err(ApiError::Conflict(
+            "Book has already been returned".to_string(),
+        ));
+    }
+
+    if borrowing.renewal_count >= config.max_renewals {
+        return Err(ApiError::Conflict(format!(
+            "Loan has already been renewed {} times",
+            borrowing.renewal_count
+        )));
+    }
+
+    let waiting = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) FROM HOLDS WHERE book_id = ? AND status = 'waiting'"#,
+        book.id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    if waiting > 0 {
+        return Err(ApiError::Conflict(
+            "Another member is waiting for this book".to_string(),
+        ));
+    }
+
+    let loan_days = config.loan_days(book.category.as_deref());
+    let before = audit::snapshot(&mut tx, AuditEntity::Borrowing, borrowing.id).await?;
 
-    // delete the borrowed book entry
-    match sqlx::query!(
+    // An overdue loan is extended from today rather than its old due date.
+    // Today is the database's, the same date checkouts and returns go by.
+    sqlx::query!(
         r#"
-        DELETE FROM BORROWINGS
+        UPDATE BORROWINGS
+        SET due_date = DATE_ADD(GREATEST(due_date, CURRENT_DATE), INTERVAL ? DAY),
+            renewal_count = renewal_count + 1
         WHERE id = ?
         "#,
+        loan_days,
+        borrowing.id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO RENEWALS (borrowing_id, previous_due_date, new_due_date)
+        SELECT id, ?, due_date FROM BORROWINGS WHERE id = ?
+        "#,
+        borrowing.due_date,
+        borrowing.id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    audit::record(&mut tx, actor, AuditEntity::Borrowing, borrowing.id, before).await?;
+
+    let renewed = fetch_borrowing(&mut tx, borrowing.id)
+        .await?
+        .ok_or(ApiError::Internal)?;
+
+    tx.commit().await?;
+
+    Ok(renewed)
+}
+
+#[get("/loans/{id}")]
+async fn get_borrowing(
+    id: web::Path<i32>,
+    pool: web::Data<sqlx::MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    let mut conn = pool.acquire().await?;
+
+    let borrowing = fetch_borrowing(&mut conn, id.into_inner())
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Borrowing not found".to_string()))?;
+
+    Ok(HttpResponse::Ok().json(json!(borrowing)))
+}
+
+#[post("/{id}/renew")]
+async fn renew_borrowing(
+    Librarian(staff): Librarian,
+    id: web::Path<i32>,
+    pool: web::Data<sqlx::MySqlPool>,
+    config: web::Data<LibraryConfig>,
+) -> Result<HttpResponse, ApiError> {
+    let renewed = renew_loan(
+        pool.get_ref(),
+        &config,
+        id.into_inner(),
+        Actor::Staff(staff.id),
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(json!(renewed)))
+}
+
+/// Finds the book a borrowing's copy belongs to and locks it, the same lock
+/// checkouts and returns take, then loads the borrowing itself
+async fn lock_borrowing(
+    tx: &mut Transaction<'_, MySql>,
+    id: i32,
+) -> Result<(i32, Borrowing), ApiError> {
+    let book_id = sqlx::query_scalar!(
+        r#"
+        SELECT c.book_id
+        FROM BORROWINGS br
+        JOIN COPIES c ON c.id = br.copy_id
+        WHERE br.id = ?
+        "#,
         id
     )
-    .execute(pool.get_ref())
-    .await
-    {
-        Ok(_) => (),
-        Err(_) => return HttpResponse::InternalServerError().finish(),
+    .fetch_optional(&mut **tx)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Borrowing not found".to_string()))?;
+
+    sqlx::query!(r#"SELECT id FROM BOOKS WHERE id = ? FOR UPDATE"#, book_id)
+        .fetch_one(&mut **tx)
+        .await?;
+
+    sqlx::query!(r#"SELECT id FROM BORROWINGS WHERE id = ? FOR UPDATE"#, id)
+        .fetch_one(&mut **tx)
+        .await?;
+
+    let borrowing = fetch_borrowing(tx, id).await?.ok_or(ApiError::Internal)?;
+
+    if borrowing.voided_at.is_some() {
+        return Err(ApiError::Conflict("Borrowing has been voided".to_string()));
+    }
+
+    Ok((book_id, borrowing))
+}
+
+/// Sets a copy's status from the loans left open on it after a correction:
+/// borrowed while one is, otherwise on to the next hold or back on the shelf.
+/// Copies that weren't lent out are left alone. The caller must hold the lock
+/// on the book.
+async fn refresh_copy(
+    tx: &mut Transaction<'_, MySql>,
+    config: &LibraryConfig,
+    book_id: i32,
+    copy_id: i32,
+) -> Result<(), sqlx::Error> {
+    let open = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) FROM BORROWINGS
+        WHERE copy_id = ? AND return_date IS NULL AND voided_at IS NULL
+        "#,
+        copy_id
+    )
+    .fetch_one(&mut **tx)
+    .await?;
+
+    let status = sqlx::query_scalar!(
+        r#"SELECT status FROM COPIES WHERE id = ? FOR UPDATE"#,
+        copy_id
+    )
+    .fetch_one(&mut **tx)
+    .await?;
+
+    if open > 0 {
+        sqlx::query!(
+            r#"UPDATE COPIES SET status = 'borrowed' WHERE id = ?"#,
+            copy_id
+        )
+        .execute(&mut **tx)
+        .await?;
+    } else if status.as_deref() == Some("borrowed") {
+        holds::assign_copy(tx, config, book_id, copy_id).await?;
+    }
+
+    Ok(())
+}
+
+/// Adds an entry to a borrowing's audit trail
+async fn record_correction(
+    tx: &mut Transaction<'_, MySql>,
+    borrowing_id: i32,
+    staff_id: i32,
+    action: CorrectionAction,
+    changes: Map<String, Value>,
+    reason: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO BORROWING_CORRECTIONS (borrowing_id, staff_id, action, changes, reason)
+        VALUES (?, ?, ?, ?, ?)
+        "#,
+        borrowing_id,
+        staff_id,
+        action.as_str(),
+        Json(changes),
+        reason
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Fixes the member or dates of a borrowing, keeping the old values in its
+/// corrections. A loan moved to another member takes its fines with it, and
+/// its late fee is worked out again from the corrected dates. Clearing the
+/// return date reopens the loan, as long as the copy hasn't been lent out or
+/// set aside since.
+#[put("/loans/{id}")]
+async fn correct_borrowing(
+    Admin(staff): Admin,
+    id: web::Path<i32>,
+    data: web::Json<CorrectBorrowing>,
+    pool: web::Data<sqlx::MySqlPool>,
+    config: web::Data<LibraryConfig>,
+) -> Result<HttpResponse, ApiError> {
+    data.validate()?;
+
+    let mut tx = pool.begin().await?;
+
+    let (book_id, current) = lock_borrowing(&mut tx, id.into_inner()).await?;
+    let before = audit::snapshot(&mut tx, AuditEntity::Borrowing, current.id).await?;
+
+    let member_id = data.member_id.or(current.member_id);
+    let borrow_date = data.borrow_date.unwrap_or(current.borrow_date);
+    let due_date = data.due_date.unwrap_or(current.due_date);
+    let return_date = data.return_date.unwrap_or(current.return_date);
+
+    if due_date < borrow_date {
+        return Err(ApiError::BadRequest(
+            "Due date can't be before the borrow date".to_string(),
+        ));
+    }
+    if return_date.is_some_and(|return_date| return_date < borrow_date) {
+        return Err(ApiError::BadRequest(
+            "Return date can't be before the borrow date".to_string(),
+        ));
+    }
+    // Loan dates are set from the database's clock, so "today" is its date too
+    let today = sqlx::query_scalar!(r#"SELECT CURRENT_DATE AS `today!: NaiveDate`"#)
+        .fetch_one(&mut *tx)
+        .await?;
+    if return_date.is_some_and(|return_date| return_date > today) {
+        return Err(ApiError::BadRequest(
+            "Return date can't be in the future".to_string(),
+        ));
+    }
+
+    // A loan can only be handed to a member who is still active
+    let moved_to = data
+        .member_id
+        .filter(|member_id| Some(*member_id) != current.member_id);
+    if let Some(member_id) = moved_to {
+        let member = sqlx::query!(
+            r#"SELECT deactivated_at, anonymised_at FROM MEMBERS WHERE id = ? FOR UPDATE"#,
+            member_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Member not found".to_string()))?;
+
+        if member.anonymised_at.is_some() {
+            return Err(ApiError::Conflict("Member has been anonymised".to_string()));
+        }
+        if member.deactivated_at.is_some() {
+            return Err(ApiError::Conflict("Member is deactivated".to_string()));
+        }
+    }
+
+    let mut changes = Map::new();
+    let mut change = |field: &str, old: Value, new: Value| {
+        if old != new {
+            changes.insert(field.to_string(), json!({ "old": old, "new": new }));
+        }
+    };
+    change("member_id", json!(current.member_id), json!(member_id));
+    change(
+        "borrow_date",
+        json!(current.borrow_date),
+        json!(borrow_date),
+    );
+    change("due_date", json!(current.due_date), json!(due_date));
+    change(
+        "return_date",
+        json!(current.return_date),
+        json!(return_date),
+    );
+
+    if changes.is_empty() {
+        return Ok(HttpResponse::Ok().json(json!(current)));
     }
 
-    // set the original book status to available (just in case)
-    match sqlx::query!(
+    let copy_id = current.copy_id.ok_or(ApiError::Internal)?;
+    let reopened = current.return_date.is_some() && return_date.is_none();
+    if reopened {
+        let copy = sqlx::query!(
+            r#"
+            SELECT c.status, COUNT(br.id) AS open
+            FROM COPIES c
+            LEFT JOIN BORROWINGS br
+                ON br.copy_id = c.id AND br.return_date IS NULL AND br.voided_at IS NULL
+            WHERE c.id = ?
+            GROUP BY c.id, c.status
+            "#,
+            copy_id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        if copy.open > 0 || copy.status.as_deref() != Some("available") {
+            return Err(ApiError::Conflict(
+                "Copy has been lent out or set aside since it was returned".to_string(),
+            ));
+        }
+    }
+
+    sqlx::query!(
         r#"
-        UPDATE BOOKS
-        SET status = 'available'
+        UPDATE BORROWINGS
+        SET member_id = ?, borrow_date = ?, due_date = ?, return_date = ?
         WHERE id = ?
         "#,
-        borrowing.book_id
+        member_id,
+        borrow_date,
+        due_date,
+        return_date,
+        current.id
     )
-    .execute(pool.get_ref())
-    .await
-    {
-        Ok(_) => HttpResponse::Ok().json(json!("Borrowing deleted")),
-        Err(_) => HttpResponse::InternalServerError().finish(),
+    .execute(&mut *tx)
+    .await?;
+
+    // The loan's fines belong to whoever has the loan
+    if let Some(member_id) = moved_to {
+        sqlx::query!(
+            r#"UPDATE FINES SET member_id = ? WHERE borrowing_id = ?"#,
+            member_id,
+            current.id
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    // The late fee follows the corrected dates, and a reopened loan owes none
+    // until it is returned again
+    if reopened {
+        fines::cancel_late_fine(&mut tx, current.id).await?;
+    } else if let (Some(member_id), Some(return_date)) = (member_id, return_date) {
+        if current.due_date != due_date || current.return_date != Some(return_date) {
+            let days_late = (return_date - due_date).num_days();
+            fines::accrue_late_fine(&mut tx, &config, member_id, current.id, days_late).await?;
+        }
     }
+
+    record_correction(
+        &mut tx,
+        current.id,
+        staff.id,
+        CorrectionAction::Edit,
+        changes,
+        data.reason.as_deref(),
+    )
+    .await?;
+    audit::record(
+        &mut tx,
+        Actor::Staff(staff.id),
+        AuditEntity::Borrowing,
+        current.id,
+        before,
+    )
+    .await?;
+
+    if current.return_date.is_some() != return_date.is_some() {
+        refresh_copy(&mut tx, &config, book_id, copy_id).await?;
+    }
+
+    let corrected = fetch_borrowing(&mut tx, current.id)
+        .await?
+        .ok_or(ApiError::Internal)?;
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().json(json!(corrected)))
+}
+
+/// Voids a borrowing that shouldn't have been recorded. The row is kept with
+/// the reason, but drops out of the loan lists, frees the copy if it was
+/// still out and has what is owed on its fines waived.
+#[post("/loans/{id}/void")]
+async fn void_borrowing(
+    Admin(staff): Admin,
+    id: web::Path<i32>,
+    data: web::Json<VoidBorrowing>,
+    pool: web::Data<sqlx::MySqlPool>,
+    config: web::Data<LibraryConfig>,
+) -> Result<HttpResponse, ApiError> {
+    data.validate()?;
+
+    let mut tx = pool.begin().await?;
+
+    let (book_id, current) = lock_borrowing(&mut tx, id.into_inner()).await?;
+    let before = audit::snapshot(&mut tx, AuditEntity::Borrowing, current.id).await?;
+    let voided_at = Utc::now().naive_utc();
+
+    sqlx::query!(
+        r#"UPDATE BORROWINGS SET voided_at = ?, void_reason = ? WHERE id = ?"#,
+        voided_at,
+        data.reason,
+        current.id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    // A loan that never should have existed can't leave the member owing
+    fines::waive_borrowing_fines(&mut tx, current.id, &data.reason).await?;
+
+    let mut changes = Map::new();
+    changes.insert(
+        "voided_at".to_string(),
+        json!({ "old": null, "new": voided_at }),
+    );
+    record_correction(
+        &mut tx,
+        current.id,
+        staff.id,
+        CorrectionAction::Void,
+        changes,
+        Some(&data.reason),
+    )
+    .await?;
+    audit::record(
+        &mut tx,
+        Actor::Staff(staff.id),
+        AuditEntity::Borrowing,
+        current.id,
+        before,
+    )
+    .await?;
+
+    if let (Some(copy_id), None) = (current.copy_id, current.return_date) {
+        refresh_copy(&mut tx, &config, book_id, copy_id).await?;
+    }
+
+    let voided = fetch_borrowing(&mut tx, current.id)
+        .await?
+        .ok_or(ApiError::Internal)?;
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().json(json!(voided)))
 }
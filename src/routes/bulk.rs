@@ -0,0 +1,269 @@
+use std::future::Future;
+
+use actix_web::{web::Bytes, HttpResponse};
+use futures::{stream, Stream};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::json;
+use sqlx::{MySql, Transaction};
+use validator::ValidationErrors;
+
+use crate::error::ApiError;
+use crate::views::bulk::{Format, ImportAction, ImportError, ImportPreview, ImportReport};
+
+/// Largest CSV file accepted by the import endpoints
+pub(crate) const IMPORT_LIMIT: usize = 10 * 1024 * 1024;
+
+// Rows fetched per query while streaming an export
+const EXPORT_PAGE_SIZE: usize = 500;
+
+/// A parsed CSV row and the line of the file it started on
+pub(crate) type Row<T> = (u64, Result<T, String>);
+
+impl ImportReport {
+    /// Counts a row that was saved, or would be on a dry run
+    pub(crate) fn record<T: Serialize>(&mut self, line: u64, action: ImportAction, record: &T) {
+        match action {
+            ImportAction::Create => self.created += 1,
+            ImportAction::Update => self.updated += 1,
+        }
+
+        if self.dry_run {
+            self.preview.push(ImportPreview {
+                line,
+                action,
+                record: json!(record),
+            });
+        }
+    }
+
+    pub(crate) fn error(&mut self, line: u64, message: impl Into<String>) {
+        self.errors.push(ImportError {
+            line,
+            message: message.into(),
+        });
+    }
+
+    pub(crate) fn invalid(&mut self, line: u64, errors: &ValidationErrors) {
+        let mut messages: Vec<String> = errors
+            .field_errors()
+            .into_iter()
+            .flat_map(|(field, errors)| {
+                errors.iter().map(move |error| match &error.message {
+                    Some(message) => message.to_string(),
+                    None => format!("{} is not valid", field),
+                })
+            })
+            .collect();
+        messages.sort();
+
+        self.error(line, messages.join("; "));
+    }
+
+    /// Records a database error against a row when it is the row's fault
+    /// (a duplicate value and so on), anything else aborts the import
+    pub(crate) fn database_error(&mut self, line: u64, error: sqlx::Error) -> Result<(), ApiError> {
+        match ApiError::from(error) {
+            ApiError::Internal => Err(ApiError::Internal),
+            error => {
+                self.error(line, error.message());
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Parses an uploaded CSV file with a header row into one result per row
+pub(crate) fn read_rows<T: DeserializeOwned>(body: &[u8]) -> Result<Vec<Row<T>>, ApiError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(body);
+
+    let headers = reader
+        .headers()
+        .map_err(|error| ApiError::BadRequest(format!("Could not read the CSV header: {}", error)))?
+        .clone();
+
+    Ok(reader
+        .records()
+        .map(|record| match record {
+            Ok(record) => {
+                let line = record.position().map_or(0, |position| position.line());
+                let row = record
+                    .deserialize(Some(&headers))
+                    .map_err(|error| error.to_string());
+                (line, row)
+            }
+            Err(error) => {
+                let line = error.position().map_or(0, |position| position.line());
+                (line, Err(error.to_string()))
+            }
+        })
+        .collect())
+}
+
+/// Saves the import, or rolls it back for a dry run. Imports are all or
+/// nothing, so any row errors roll everything back and are reported as a 422.
+pub(crate) async fn finish_import(
+    tx: Transaction<'_, MySql>,
+    report: ImportReport,
+) -> Result<HttpResponse, ApiError> {
+    if !report.errors.is_empty() {
+        tx.rollback().await?;
+
+        return Err(ApiError::Unprocessable(
+            format!("{} row(s) could not be imported", report.errors.len()),
+            Some(json!(report)),
+        ));
+    }
+
+    if report.dry_run {
+        tx.rollback().await?;
+    } else {
+        tx.commit().await?;
+    }
+
+    Ok(HttpResponse::Ok().json(json!(report)))
+}
+
+/// Picks the requested format, CSV when none is given, as long as the
+/// endpoint supports it
+pub(crate) fn check_format(
+    format: Option<Format>,
+    supported: &[Format],
+) -> Result<Format, ApiError> {
+    let format = format.unwrap_or_default();
+
+    if !supported.contains(&format) {
+        return Err(ApiError::BadRequest(format!(
+            "Unsupported format: {}",
+            format.as_str()
+        )));
+    }
+
+    Ok(format)
+}
+
+/// Streams rows out a page at a time, so a large export is never held in
+/// memory. `fetch_page` gets the key of the last row sent (0 to start) and
+/// returns the next rows in key order, `write_page` turns them into bytes and
+/// is told whether it is writing the first page.
+pub(crate) fn page_stream<T, F, Fut, W, E>(
+    key: fn(&T) -> i32,
+    fetch_page: F,
+    write_page: W,
+) -> impl Stream<Item = Result<Bytes, actix_web::Error>>
+where
+    F: FnMut(i32, usize) -> Fut,
+    Fut: Future<Output = Result<Vec<T>, sqlx::Error>>,
+    W: Fn(&[T], bool) -> Result<Vec<u8>, E>,
+    E: std::fmt::Debug,
+{
+    stream::unfold(
+        (fetch_page, write_page, Some(0), true),
+        move |(mut fetch_page, write_page, after, first)| async move {
+            let after = after?;
+
+            let rows = match fetch_page(after, EXPORT_PAGE_SIZE).await {
+                Ok(rows) => rows,
+                Err(error) => {
+                    let error = ApiError::from(error).into();
+                    return Some((Err(error), (fetch_page, write_page, None, false)));
+                }
+            };
+
+            if rows.is_empty() && !first {
+                return None;
+            }
+
+            // A short page means there is nothing after it
+            let next = match rows.len() {
+                len if len < EXPORT_PAGE_SIZE => None,
+                _ => rows.last().map(key),
+            };
+
+            let chunk = write_page(&rows, first).map(Bytes::from).map_err(|error| {
+                tracing::warn!("Failed to write export: {:?}", error);
+                ApiError::Internal.into()
+            });
+
+            Some((chunk, (fetch_page, write_page, next, false)))
+        },
+    )
+}
+
+/// [`page_stream`] for CSV, with a header row at the start
+pub(crate) fn csv_stream<T, F, Fut>(
+    headers: &'static [&'static str],
+    key: fn(&T) -> i32,
+    fetch_page: F,
+) -> impl Stream<Item = Result<Bytes, actix_web::Error>>
+where
+    T: Serialize,
+    F: FnMut(i32, usize) -> Fut,
+    Fut: Future<Output = Result<Vec<T>, sqlx::Error>>,
+{
+    page_stream(key, fetch_page, move |rows: &[T], first| {
+        write_csv(headers, rows, first)
+    })
+}
+
+fn write_csv<T: Serialize>(
+    headers: &[&str],
+    rows: &[T],
+    with_headers: bool,
+) -> Result<Vec<u8>, csv::Error> {
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(vec![]);
+
+    if with_headers {
+        writer.write_record(headers)?;
+    }
+
+    for row in rows {
+        writer.serialize(row)?;
+    }
+
+    writer
+        .into_inner()
+        .map_err(|error| csv::Error::from(error.into_error()))
+}
+
+/// CSV download of rows that are already in memory, with a header row
+pub(crate) fn csv_rows<T: Serialize>(
+    filename: &str,
+    headers: &[&str],
+    rows: &[T],
+) -> Result<HttpResponse, ApiError> {
+    let body = write_csv(headers, rows, true).map_err(|error| {
+        tracing::warn!("Failed to write export: {:?}", error);
+        ApiError::Internal
+    })?;
+
+    Ok(csv_response(
+        filename,
+        stream::once(async move { Ok(Bytes::from(body)) }),
+    ))
+}
+
+/// Response for a streamed download
+pub(crate) fn file_response<S>(content_type: &str, filename: &str, body: S) -> HttpResponse
+where
+    S: Stream<Item = Result<Bytes, actix_web::Error>> + 'static,
+{
+    HttpResponse::Ok()
+        .content_type(content_type)
+        .insert_header((
+            "Content-Disposition",
+            format!("attachment; filename=\"{}\"", filename),
+        ))
+        .streaming(body)
+}
+
+/// Response for a streamed CSV download
+pub(crate) fn csv_response<S>(filename: &str, body: S) -> HttpResponse
+where
+    S: Stream<Item = Result<Bytes, actix_web::Error>> + 'static,
+{
+    file_response("text/csv; charset=utf-8", filename, body)
+}
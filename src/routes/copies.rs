@@ -0,0 +1,160 @@
+use actix_web::{delete, get, put, web, HttpResponse};
+use serde_json::json;
+
+use super::audit::{self, Actor};
+use crate::auth::{Admin, Librarian};
+use crate::error::ApiError;
+use crate::views::{
+    audit::AuditEntity,
+    copies::{Copy, UpdateCopy},
+};
+
+pub fn copies_config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/copies")
+            .service(get_copy_by_barcode)
+            .service(get_copy_by_id)
+            .service(update_copy)
+            .service(delete_copy),
+    );
+}
+
+#[get("/barcode/{barcode}")]
+async fn get_copy_by_barcode(
+    barcode: web::Path<String>,
+    pool: web::Data<sqlx::MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    let copy: Copy = sqlx::query_as(
+        r#"
+        SELECT id, book_id, barcode, shelf_location, `condition`, status
+        FROM COPIES
+        WHERE barcode = ?
+        "#,
+    )
+    .bind(barcode.into_inner())
+    .fetch_optional(pool.get_ref())
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Copy not found".to_string()))?;
+
+    Ok(HttpResponse::Ok().json(json!(copy)))
+}
+
+#[get("/{id}")]
+async fn get_copy_by_id(
+    id: web::Path<i32>,
+    pool: web::Data<sqlx::MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    let copy: Copy = sqlx::query_as(
+        r#"
+        SELECT id, book_id, barcode, shelf_location, `condition`, status
+        FROM COPIES
+        WHERE id = ?
+        "#,
+    )
+    .bind(id.into_inner())
+    .fetch_optional(pool.get_ref())
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Copy not found".to_string()))?;
+
+    Ok(HttpResponse::Ok().json(json!(copy)))
+}
+
+#[put("/{id}")]
+async fn update_copy(
+    Librarian(staff): Librarian,
+    id: web::Path<i32>,
+    data: web::Json<UpdateCopy>,
+    pool: web::Data<sqlx::MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    let mut tx = pool.begin().await?;
+
+    let copy = sqlx::query!(
+        r#"
+            SELECT id, barcode, shelf_location, `condition`
+            FROM COPIES
+            WHERE id = ?
+            FOR UPDATE
+        "#,
+        id.into_inner()
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Copy not found".to_string()))?;
+    let before = audit::snapshot(&mut tx, AuditEntity::Copy, copy.id).await?;
+
+    sqlx::query!(
+        r#"
+            UPDATE COPIES
+            SET barcode = ?, shelf_location = ?, `condition` = ?
+            WHERE id = ?
+        "#,
+        data.barcode.clone().or(copy.barcode),
+        data.shelf_location.clone().or(copy.shelf_location),
+        data.condition.clone().or(copy.condition),
+        copy.id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    audit::record(
+        &mut tx,
+        Actor::Staff(staff.id),
+        AuditEntity::Copy,
+        copy.id,
+        before,
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Deletes a copy for good. Only copies that have never been lent can go, so
+/// deleting one never takes loan history with it.
+#[delete("/{id}")]
+async fn delete_copy(
+    Admin(staff): Admin,
+    id: web::Path<i32>,
+    pool: web::Data<sqlx::MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    let id = id.into_inner();
+    let mut tx = pool.begin().await?;
+
+    let copy = sqlx::query!(r#"SELECT id FROM COPIES WHERE id = ? FOR UPDATE"#, id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Copy not found".to_string()))?;
+
+    let borrowings = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) FROM BORROWINGS WHERE copy_id = ?"#,
+        copy.id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    if borrowings > 0 {
+        return Err(ApiError::Conflict(
+            "Copy has a loan history and can't be deleted".to_string(),
+        ));
+    }
+
+    let before = audit::snapshot(&mut tx, AuditEntity::Copy, copy.id).await?;
+
+    sqlx::query!(r#"DELETE FROM COPIES WHERE id = ?"#, copy.id)
+        .execute(&mut *tx)
+        .await?;
+
+    audit::record(
+        &mut tx,
+        Actor::Staff(staff.id),
+        AuditEntity::Copy,
+        copy.id,
+        before,
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -0,0 +1,111 @@
+use serde_json::{json, Map, Value};
+use sqlx::{MySql, Transaction};
+
+use super::fines;
+use crate::config::LibraryConfig;
+use crate::error::ApiError;
+use crate::views::{borrowings::BorrowingRule, members::Member};
+
+/// A borrowing rule the member breaks, with the reason to show at the desk
+pub(crate) struct Refusal {
+    pub(crate) rule: BorrowingRule,
+    pub(crate) message: String,
+}
+
+/// Where the member stands on the date-based rules, judged by the database's
+/// date like the loans themselves
+struct Standing {
+    expired: bool,
+    loans: i64,
+    overdue: i64,
+}
+
+/// Checks the member against every borrowing rule, returning the ones they
+/// break. Nothing is returned when they can borrow.
+pub(crate) async fn check(
+    tx: &mut Transaction<'_, MySql>,
+    config: &LibraryConfig,
+    member: &Member,
+) -> Result<Vec<Refusal>, sqlx::Error> {
+    let mut refusals = vec![];
+
+    let Standing {
+        expired,
+        loans,
+        overdue,
+    } = sqlx::query_as!(
+        Standing,
+        r#"
+        SELECT
+            COALESCE(m.membership_expires_at < CURRENT_DATE, FALSE) AS `expired!: bool`,
+            COUNT(br.id) AS `loans!: i64`,
+            CAST(COALESCE(SUM(br.due_date < CURRENT_DATE), 0) AS SIGNED) AS `overdue!: i64`
+        FROM MEMBERS m
+        LEFT JOIN BORROWINGS br
+            ON br.member_id = m.id AND br.return_date IS NULL AND br.voided_at IS NULL
+        WHERE m.id = ?
+        GROUP BY m.id, m.membership_expires_at
+        "#,
+        member.id
+    )
+    .fetch_one(&mut **tx)
+    .await?;
+
+    if let (true, Some(expires_at)) = (expired, member.membership_expires_at) {
+        refusals.push(Refusal {
+            rule: BorrowingRule::MembershipExpired,
+            message: format!("Membership expired on {}", expires_at),
+        });
+    }
+
+    let max_loans = config.max_loans(member.member_type);
+    if loans >= i64::from(max_loans) {
+        refusals.push(Refusal {
+            rule: BorrowingRule::LoanLimit,
+            message: format!(
+                "Member already has {} loans, the most allowed for a member of type {}",
+                loans,
+                member.member_type.as_str()
+            ),
+        });
+    }
+
+    if overdue > 0 {
+        refusals.push(Refusal {
+            rule: BorrowingRule::OverdueLoans,
+            message: format!(
+                "Member has {} overdue {} to return first",
+                overdue,
+                if overdue == 1 { "loan" } else { "loans" }
+            ),
+        });
+    }
+
+    let balance = fines::member_balance(&mut **tx, member.id).await?;
+    if balance.balance_cents > config.max_balance_cents {
+        refusals.push(Refusal {
+            rule: BorrowingRule::UnpaidFines,
+            message: format!(
+                "Member owes {} cents in fines, more than the {} cents allowed",
+                balance.balance_cents, config.max_balance_cents
+            ),
+        });
+    }
+
+    Ok(refusals)
+}
+
+/// The error for a checkout the rules don't allow. `details` maps each rule
+/// that was broken to its reason, e.g.
+/// `"details": {"loan_limit": ["Member already has 10 loans, ..."]}`
+pub(crate) fn refused(refusals: &[Refusal]) -> ApiError {
+    let details: Map<String, Value> = refusals
+        .iter()
+        .map(|refusal| (refusal.rule.as_str().to_string(), json!([refusal.message])))
+        .collect();
+
+    ApiError::Unprocessable(
+        "Member is not allowed to borrow".to_string(),
+        Some(Value::Object(details)),
+    )
+}
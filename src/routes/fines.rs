@@ -0,0 +1,409 @@
+use actix_web::{get, post, web, HttpResponse};
+use serde_json::json;
+use sqlx::{MySql, Transaction};
+use validator::Validate;
+
+use super::pagination::Paging;
+use crate::auth::Librarian;
+use crate::config::LibraryConfig;
+use crate::error::ApiError;
+use crate::views::fines::{
+    Fine, FineDetails, FineKind, FinePayment, FineSearchParams, FineTransaction, FineWaiver,
+    MemberBalance, NewFine,
+};
+
+pub fn fines_config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/fines")
+            .service(get_fines)
+            .service(create_fine)
+            .service(get_fine_by_id)
+            .service(pay_fine)
+            .service(waive_fine),
+    );
+}
+
+/// Charges a late fee for a borrowing returned `days_late` days after it was
+/// due. Runs inside the return or correction transaction so the fee and the
+/// loan's dates are recorded together. A fee already charged for the
+/// borrowing is changed to the new amount instead, though never to less than
+/// was paid or waived on it. Returns the amount charged, which may be zero.
+pub(crate) async fn accrue_late_fine(
+    tx: &mut Transaction<'_, MySql>,
+    config: &LibraryConfig,
+    member_id: i32,
+    borrowing_id: i32,
+    days_late: i64,
+) -> Result<i64, sqlx::Error> {
+    let amount = config.late_fine_cents(days_late);
+    let reason = format!("Returned {} day(s) late", days_late);
+
+    let charged = sqlx::query!(
+        r#"SELECT id FROM FINES WHERE borrowing_id = ? AND kind = 'late' FOR UPDATE"#,
+        borrowing_id
+    )
+    .fetch_optional(&mut **tx)
+    .await?;
+
+    if let Some(fine) = charged {
+        set_fine_amount(tx, fine.id, amount, &reason).await?;
+    } else if amount > 0 {
+        sqlx::query!(
+            r#"
+            INSERT INTO FINES (member_id, borrowing_id, kind, amount_cents, reason)
+            VALUES (?, ?, 'late', ?, ?)
+            "#,
+            member_id,
+            borrowing_id,
+            amount,
+            reason
+        )
+        .execute(&mut **tx)
+        .await?;
+    }
+
+    Ok(amount)
+}
+
+/// Drops the late fee of a borrowing that has been reopened, leaving only
+/// what was already paid or waived on it. The fee is charged again when the
+/// loan is returned.
+pub(crate) async fn cancel_late_fine(
+    tx: &mut Transaction<'_, MySql>,
+    borrowing_id: i32,
+) -> Result<(), sqlx::Error> {
+    let charged = sqlx::query!(
+        r#"SELECT id FROM FINES WHERE borrowing_id = ? AND kind = 'late' FOR UPDATE"#,
+        borrowing_id
+    )
+    .fetch_optional(&mut **tx)
+    .await?;
+
+    if let Some(fine) = charged {
+        set_fine_amount(tx, fine.id, 0, "Loan reopened").await?;
+    }
+
+    Ok(())
+}
+
+/// Changes the amount of a fine, keeping it at least what has been paid or
+/// waived so its balance never goes negative. The caller must hold the lock
+/// on the fine.
+async fn set_fine_amount(
+    tx: &mut Transaction<'_, MySql>,
+    fine_id: i32,
+    amount: i64,
+    reason: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE FINES f
+        SET f.amount_cents = GREATEST(?, (
+                SELECT COALESCE(SUM(p.amount_cents), 0)
+                FROM FINE_PAYMENTS p
+                WHERE p.fine_id = f.id
+            )),
+            f.reason = ?
+        WHERE f.id = ?
+        "#,
+        amount,
+        reason,
+        fine_id
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Waives whatever is still owed on the fines of a borrowing, giving the
+/// reason on each waiver. Used when the borrowing is voided.
+pub(crate) async fn waive_borrowing_fines(
+    tx: &mut Transaction<'_, MySql>,
+    borrowing_id: i32,
+    reason: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"SELECT id FROM FINES WHERE borrowing_id = ? FOR UPDATE"#,
+        borrowing_id
+    )
+    .fetch_all(&mut **tx)
+    .await?;
+
+    let outstanding = sqlx::query!(
+        r#"
+        SELECT id, balance_cents
+        FROM FINE_BALANCES
+        WHERE borrowing_id = ? AND balance_cents > 0
+        "#,
+        borrowing_id
+    )
+    .fetch_all(&mut **tx)
+    .await?;
+
+    for fine in outstanding {
+        sqlx::query!(
+            r#"
+            INSERT INTO FINE_PAYMENTS (fine_id, kind, amount_cents, reason)
+            VALUES (?, 'waiver', ?, ?)
+            "#,
+            fine.id,
+            fine.balance_cents,
+            reason
+        )
+        .execute(&mut **tx)
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Totals every fine charged to a member along with what has been paid or
+/// waived against them
+pub(crate) async fn member_balance(
+    executor: impl sqlx::MySqlExecutor<'_>,
+    member_id: i32,
+) -> Result<MemberBalance, sqlx::Error> {
+    sqlx::query_as!(
+        MemberBalance,
+        r#"
+        SELECT
+            CAST(? AS SIGNED) AS `member_id!: i32`,
+            CAST(COALESCE(SUM(amount_cents), 0) AS SIGNED) AS `charged_cents!: i64`,
+            CAST(COALESCE(SUM(paid_cents), 0) AS SIGNED) AS `paid_cents!: i64`,
+            CAST(COALESCE(SUM(waived_cents), 0) AS SIGNED) AS `waived_cents!: i64`,
+            CAST(COALESCE(SUM(balance_cents), 0) AS SIGNED) AS `balance_cents!: i64`
+        FROM FINE_BALANCES
+        WHERE member_id = ?
+        "#,
+        member_id,
+        member_id
+    )
+    .fetch_one(executor)
+    .await
+}
+
+/// Fines matching the search, newest first
+pub(crate) async fn find_fines(
+    pool: &sqlx::MySqlPool,
+    query: &FineSearchParams,
+) -> Result<Vec<Fine>, ApiError> {
+    let paging = Paging::new(query.page, query.limit, None)?;
+
+    let fines = sqlx::query_as!(
+        Fine,
+        r#"
+        SELECT id AS `id!`, member_id, borrowing_id, kind AS `kind!: FineKind`,
+               amount_cents AS `amount_cents!: i64`, paid_cents AS `paid_cents!: i64`,
+               waived_cents AS `waived_cents!: i64`, balance_cents AS `balance_cents!: i64`,
+               reason, created_at AS `created_at!`
+        FROM FINE_BALANCES
+        WHERE
+            (? IS NULL OR member_id = ?)
+            AND (? IS NULL OR (balance_cents > 0) = ?)
+        ORDER BY created_at DESC
+        LIMIT ? OFFSET ?
+        "#,
+        query.member_id,
+        query.member_id,
+        query.outstanding,
+        query.outstanding,
+        paging.limit(),
+        paging.offset()
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(fines)
+}
+
+#[get("/")]
+async fn get_fines(
+    query: web::Query<FineSearchParams>,
+    pool: web::Data<sqlx::MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    let results = find_fines(pool.get_ref(), &query).await?;
+
+    Ok(HttpResponse::Ok().json(json!(results)))
+}
+
+#[post("/")]
+async fn create_fine(
+    _staff: Librarian,
+    data: web::Json<NewFine>,
+    pool: web::Data<sqlx::MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    data.validate()?;
+
+    // Late fees are accrued automatically when a book is returned
+    if data.kind == FineKind::Late {
+        return Err(ApiError::BadRequest(
+            "Late fees cannot be charged manually".to_string(),
+        ));
+    }
+
+    sqlx::query!(r#"SELECT id FROM MEMBERS WHERE id = ?"#, data.member_id)
+        .fetch_optional(pool.get_ref())
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Member not found".to_string()))?;
+
+    // A fine for a loan has to be charged to the member who borrowed it
+    if let Some(borrowing_id) = data.borrowing_id {
+        sqlx::query!(
+            r#"SELECT id FROM BORROWINGS WHERE id = ? AND member_id = ?"#,
+            borrowing_id,
+            data.member_id
+        )
+        .fetch_optional(pool.get_ref())
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Borrowing not found".to_string()))?;
+    }
+
+    sqlx::query!(
+        r#"
+            INSERT INTO FINES (member_id, borrowing_id, kind, amount_cents, reason)
+            VALUES (?, ?, ?, ?, ?)
+        "#,
+        data.member_id,
+        data.borrowing_id,
+        data.kind.as_str(),
+        data.amount_cents,
+        data.reason
+    )
+    .execute(pool.get_ref())
+    .await?;
+
+    Ok(HttpResponse::Created().finish())
+}
+
+#[get("/{id}")]
+async fn get_fine_by_id(
+    id: web::Path<i32>,
+    pool: web::Data<sqlx::MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    let id = id.into_inner();
+
+    let fine = sqlx::query_as!(
+        Fine,
+        r#"
+        SELECT id AS `id!`, member_id, borrowing_id, kind AS `kind!: FineKind`,
+               amount_cents AS `amount_cents!: i64`, paid_cents AS `paid_cents!: i64`,
+               waived_cents AS `waived_cents!: i64`, balance_cents AS `balance_cents!: i64`,
+               reason, created_at AS `created_at!`
+        FROM FINE_BALANCES
+        WHERE id = ?
+        "#,
+        id
+    )
+    .fetch_optional(pool.get_ref())
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Fine not found".to_string()))?;
+
+    let transactions = sqlx::query_as!(
+        FineTransaction,
+        r#"
+        SELECT id, fine_id, kind, amount_cents AS `amount_cents: i64`, reason, created_at
+        FROM FINE_PAYMENTS
+        WHERE fine_id = ?
+        ORDER BY created_at
+        "#,
+        id
+    )
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    Ok(HttpResponse::Ok().json(json!(FineDetails { fine, transactions })))
+}
+
+#[post("/{id}/payments")]
+async fn pay_fine(
+    _staff: Librarian,
+    id: web::Path<i32>,
+    data: web::Json<FinePayment>,
+    pool: web::Data<sqlx::MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    data.validate()?;
+
+    record_transaction(
+        id.into_inner(),
+        "payment",
+        Some(data.amount_cents),
+        data.reason.clone(),
+        pool.get_ref(),
+    )
+    .await
+}
+
+#[post("/{id}/waivers")]
+async fn waive_fine(
+    _staff: Librarian,
+    id: web::Path<i32>,
+    data: web::Json<FineWaiver>,
+    pool: web::Data<sqlx::MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    data.validate()?;
+
+    record_transaction(
+        id.into_inner(),
+        "waiver",
+        data.amount_cents,
+        Some(data.reason.clone()),
+        pool.get_ref(),
+    )
+    .await
+}
+
+/// Records a payment or waiver against a fine, never letting the total go
+/// past what is still owed. A missing amount settles the whole balance.
+async fn record_transaction(
+    fine_id: i32,
+    kind: &str,
+    amount_cents: Option<i64>,
+    reason: Option<String>,
+    pool: &sqlx::MySqlPool,
+) -> Result<HttpResponse, ApiError> {
+    let mut tx = pool.begin().await?;
+
+    // Lock the fine so two payments can't both settle the same balance
+    sqlx::query!(r#"SELECT id FROM FINES WHERE id = ? FOR UPDATE"#, fine_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Fine not found".to_string()))?;
+
+    let balance = sqlx::query_scalar!(
+        r#"SELECT balance_cents AS `balance_cents!: i64` FROM FINE_BALANCES WHERE id = ?"#,
+        fine_id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    if balance <= 0 {
+        return Err(ApiError::Conflict("Fine is already settled".to_string()));
+    }
+
+    let amount = amount_cents.unwrap_or(balance);
+
+    if amount > balance {
+        return Err(ApiError::BadRequest(format!(
+            "Amount exceeds the outstanding balance of {} cents",
+            balance
+        )));
+    }
+
+    sqlx::query!(
+        r#"
+        INSERT INTO FINE_PAYMENTS (fine_id, kind, amount_cents, reason)
+        VALUES (?, ?, ?, ?)
+        "#,
+        fine_id,
+        kind,
+        amount,
+        reason
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::Created().json(json!({ "balance_cents": balance - amount })))
+}
@@ -0,0 +1,512 @@
+use actix_web::{delete, get, post, put, web, HttpResponse};
+use serde_json::json;
+use sqlx::{MySql, Transaction};
+
+use super::pagination::Paging;
+use crate::auth::Librarian;
+use crate::config::LibraryConfig;
+use crate::error::ApiError;
+use crate::views::holds::{Hold, HoldPosition, HoldSearchParams, NewHold};
+
+pub fn holds_config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/holds")
+            .service(get_holds)
+            .service(create_hold)
+            .service(get_hold_by_id)
+            .service(move_hold)
+            .service(cancel_by_id),
+    );
+}
+
+const HOLD_COLUMNS: &str = r#"
+    h.id, h.book_id, b.title, h.member_id, h.copy_id, h.position, h.status,
+    h.created_at, h.ready_at, h.expires_at
+"#;
+
+/// Hands a copy that just came back (or was released from an expired or
+/// cancelled hold) to the next member waiting for the book, or puts it back
+/// on the shelf when nobody is. The caller must hold the lock on the book.
+/// Returns whether the copy was set aside for a hold.
+pub(crate) async fn assign_copy(
+    tx: &mut Transaction<'_, MySql>,
+    config: &LibraryConfig,
+    book_id: i32,
+    copy_id: i32,
+) -> Result<bool, sqlx::Error> {
+    let next = sqlx::query!(
+        r#"
+        SELECT id FROM HOLDS
+        WHERE book_id = ? AND status = 'waiting'
+        ORDER BY position, id
+        LIMIT 1
+        FOR UPDATE
+        "#,
+        book_id
+    )
+    .fetch_optional(&mut **tx)
+    .await?;
+
+    let Some(next) = next else {
+        sqlx::query!(
+            r#"UPDATE COPIES SET status = 'available' WHERE id = ?"#,
+            copy_id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        return Ok(false);
+    };
+
+    sqlx::query!(
+        r#"
+        UPDATE HOLDS
+        SET status = 'ready', copy_id = ?, ready_at = NOW(),
+            expires_at = DATE_ADD(NOW(), INTERVAL ? DAY)
+        WHERE id = ?
+        "#,
+        copy_id,
+        config.hold_pickup_days,
+        next.id
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    sqlx::query!(
+        r#"UPDATE COPIES SET status = 'on_hold' WHERE id = ?"#,
+        copy_id
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(true)
+}
+
+/// Expires ready holds on a book that were not picked up in time and passes
+/// their copies on down the queue. Holds are expired lazily, whenever the
+/// book is checked out, returned or held, so the caller must hold the lock
+/// on the book.
+pub(crate) async fn expire_holds(
+    tx: &mut Transaction<'_, MySql>,
+    config: &LibraryConfig,
+    book_id: i32,
+) -> Result<(), sqlx::Error> {
+    let expired = sqlx::query!(
+        r#"
+        SELECT id, copy_id FROM HOLDS
+        WHERE book_id = ? AND status = 'ready' AND expires_at < NOW()
+        FOR UPDATE
+        "#,
+        book_id
+    )
+    .fetch_all(&mut **tx)
+    .await?;
+
+    for hold in expired {
+        sqlx::query!(
+            r#"UPDATE HOLDS SET status = 'expired' WHERE id = ?"#,
+            hold.id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        if let Some(copy_id) = hold.copy_id {
+            assign_copy(tx, config, book_id, copy_id).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Renumbers the waiting holds on a book 1..n, keeping their current order
+async fn renumber_queue(tx: &mut Transaction<'_, MySql>, ids: &[i32]) -> Result<(), sqlx::Error> {
+    for (index, id) in ids.iter().enumerate() {
+        sqlx::query!(
+            r#"UPDATE HOLDS SET position = ? WHERE id = ?"#,
+            index as i32 + 1,
+            id
+        )
+        .execute(&mut **tx)
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Queues a member for the next copy of a book. Holds are only taken while
+/// every copy is out, otherwise the member should just borrow one.
+pub(crate) async fn place_hold(
+    pool: &sqlx::MySqlPool,
+    config: &LibraryConfig,
+    member_id: i32,
+    book_id: i32,
+) -> Result<Hold, ApiError> {
+    let mut tx = pool.begin().await?;
+
+    let member = sqlx::query!(
+        r#"SELECT id, deactivated_at FROM MEMBERS WHERE id = ?"#,
+        member_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Member not found".to_string()))?;
+
+    if member.deactivated_at.is_some() {
+        return Err(ApiError::Conflict("Member is deactivated".to_string()));
+    }
+
+    // Lock the book so the queue can't change underneath us
+    let book = sqlx::query!(
+        r#"SELECT id, withdrawn_status FROM BOOKS WHERE id = ? FOR UPDATE"#,
+        book_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Book not found".to_string()))?;
+
+    if book.withdrawn_status.is_some() {
+        return Err(ApiError::Conflict("Book has been withdrawn".to_string()));
+    }
+
+    expire_holds(&mut tx, config, book_id).await?;
+
+    let state = sqlx::query!(
+        r#"
+        SELECT
+            (SELECT COUNT(*) FROM COPIES
+             WHERE book_id = ? AND status = 'available') AS `available!: i64`,
+            (SELECT COUNT(*) FROM HOLDS
+             WHERE book_id = ? AND member_id = ? AND status IN ('waiting', 'ready')) AS `holds!: i64`,
+            (SELECT COUNT(*) FROM BORROWINGS br JOIN COPIES c ON c.id = br.copy_id
+             WHERE c.book_id = ? AND br.member_id = ? AND br.return_date IS NULL
+               AND br.voided_at IS NULL) AS `loans!: i64`,
+            (SELECT COALESCE(MAX(position), 0) FROM HOLDS
+             WHERE book_id = ? AND status = 'waiting') AS `last_position!: i64`
+        "#,
+        book_id,
+        book_id,
+        member_id,
+        book_id,
+        member_id,
+        book_id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    if state.holds > 0 {
+        return Err(ApiError::Conflict(
+            "Member already has a hold on this book".to_string(),
+        ));
+    }
+
+    if state.loans > 0 {
+        return Err(ApiError::Conflict(
+            "Member already has this book borrowed".to_string(),
+        ));
+    }
+
+    if state.available > 0 {
+        return Err(ApiError::Conflict(
+            "A copy is available to borrow".to_string(),
+        ));
+    }
+
+    let id = sqlx::query!(
+        r#"
+        INSERT INTO HOLDS (book_id, member_id, position)
+        VALUES (?, ?, ?)
+        "#,
+        book_id,
+        member_id,
+        state.last_position + 1
+    )
+    .execute(&mut *tx)
+    .await?
+    .last_insert_id();
+
+    let q = format!(
+        "SELECT {} FROM HOLDS h JOIN BOOKS b ON b.id = h.book_id WHERE h.id = ?",
+        HOLD_COLUMNS
+    );
+    let hold: Hold = sqlx::query_as(&q).bind(id).fetch_one(&mut *tx).await?;
+
+    tx.commit().await?;
+
+    Ok(hold)
+}
+
+/// Cancels a waiting or ready hold. When `member_id` is given the hold must
+/// belong to that member. A copy that was set aside moves on to the next
+/// member in the queue.
+pub(crate) async fn cancel_hold(
+    pool: &sqlx::MySqlPool,
+    config: &LibraryConfig,
+    id: i32,
+    member_id: Option<i32>,
+) -> Result<(), ApiError> {
+    let mut tx = pool.begin().await?;
+
+    let hold = sqlx::query!(
+        r#"SELECT id, book_id, member_id, copy_id, status FROM HOLDS WHERE id = ?"#,
+        id
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .filter(|hold| member_id.is_none_or(|member_id| member_id == hold.member_id))
+    .ok_or_else(|| ApiError::NotFound("Hold not found".to_string()))?;
+
+    sqlx::query!(
+        r#"SELECT id FROM BOOKS WHERE id = ? FOR UPDATE"#,
+        hold.book_id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    // Re-read the status now that the book is locked
+    let status = sqlx::query_scalar!(r#"SELECT status FROM HOLDS WHERE id = ?"#, hold.id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+    if status != "waiting" && status != "ready" {
+        return Err(ApiError::Conflict(format!("Hold is already {}", status)));
+    }
+
+    sqlx::query!(
+        r#"UPDATE HOLDS SET status = 'cancelled' WHERE id = ?"#,
+        hold.id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    if let (Some(copy_id), "ready") = (hold.copy_id, status.as_str()) {
+        assign_copy(&mut tx, config, hold.book_id, copy_id).await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Cancels every waiting or ready hold on a book that has been withdrawn,
+/// putting copies that were set aside back on the shelf. The caller must hold
+/// the lock on the book.
+pub(crate) async fn cancel_book_holds(
+    tx: &mut Transaction<'_, MySql>,
+    book_id: i32,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE HOLDS SET status = 'cancelled'
+        WHERE book_id = ? AND status IN ('waiting', 'ready')
+        "#,
+        book_id
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    sqlx::query!(
+        r#"UPDATE COPIES SET status = 'available' WHERE book_id = ? AND status = 'on_hold'"#,
+        book_id
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Cancels every waiting or ready hold a member has, passing copies set
+/// aside for them on down the queue. Used when the member leaves, so locks
+/// each book in turn.
+pub(crate) async fn cancel_member_holds(
+    tx: &mut Transaction<'_, MySql>,
+    config: &LibraryConfig,
+    member_id: i32,
+) -> Result<(), sqlx::Error> {
+    let holds = sqlx::query!(
+        r#"
+        SELECT id, book_id FROM HOLDS
+        WHERE member_id = ? AND status IN ('waiting', 'ready')
+        ORDER BY book_id
+        "#,
+        member_id
+    )
+    .fetch_all(&mut **tx)
+    .await?;
+
+    for hold in holds {
+        sqlx::query!(
+            r#"SELECT id FROM BOOKS WHERE id = ? FOR UPDATE"#,
+            hold.book_id
+        )
+        .fetch_one(&mut **tx)
+        .await?;
+
+        // Re-read the hold now that the book is locked
+        let hold = sqlx::query!(
+            r#"SELECT id, book_id, copy_id, status FROM HOLDS WHERE id = ?"#,
+            hold.id
+        )
+        .fetch_one(&mut **tx)
+        .await?;
+
+        if hold.status != "waiting" && hold.status != "ready" {
+            continue;
+        }
+
+        sqlx::query!(
+            r#"UPDATE HOLDS SET status = 'cancelled' WHERE id = ?"#,
+            hold.id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        if let (Some(copy_id), "ready") = (hold.copy_id, hold.status.as_str()) {
+            assign_copy(tx, config, hold.book_id, copy_id).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Holds matching the search in queue order
+pub(crate) async fn find_holds(
+    pool: &sqlx::MySqlPool,
+    query: &HoldSearchParams,
+) -> Result<Vec<Hold>, ApiError> {
+    let paging = Paging::new(query.page, query.limit, None)?;
+    let status = query.status.as_ref().map(|status| status.as_str());
+
+    // Queue order within a book, oldest books first
+    let q = format!(
+        r#"
+        SELECT {}
+        FROM HOLDS h
+        JOIN BOOKS b ON b.id = h.book_id
+        WHERE
+            (? IS NULL OR h.book_id = ?)
+            AND (? IS NULL OR h.member_id = ?)
+            AND (? IS NULL OR h.status = ?)
+        ORDER BY h.book_id, h.position, h.id
+        LIMIT ? OFFSET ?
+        "#,
+        HOLD_COLUMNS
+    );
+
+    let holds = sqlx::query_as(&q)
+        .bind(query.book_id)
+        .bind(query.book_id)
+        .bind(query.member_id)
+        .bind(query.member_id)
+        .bind(status)
+        .bind(status)
+        .bind(paging.limit())
+        .bind(paging.offset())
+        .fetch_all(pool)
+        .await?;
+
+    Ok(holds)
+}
+
+#[get("/")]
+async fn get_holds(
+    query: web::Query<HoldSearchParams>,
+    pool: web::Data<sqlx::MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    let results = find_holds(pool.get_ref(), &query).await?;
+
+    Ok(HttpResponse::Ok().json(json!(results)))
+}
+
+#[post("/")]
+async fn create_hold(
+    _staff: Librarian,
+    data: web::Json<NewHold>,
+    pool: web::Data<sqlx::MySqlPool>,
+    config: web::Data<LibraryConfig>,
+) -> Result<HttpResponse, ApiError> {
+    let hold = place_hold(pool.get_ref(), &config, data.member_id, data.book_id).await?;
+
+    Ok(HttpResponse::Created().json(json!(hold)))
+}
+
+#[get("/{id}")]
+async fn get_hold_by_id(
+    id: web::Path<i32>,
+    pool: web::Data<sqlx::MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    let q = format!(
+        "SELECT {} FROM HOLDS h JOIN BOOKS b ON b.id = h.book_id WHERE h.id = ?",
+        HOLD_COLUMNS
+    );
+
+    let hold: Hold = sqlx::query_as(&q)
+        .bind(id.into_inner())
+        .fetch_optional(pool.get_ref())
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Hold not found".to_string()))?;
+
+    Ok(HttpResponse::Ok().json(json!(hold)))
+}
+
+#[put("/{id}/position")]
+async fn move_hold(
+    _staff: Librarian,
+    id: web::Path<i32>,
+    data: web::Json<HoldPosition>,
+    pool: web::Data<sqlx::MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    let id = id.into_inner();
+
+    if data.position == 0 {
+        return Err(ApiError::BadRequest("Positions start at 1".to_string()));
+    }
+
+    let mut tx = pool.begin().await?;
+
+    let book_id = sqlx::query_scalar!(r#"SELECT book_id FROM HOLDS WHERE id = ?"#, id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Hold not found".to_string()))?;
+
+    sqlx::query!(r#"SELECT id FROM BOOKS WHERE id = ? FOR UPDATE"#, book_id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+    let mut queue: Vec<i32> = sqlx::query_scalar!(
+        r#"
+        SELECT id FROM HOLDS
+        WHERE book_id = ? AND status = 'waiting'
+        ORDER BY position, id
+        "#,
+        book_id
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    // Only holds still in the queue can be moved
+    let Some(current) = queue.iter().position(|hold_id| *hold_id == id) else {
+        return Err(ApiError::Conflict("Hold is no longer waiting".to_string()));
+    };
+
+    let hold_id = queue.remove(current);
+    let target = (data.position as usize - 1).min(queue.len());
+    queue.insert(target, hold_id);
+
+    renumber_queue(&mut tx, &queue).await?;
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[delete("/{id}")]
+async fn cancel_by_id(
+    _staff: Librarian,
+    id: web::Path<i32>,
+    pool: web::Data<sqlx::MySqlPool>,
+    config: web::Data<LibraryConfig>,
+) -> Result<HttpResponse, ApiError> {
+    cancel_hold(pool.get_ref(), &config, id.into_inner(), None).await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
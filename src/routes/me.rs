@@ -0,0 +1,224 @@
+use actix_web::{
+    cookie::{Cookie, SameSite},
+    delete, get, post, web, HttpRequest, HttpResponse,
+};
+use serde_json::json;
+
+use super::audit::Actor;
+use super::members::MEMBER_COLUMNS;
+use crate::auth::{self, Patron, MEMBER_SESSION_COOKIE};
+use crate::config::LibraryConfig;
+use crate::error::ApiError;
+use crate::routes::{borrowings, fines, holds};
+use crate::views::{
+    borrowings::BorrowParams,
+    fines::FineSearchParams,
+    holds::{HoldSearchParams, PatronHold},
+    members::{Member, MemberCard, MemberSession},
+    Status,
+};
+
+/// The self-service portal. Members sign in with their library card and PIN
+/// and everything here is limited to their own records.
+pub fn me_config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/me")
+            .service(login)
+            .service(logout)
+            .service(get_profile)
+            .service(get_history)
+            .service(get_loans)
+            .service(renew_loan)
+            .service(get_holds)
+            .service(place_hold)
+            .service(cancel_hold)
+            .service(get_fines),
+    );
+}
+
+async fn fetch_member(pool: &sqlx::MySqlPool, id: i32) -> Result<Member, sqlx::Error> {
+    let q = format!("SELECT {} FROM MEMBERS WHERE id = ?", MEMBER_COLUMNS);
+    sqlx::query_as(&q).bind(id).fetch_one(pool).await
+}
+
+#[post("/login")]
+async fn login(
+    data: web::Json<MemberCard>,
+    pool: web::Data<sqlx::MySqlPool>,
+    config: web::Data<LibraryConfig>,
+) -> Result<HttpResponse, ApiError> {
+    let invalid = || ApiError::Unauthorized("Invalid card number or PIN".to_string());
+
+    let account = sqlx::query!(
+        r#"SELECT id, pin_hash FROM MEMBERS WHERE card_number = ? AND deactivated_at IS NULL"#,
+        data.card_number.trim()
+    )
+    .fetch_optional(pool.get_ref())
+    .await?;
+
+    // Unknown cards, cards without a PIN and wrong PINs get the same answer,
+    // in the same time
+    let (member_id, pin_hash) = match account {
+        Some(account) => (Some(account.id), account.pin_hash),
+        None => (None, None),
+    };
+
+    let pin = data.pin.clone();
+    if !web::block(move || auth::verify_login(&pin, pin_hash.as_deref())).await? {
+        return Err(invalid());
+    }
+    let member_id = member_id.ok_or_else(invalid)?;
+
+    let (token, expires_at) =
+        auth::create_member_session(pool.get_ref(), member_id, config.session_hours).await?;
+
+    let member = fetch_member(pool.get_ref(), member_id).await?;
+
+    let cookie = Cookie::build(MEMBER_SESSION_COOKIE, token.clone())
+        .path("/")
+        .http_only(true)
+        .same_site(SameSite::Strict)
+        .max_age(actix_web::cookie::time::Duration::hours(
+            config.session_hours,
+        ))
+        .finish();
+
+    Ok(HttpResponse::Ok().cookie(cookie).json(json!(MemberSession {
+        token,
+        expires_at,
+        member,
+    })))
+}
+
+#[post("/logout")]
+async fn logout(
+    req: HttpRequest,
+    pool: web::Data<sqlx::MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    if let Some(token) = auth::session_token(&req, MEMBER_SESSION_COOKIE) {
+        sqlx::query!(
+            r#"DELETE FROM MEMBER_SESSIONS WHERE token_hash = ?"#,
+            auth::hash_token(&token)
+        )
+        .execute(pool.get_ref())
+        .await?;
+    }
+
+    let mut cookie = Cookie::build(MEMBER_SESSION_COOKIE, "").path("/").finish();
+    cookie.make_removal();
+
+    Ok(HttpResponse::NoContent().cookie(cookie).finish())
+}
+
+#[get("/")]
+async fn get_profile(
+    patron: Patron,
+    pool: web::Data<sqlx::MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    let member = fetch_member(pool.get_ref(), patron.member_id).await?;
+
+    Ok(HttpResponse::Ok().json(json!(member)))
+}
+
+#[get("/loans")]
+async fn get_loans(
+    patron: Patron,
+    query: web::Query<BorrowParams>,
+    pool: web::Data<sqlx::MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    // Only what the member has out right now, overdue included
+    let mut params = query.into_inner();
+    params.status = Some(Status::Borrowed);
+
+    let results = borrowings::member_borrowings(pool.get_ref(), patron.member_id, &params).await?;
+
+    Ok(HttpResponse::Ok().json(json!(results)))
+}
+
+#[get("/loans/history")]
+async fn get_history(
+    patron: Patron,
+    query: web::Query<BorrowParams>,
+    pool: web::Data<sqlx::MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    let results = borrowings::member_borrowings(pool.get_ref(), patron.member_id, &query).await?;
+
+    Ok(HttpResponse::Ok().json(json!(results)))
+}
+
+#[post("/loans/{id}/renew")]
+async fn renew_loan(
+    patron: Patron,
+    id: web::Path<i32>,
+    pool: web::Data<sqlx::MySqlPool>,
+    config: web::Data<LibraryConfig>,
+) -> Result<HttpResponse, ApiError> {
+    let renewed = borrowings::renew_loan(
+        pool.get_ref(),
+        &config,
+        id.into_inner(),
+        Actor::Member(patron.member_id),
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(json!(renewed)))
+}
+
+#[get("/holds")]
+async fn get_holds(
+    patron: Patron,
+    query: web::Query<HoldSearchParams>,
+    pool: web::Data<sqlx::MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    let mut params = query.into_inner();
+    params.member_id = Some(patron.member_id);
+
+    let results = holds::find_holds(pool.get_ref(), &params).await?;
+
+    Ok(HttpResponse::Ok().json(json!(results)))
+}
+
+#[post("/holds")]
+async fn place_hold(
+    patron: Patron,
+    data: web::Json<PatronHold>,
+    pool: web::Data<sqlx::MySqlPool>,
+    config: web::Data<LibraryConfig>,
+) -> Result<HttpResponse, ApiError> {
+    let hold = holds::place_hold(pool.get_ref(), &config, patron.member_id, data.book_id).await?;
+
+    Ok(HttpResponse::Created().json(json!(hold)))
+}
+
+#[delete("/holds/{id}")]
+async fn cancel_hold(
+    patron: Patron,
+    id: web::Path<i32>,
+    pool: web::Data<sqlx::MySqlPool>,
+    config: web::Data<LibraryConfig>,
+) -> Result<HttpResponse, ApiError> {
+    holds::cancel_hold(
+        pool.get_ref(),
+        &config,
+        id.into_inner(),
+        Some(patron.member_id),
+    )
+    .await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[get("/fines")]
+async fn get_fines(
+    patron: Patron,
+    query: web::Query<FineSearchParams>,
+    pool: web::Data<sqlx::MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    let mut params = query.into_inner();
+    params.member_id = Some(patron.member_id);
+
+    let results = fines::find_fines(pool.get_ref(), &params).await?;
+    let balance = fines::member_balance(pool.get_ref(), patron.member_id).await?;
+
+    Ok(HttpResponse::Ok().json(json!({ "balance": balance, "fines": results })))
+}
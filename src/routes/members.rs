@@ -1,30 +1,127 @@
-use actix_web::{delete, get, post, put, web, HttpResponse, Responder};
+use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse};
 use serde_json::json;
+use sqlx::{mysql::MySqlArguments, query::QueryAs, MySql, Transaction};
+use validator::Validate;
 
-use crate::views::members::{Member, MemberSearchParams, NewMember, UpdateMember};
+use super::audit::{self, Actor};
+use super::bulk;
+use super::pagination::Paging;
+use crate::auth::{self, Admin, Librarian};
+use crate::config::LibraryConfig;
+use crate::error::ApiError;
+use crate::routes::{fines, holds};
+use crate::validation;
+use crate::views::{
+    audit::AuditEntity,
+    bulk::{ExportParams, Format, ImportAction, ImportParams, ImportReport},
+    members::{Member, MemberCard, MemberSearchParams, MemberStatus, NewMember, UpdateMember},
+};
 
 pub fn members_config(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/members")
+            .app_data(web::PayloadConfig::new(bulk::IMPORT_LIMIT))
             .service(get_members)
+            .service(export_members)
+            .service(import_members)
             .service(create_member)
             .service(update_member)
             .service(get_by_id)
+            .service(get_balance)
+            .service(set_card)
+            .service(deactivate_member)
+            .service(reactivate_member)
+            .service(anonymise_member)
             .service(delete_by_id),
     );
 }
 
+// Columns of Member, in the order of its fields
+pub(crate) const MEMBER_COLUMNS: &str = r#"
+    id, first_name, last_name, email, phone, card_number, member_type,
+    membership_expires_at, deactivated_at, anonymised_at
+"#;
+
+// Filters shared by the member list and the export, see MemberFilters::bind
+const MEMBER_FILTERS: &str = r#"
+    (? IS NULL OR first_name LIKE CONCAT('%', ?, '%'))
+    AND (? IS NULL OR last_name LIKE CONCAT('%', ?, '%'))
+    AND (? IS NULL OR email LIKE CONCAT('%', ?, '%'))
+    AND (? IS NULL OR phone LIKE CONCAT('%', ?, '%'))
+    AND (CASE ?
+        WHEN 'active' THEN deactivated_at IS NULL
+        WHEN 'deactivated' THEN deactivated_at IS NOT NULL
+        WHEN 'anonymised' THEN anonymised_at IS NOT NULL
+        ELSE TRUE
+    END)
+"#;
+
+// Column order of the CSV export, matching the fields of Member
+const MEMBER_EXPORT_HEADERS: &[&str] = &[
+    "id",
+    "first_name",
+    "last_name",
+    "email",
+    "phone",
+    "card_number",
+    "member_type",
+    "membership_expires_at",
+    "deactivated_at",
+    "anonymised_at",
+];
+
+#[derive(Clone)]
+struct MemberFilters {
+    first_name: Option<String>,
+    last_name: Option<String>,
+    email: Option<String>,
+    phone: Option<String>,
+    status: MemberStatus,
+}
+
+impl MemberFilters {
+    fn new(query: &MemberSearchParams) -> Self {
+        MemberFilters {
+            first_name: query.first_name.clone(),
+            last_name: query.last_name.clone(),
+            email: query.email.clone(),
+            phone: query.phone.clone(),
+            status: query.status.unwrap_or_default(),
+        }
+    }
+
+    /// Binds the parameters for MEMBER_FILTERS in order
+    fn bind<'q, O>(
+        self,
+        query: QueryAs<'q, MySql, O, MySqlArguments>,
+    ) -> QueryAs<'q, MySql, O, MySqlArguments> {
+        query
+            .bind(self.first_name.clone())
+            .bind(self.first_name.unwrap_or_default())
+            .bind(self.last_name.clone())
+            .bind(self.last_name.unwrap_or_default())
+            .bind(self.email.clone())
+            .bind(self.email.unwrap_or_default())
+            .bind(self.phone.clone())
+            .bind(self.phone.unwrap_or_default())
+            .bind(self.status.as_str().to_string())
+    }
+}
+
 #[get("/")]
 pub async fn get_members(
+    req: HttpRequest,
     query: web::Query<MemberSearchParams>,
     pool: web::Data<sqlx::MySqlPool>,
-) -> impl Responder {
+) -> Result<HttpResponse, ApiError> {
     let order_by = match query.get_order_by().as_str() {
         "id" | "first_name" | "last_name" | "email" | "phone" | "status" | "created_at" => {
             query.get_order_by()
         }
         _ => "id".to_string(),
     };
+    let paging = Paging::new(query.page, query.limit, query.cursor.as_deref())?;
+    let filters = MemberFilters::new(&query);
 
     // This query took a bulk of my time to create as I had to look up CASE statements in SQL and MariaDB
     // and figure out how to use them in Rust. I also had to figure out how to use the url parameters
@@ -32,14 +129,11 @@ pub async fn get_members(
     // processing power.
     let q = format!(
         r#"
-        SELECT id, first_name, last_name, email, phone
+        SELECT {}
         FROM MEMBERS
-        WHERE
-            (? IS NULL OR first_name LIKE CONCAT('%', ?, '%'))
-            AND (? IS NULL OR last_name LIKE CONCAT('%', ?, '%'))
-            AND (? IS NULL OR email LIKE CONCAT('%', ?, '%'))
-            AND (? IS NULL OR phone LIKE CONCAT('%', ?, '%'))
+        WHERE {} {}
         ORDER BY
+            {}
             CASE
                 WHEN ? = 'id' THEN id
                 WHEN ? = 'first_name' THEN first_name
@@ -51,138 +145,623 @@ pub async fn get_members(
             {}
         LIMIT ? OFFSET ?
         "#,
+        MEMBER_COLUMNS,
+        MEMBER_FILTERS,
+        paging.keyset("id"),
+        paging.key_order("id"),
         query.get_order().as_str()
     );
 
-    let results: Vec<Member> = sqlx::query_as(&q)
-        .bind(query.first_name.clone())
-        .bind(query.first_name.clone().unwrap_or("".to_string()))
-        .bind(query.last_name.clone())
-        .bind(query.last_name.clone().unwrap_or("".to_string()))
-        .bind(query.email.clone())
-        .bind(query.email.clone().unwrap_or("".to_string()))
-        .bind(query.phone.clone())
-        .bind(query.phone.clone().unwrap_or("".to_string()))
+    let results: Vec<Member> = paging
+        .bind(filters.clone().bind(sqlx::query_as(&q)))
         .bind(order_by.as_str())
         .bind(order_by.as_str())
         .bind(order_by.as_str())
         .bind(order_by.as_str())
         .bind(order_by.as_str())
-        .bind(query.limit.unwrap_or(10))
-        .bind(query.get_offset())
+        .bind(paging.limit())
+        .bind(paging.offset())
         .fetch_all(pool.get_ref())
-        .await
-        .unwrap();
+        .await?;
+
+    let total_query = format!("SELECT COUNT(*) FROM MEMBERS WHERE {}", MEMBER_FILTERS);
+    let (total,): (i64,) = filters
+        .bind(sqlx::query_as(&total_query))
+        .fetch_one(pool.get_ref())
+        .await?;
 
-    HttpResponse::Ok().json(json!(results))
+    Ok(HttpResponse::Ok().json(json!(paging.page(&req, results, total, |member| member.id))))
 }
 
 #[post("/")]
 async fn create_member(
+    Librarian(staff): Librarian,
     data: web::Json<NewMember>,
     pool: web::Data<sqlx::MySqlPool>,
-) -> impl Responder {
-    let result = sqlx::query!(
+) -> Result<HttpResponse, ApiError> {
+    data.validate()?;
+
+    let mut tx = pool.begin().await?;
+
+    let id = sqlx::query!(
         r#"
-            INSERT INTO MEMBERS (first_name, last_name, email, phone)
-            VALUES (?, ?, ?, ?)
+            INSERT INTO MEMBERS
+                (first_name, last_name, email, phone, member_type, membership_expires_at)
+            VALUES (?, ?, ?, ?, ?, ?)
         "#,
         data.first_name,
         data.last_name,
         data.email,
-        data.phone
+        data.phone.as_deref().map(validation::to_e164),
+        data.member_type.unwrap_or_default().as_str(),
+        data.membership_expires_at
+    )
+    .execute(&mut *tx)
+    .await?
+    .last_insert_id();
+
+    audit::record(
+        &mut tx,
+        Actor::Staff(staff.id),
+        AuditEntity::Member,
+        id as i32,
+        None,
     )
-    .execute(pool.get_ref())
-    .await;
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::Created().finish())
+}
+
+/// Adds the members in an uploaded CSV file with the columns `first_name`,
+/// `last_name`, `email` and `phone`. A row whose email already belongs to a
+/// member updates that member instead.
+#[post("/import")]
+async fn import_members(
+    Librarian(staff): Librarian,
+    params: web::Query<ImportParams>,
+    body: web::Bytes,
+    pool: web::Data<sqlx::MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    bulk::check_format(params.format, &[Format::Csv])?;
+
+    let rows = bulk::read_rows::<NewMember>(&body)?;
+
+    let mut report = ImportReport {
+        dry_run: params.dry_run.unwrap_or(false),
+        ..Default::default()
+    };
+
+    let mut tx = pool.begin().await?;
+
+    for (line, row) in rows {
+        let member = match row {
+            Ok(member) => member,
+            Err(error) => {
+                report.error(line, error);
+                continue;
+            }
+        };
+
+        if let Err(errors) = member.validate() {
+            report.invalid(line, &errors);
+            continue;
+        }
 
-    match result {
-        Ok(_) => HttpResponse::Created().finish(),
-        Err(_) => HttpResponse::InternalServerError().finish(),
+        let phone = member.phone.as_deref().map(validation::to_e164);
+
+        let existing = sqlx::query_scalar!(
+            r#"SELECT id FROM MEMBERS WHERE email = ? FOR UPDATE"#,
+            member.email
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+        let before = match existing {
+            Some(id) => audit::snapshot(&mut tx, AuditEntity::Member, id).await?,
+            None => None,
+        };
+
+        let result = match existing {
+            Some(id) => sqlx::query!(
+                r#"
+                    UPDATE MEMBERS
+                    SET first_name = ?, last_name = ?, phone = COALESCE(?, phone),
+                        member_type = COALESCE(?, member_type),
+                        membership_expires_at = COALESCE(?, membership_expires_at)
+                    WHERE id = ?
+                "#,
+                member.first_name,
+                member.last_name,
+                phone,
+                member
+                    .member_type
+                    .map(|member_type| member_type.as_str().to_string()),
+                member.membership_expires_at,
+                id
+            )
+            .execute(&mut *tx)
+            .await
+            .map(|_| (ImportAction::Update, id)),
+            None => sqlx::query!(
+                r#"
+                    INSERT INTO MEMBERS
+                        (first_name, last_name, email, phone, member_type, membership_expires_at)
+                    VALUES (?, ?, ?, ?, ?, ?)
+                "#,
+                member.first_name,
+                member.last_name,
+                member.email,
+                phone,
+                member.member_type.unwrap_or_default().as_str(),
+                member.membership_expires_at
+            )
+            .execute(&mut *tx)
+            .await
+            .map(|result| (ImportAction::Create, result.last_insert_id() as i32)),
+        };
+
+        match result {
+            Ok((action, id)) => {
+                audit::record(
+                    &mut tx,
+                    Actor::Staff(staff.id),
+                    AuditEntity::Member,
+                    id,
+                    before,
+                )
+                .await?;
+                report.record(line, action, &member);
+            }
+            Err(error) => report.database_error(line, error)?,
+        }
     }
+
+    bulk::finish_import(tx, report).await
+}
+
+/// Streams every member matching the same filters as the member list,
+/// ignoring paging and sorting
+#[get("/export")]
+async fn export_members(
+    query: web::Query<MemberSearchParams>,
+    params: web::Query<ExportParams>,
+    pool: web::Data<sqlx::MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    bulk::check_format(params.format, &[Format::Csv])?;
+
+    let filters = MemberFilters::new(&query);
+    let pool = pool.into_inner();
+
+    let rows = bulk::csv_stream(
+        MEMBER_EXPORT_HEADERS,
+        |member: &Member| member.id,
+        move |after, limit| {
+            let pool = pool.clone();
+            let filters = filters.clone();
+
+            async move {
+                let q = format!(
+                    r#"
+                    SELECT {}
+                    FROM MEMBERS
+                    WHERE {} AND id > ?
+                    ORDER BY id
+                    LIMIT ?
+                    "#,
+                    MEMBER_COLUMNS, MEMBER_FILTERS
+                );
+
+                filters
+                    .bind(sqlx::query_as(&q))
+                    .bind(after)
+                    .bind(limit as u32)
+                    .fetch_all(pool.as_ref())
+                    .await
+            }
+        },
+    );
+
+    Ok(bulk::csv_response("members.csv", rows))
 }
 
 #[put("/{id}")]
 async fn update_member(
+    Librarian(staff): Librarian,
     id: web::Path<i32>,
     data: web::Json<UpdateMember>,
     pool: web::Data<sqlx::MySqlPool>,
-) -> impl Responder {
+) -> Result<HttpResponse, ApiError> {
+    data.validate()?;
+
+    let mut tx = pool.begin().await?;
+
     // Check if the member exists
-    let member = match sqlx::query_as!(
-        Member,
-        r#"
-            SELECT id, first_name, last_name, email, phone
-            FROM MEMBERS
-            WHERE id = ?
-        "#,
-        id.clone()
-    )
-    .fetch_one(pool.get_ref())
-    .await
-    {
-        Ok(member) => member,
-        Err(_) => return HttpResponse::NotFound().finish(),
-    };
+    let q = format!(
+        "SELECT {} FROM MEMBERS WHERE id = ? FOR UPDATE",
+        MEMBER_COLUMNS
+    );
+    let member: Member = sqlx::query_as(&q)
+        .bind(id.into_inner())
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Member not found".to_string()))?;
+
+    // Writing details back would undo the anonymisation
+    if member.anonymised_at.is_some() {
+        return Err(ApiError::Conflict(
+            "Anonymised members can't be changed".to_string(),
+        ));
+    }
+    let before = audit::snapshot(&mut tx, AuditEntity::Member, member.id).await?;
 
     // Update the member with the new data
-    let result = sqlx::query!(
+    sqlx::query!(
         r#"
             UPDATE MEMBERS
-            SET first_name = ?, last_name = ?, email = ?, phone = ?
+            SET first_name = ?, last_name = ?, email = ?, phone = ?, member_type = ?,
+                membership_expires_at = ?
             WHERE id = ?
         "#,
         data.first_name.clone().unwrap_or(member.first_name),
         data.last_name.clone().unwrap_or(member.last_name),
         data.email.clone().unwrap_or(member.email),
-        if data.phone.clone().is_some() {
-            data.phone.clone()
-        } else {
-            member.phone
-        },
-        id.into_inner()
+        data.phone
+            .as_deref()
+            .map(validation::to_e164)
+            .or(member.phone),
+        data.member_type.unwrap_or(member.member_type).as_str(),
+        data.membership_expires_at
+            .unwrap_or(member.membership_expires_at),
+        member.id
     )
-    .execute(pool.get_ref())
-    .await;
+    .execute(&mut *tx)
+    .await?;
 
-    match result {
-        Ok(_) => HttpResponse::Ok().finish(),
-        Err(_) => HttpResponse::InternalServerError().finish(),
-    }
+    audit::record(
+        &mut tx,
+        Actor::Staff(staff.id),
+        AuditEntity::Member,
+        member.id,
+        before,
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().finish())
 }
 
 #[get("/{id}")]
-async fn get_by_id(id: web::Path<i32>, pool: web::Data<sqlx::MySqlPool>) -> impl Responder {
-    match sqlx::query_as!(
-        Member,
+async fn get_by_id(
+    id: web::Path<i32>,
+    pool: web::Data<sqlx::MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    let q = format!("SELECT {} FROM MEMBERS WHERE id = ?", MEMBER_COLUMNS);
+    let member: Member = sqlx::query_as(&q)
+        .bind(id.into_inner())
+        .fetch_optional(pool.get_ref())
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Member not found".to_string()))?;
+
+    Ok(HttpResponse::Ok().json(json!(member)))
+}
+
+#[get("/{id}/balance")]
+async fn get_balance(
+    id: web::Path<i32>,
+    pool: web::Data<sqlx::MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    let id = id.into_inner();
+
+    sqlx::query!(r#"SELECT id FROM MEMBERS WHERE id = ?"#, id)
+        .fetch_optional(pool.get_ref())
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Member not found".to_string()))?;
+
+    let balance = fines::member_balance(pool.get_ref(), id).await?;
+
+    Ok(HttpResponse::Ok().json(json!(balance)))
+}
+
+/// Issues a library card, or replaces the card number and PIN, so the member
+/// can sign in to `/api/me`
+#[put("/{id}/card")]
+async fn set_card(
+    Librarian(staff): Librarian,
+    id: web::Path<i32>,
+    data: web::Json<MemberCard>,
+    pool: web::Data<sqlx::MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    let id = id.into_inner();
+    let card_number = data.card_number.trim().to_string();
+
+    if card_number.is_empty() {
+        return Err(ApiError::BadRequest(
+            "A card number is required".to_string(),
+        ));
+    }
+
+    if data.pin.len() < 4 || !data.pin.chars().all(|c| c.is_ascii_digit()) {
+        return Err(ApiError::BadRequest(
+            "PIN must be at least 4 digits".to_string(),
+        ));
+    }
+
+    let pin = data.pin.clone();
+    let pin_hash = web::block(move || auth::hash_password(&pin)).await??;
+
+    let mut tx = pool.begin().await?;
+
+    if lock_member(&mut tx, id).await? {
+        return Err(ApiError::Conflict(
+            "Anonymised members can't be changed".to_string(),
+        ));
+    }
+    let before = audit::snapshot(&mut tx, AuditEntity::Member, id).await?;
+
+    sqlx::query!(
         r#"
-            SELECT id, first_name, last_name, email, phone
-            FROM MEMBERS
+            UPDATE MEMBERS
+            SET card_number = ?, pin_hash = ?
             WHERE id = ?
         "#,
-        id.into_inner()
+        card_number,
+        pin_hash,
+        id
     )
-    .fetch_one(pool.get_ref())
+    .execute(&mut *tx)
     .await
-    {
-        Ok(member) => HttpResponse::Ok().json(json!(member)),
-        Err(_) => HttpResponse::NotFound().finish(),
-    }
+    .map_err(|error| match error {
+        sqlx::Error::Database(error) if error.is_unique_violation() => {
+            ApiError::Conflict("Card number is already in use".to_string())
+        }
+        error => error.into(),
+    })?;
+
+    // A new card or PIN signs the member out everywhere
+    sqlx::query!(r#"DELETE FROM MEMBER_SESSIONS WHERE member_id = ?"#, id)
+        .execute(&mut *tx)
+        .await?;
+
+    audit::record(
+        &mut tx,
+        Actor::Staff(staff.id),
+        AuditEntity::Member,
+        id,
+        before,
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::NoContent().finish())
 }
 
-#[delete("/{id}")]
-async fn delete_by_id(id: web::Path<i32>, pool: web::Data<sqlx::MySqlPool>) -> impl Responder {
-    let result = sqlx::query!(
+/// Locks a member for a change to their account, returning whether they
+/// have already been anonymised
+async fn lock_member(tx: &mut Transaction<'_, MySql>, id: i32) -> Result<bool, ApiError> {
+    let anonymised_at = sqlx::query_scalar!(
+        r#"SELECT anonymised_at FROM MEMBERS WHERE id = ? FOR UPDATE"#,
+        id
+    )
+    .fetch_optional(&mut **tx)
+    .await?
+    .ok_or_else(|| ApiError::NotFound("Member not found".to_string()))?;
+
+    Ok(anonymised_at.is_some())
+}
+
+/// Refuses to let a member go while they still have books out or fines to
+/// pay, so nothing is left that can't be chased up
+async fn check_settled(tx: &mut Transaction<'_, MySql>, id: i32) -> Result<(), ApiError> {
+    let loans = sqlx::query_scalar!(
         r#"
-            DELETE FROM MEMBERS
-            WHERE id = ?
+        SELECT COUNT(*) FROM BORROWINGS
+        WHERE member_id = ? AND return_date IS NULL AND voided_at IS NULL
+        "#,
+        id
+    )
+    .fetch_one(&mut **tx)
+    .await?;
+
+    if loans > 0 {
+        return Err(ApiError::Conflict(format!(
+            "Member still has {} loan(s) out",
+            loans
+        )));
+    }
+
+    let balance_cents = sqlx::query_scalar!(
+        r#"
+        SELECT CAST(COALESCE(SUM(balance_cents), 0) AS SIGNED) AS `balance_cents!: i64`
+        FROM FINE_BALANCES
+        WHERE member_id = ?
         "#,
-        id.into_inner()
+        id
     )
-    .execute(pool.get_ref())
-    .await;
+    .fetch_one(&mut **tx)
+    .await?;
 
-    match result {
-        Ok(_) => HttpResponse::NoContent().finish(),
-        Err(_) => HttpResponse::InternalServerError().finish(),
+    if balance_cents > 0 {
+        return Err(ApiError::Conflict(
+            "Member still has unpaid fines".to_string(),
+        ));
     }
+
+    Ok(())
+}
+
+/// Stops a member from borrowing, placing holds or signing in, while keeping
+/// their account and history. Their holds are cancelled and their sessions
+/// ended, books they still have can be returned as usual.
+#[post("/{id}/deactivate")]
+async fn deactivate_member(
+    Admin(staff): Admin,
+    id: web::Path<i32>,
+    pool: web::Data<sqlx::MySqlPool>,
+    config: web::Data<LibraryConfig>,
+) -> Result<HttpResponse, ApiError> {
+    let id = id.into_inner();
+    let mut tx = pool.begin().await?;
+
+    lock_member(&mut tx, id).await?;
+    let before = audit::snapshot(&mut tx, AuditEntity::Member, id).await?;
+    leave(&mut tx, &config, id).await?;
+    audit::record(
+        &mut tx,
+        Actor::Staff(staff.id),
+        AuditEntity::Member,
+        id,
+        before,
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[post("/{id}/reactivate")]
+async fn reactivate_member(
+    Admin(staff): Admin,
+    id: web::Path<i32>,
+    pool: web::Data<sqlx::MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    let id = id.into_inner();
+    let mut tx = pool.begin().await?;
+
+    if lock_member(&mut tx, id).await? {
+        return Err(ApiError::Conflict(
+            "Anonymised members can't be reactivated".to_string(),
+        ));
+    }
+    let before = audit::snapshot(&mut tx, AuditEntity::Member, id).await?;
+
+    sqlx::query!(
+        r#"UPDATE MEMBERS SET deactivated_at = NULL WHERE id = ?"#,
+        id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    audit::record(
+        &mut tx,
+        Actor::Staff(staff.id),
+        AuditEntity::Member,
+        id,
+        before,
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Scrubs a member's personal details and deactivates them, keeping their
+/// borrowings for statistics. Only allowed once nothing is outstanding.
+#[post("/{id}/anonymise")]
+async fn anonymise_member(
+    Admin(staff): Admin,
+    id: web::Path<i32>,
+    pool: web::Data<sqlx::MySqlPool>,
+    config: web::Data<LibraryConfig>,
+) -> Result<HttpResponse, ApiError> {
+    let id = id.into_inner();
+    let mut tx = pool.begin().await?;
+
+    if lock_member(&mut tx, id).await? {
+        return Err(ApiError::Conflict(
+            "Member has already been anonymised".to_string(),
+        ));
+    }
+    check_settled(&mut tx, id).await?;
+    let before = audit::snapshot(&mut tx, AuditEntity::Member, id).await?;
+    leave(&mut tx, &config, id).await?;
+
+    // The email has to stay unique, so it is made from the id
+    sqlx::query!(
+        r#"
+        UPDATE MEMBERS
+        SET first_name = 'Anonymised', last_name = 'Member',
+            email = CONCAT('anonymised-', id, '@invalid'),
+            phone = NULL, card_number = NULL, pin_hash = NULL,
+            anonymised_at = NOW()
+        WHERE id = ?
+        "#,
+        id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    audit::record(
+        &mut tx,
+        Actor::Staff(staff.id),
+        AuditEntity::Member,
+        id,
+        before,
+    )
+    .await?;
+    audit::forget_member(&mut tx, id).await?;
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Deactivates a member, cancelling their holds and ending their sessions
+async fn leave(
+    tx: &mut Transaction<'_, MySql>,
+    config: &LibraryConfig,
+    id: i32,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"UPDATE MEMBERS SET deactivated_at = COALESCE(deactivated_at, NOW()) WHERE id = ?"#,
+        id
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    sqlx::query!(r#"DELETE FROM MEMBER_SESSIONS WHERE member_id = ?"#, id)
+        .execute(&mut **tx)
+        .await?;
+
+    holds::cancel_member_holds(tx, config, id).await
+}
+
+/// Removes a member for good. Their borrowings are kept without a member, and
+/// the delete is refused while they have loans out or fines to pay.
+#[delete("/{id}")]
+async fn delete_by_id(
+    Admin(staff): Admin,
+    id: web::Path<i32>,
+    pool: web::Data<sqlx::MySqlPool>,
+    config: web::Data<LibraryConfig>,
+) -> Result<HttpResponse, ApiError> {
+    let id = id.into_inner();
+    let mut tx = pool.begin().await?;
+
+    lock_member(&mut tx, id).await?;
+    check_settled(&mut tx, id).await?;
+    holds::cancel_member_holds(&mut tx, &config, id).await?;
+    let before = audit::snapshot(&mut tx, AuditEntity::Member, id).await?;
+
+    sqlx::query!(r#"DELETE FROM MEMBERS WHERE id = ?"#, id)
+        .execute(&mut *tx)
+        .await?;
+
+    audit::record(
+        &mut tx,
+        Actor::Staff(staff.id),
+        AuditEntity::Member,
+        id,
+        before,
+    )
+    .await?;
+    audit::forget_member(&mut tx, id).await?;
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::NoContent().finish())
 }
@@ -1,6 +1,16 @@
+pub mod audit;
 pub mod books;
 pub mod borrowings;
+mod bulk;
+pub mod copies;
+mod eligibility;
+pub mod fines;
+pub mod holds;
+pub mod me;
 pub mod members;
+mod pagination;
+pub mod reports;
+pub mod staff;
 
 use actix_web::{get, web, HttpResponse, Responder};
 use sqlx::MySqlPool;
@@ -0,0 +1,187 @@
+use actix_web::HttpRequest;
+use sqlx::{mysql::MySqlArguments, query::QueryAs, MySql};
+
+use crate::error::ApiError;
+use crate::views::page::Page;
+
+// Page size when the request doesn't give a limit
+const DEFAULT_LIMIT: u32 = 10;
+// Largest page a request can ask for, bigger limits are cut down to it
+pub(crate) const MAX_LIMIT: u32 = 100;
+
+/// Where a cursor page starts. Cursors are written as `after-<id>` and
+/// `before-<id>`, an empty cursor starts from the first row.
+#[derive(Clone, Copy)]
+enum Cursor {
+    Start,
+    After(i32),
+    Before(i32),
+}
+
+impl Cursor {
+    fn parse(value: &str) -> Result<Cursor, ApiError> {
+        if value.is_empty() {
+            return Ok(Cursor::Start);
+        }
+
+        let cursor = match value.split_once('-') {
+            Some(("after", id)) => id.parse().ok().map(Cursor::After),
+            Some(("before", id)) => id.parse().ok().map(Cursor::Before),
+            _ => None,
+        };
+
+        cursor.ok_or_else(|| ApiError::BadRequest("Cursor is not valid".to_string()))
+    }
+}
+
+/// How a list request wants its rows paged: by page number, or with a keyset
+/// cursor that stays stable while rows are added and removed. Cursor pages
+/// are always in the order of the key column.
+pub(crate) struct Paging {
+    limit: u32,
+    page: u32,
+    cursor: Option<Cursor>,
+}
+
+impl Paging {
+    pub(crate) fn new(
+        page: Option<u32>,
+        limit: Option<u32>,
+        cursor: Option<&str>,
+    ) -> Result<Self, ApiError> {
+        let limit = limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+        let page = page.unwrap_or(1).max(1);
+
+        // Every row up to the end of the page has to be countable, which
+        // also keeps Paging::offset from overflowing
+        if page.checked_mul(limit).is_none() {
+            return Err(ApiError::BadRequest("Page is out of range".to_string()));
+        }
+
+        Ok(Paging {
+            limit,
+            page,
+            cursor: cursor.map(Cursor::parse).transpose()?,
+        })
+    }
+
+    /// Condition to add to the WHERE clause, binds one parameter through
+    /// Paging::bind when it isn't empty
+    pub(crate) fn keyset(&self, column: &str) -> String {
+        match self.cursor {
+            Some(Cursor::After(_)) => format!("AND {} > ?", column),
+            Some(Cursor::Before(_)) => format!("AND {} < ?", column),
+            _ => String::new(),
+        }
+    }
+
+    /// Goes at the start of the ORDER BY clause, the key is unique so it
+    /// decides the order on its own. Before a cursor the rows are read
+    /// backwards and turned around again in Paging::page.
+    pub(crate) fn key_order(&self, column: &str) -> String {
+        match self.cursor {
+            Some(Cursor::Before(_)) => format!("{} DESC,", column),
+            Some(_) => format!("{} ASC,", column),
+            None => String::new(),
+        }
+    }
+
+    /// Binds the parameter for Paging::keyset, if it has one
+    pub(crate) fn bind<'q, O>(
+        &self,
+        query: QueryAs<'q, MySql, O, MySqlArguments>,
+    ) -> QueryAs<'q, MySql, O, MySqlArguments> {
+        match self.cursor {
+            Some(Cursor::After(id)) | Some(Cursor::Before(id)) => query.bind(id),
+            _ => query,
+        }
+    }
+
+    /// Rows to ask for in `LIMIT ?`. Cursor pages fetch one extra row to tell
+    /// whether there is another page.
+    pub(crate) fn limit(&self) -> u32 {
+        match self.cursor {
+            Some(_) => self.limit + 1,
+            None => self.limit,
+        }
+    }
+
+    /// Rows to skip in `OFFSET ?`
+    pub(crate) fn offset(&self) -> u32 {
+        match self.cursor {
+            Some(_) => 0,
+            None => (self.page - 1) * self.limit,
+        }
+    }
+
+    /// Wraps the rows of a page, with `key` giving the value of the key
+    /// column of a row for the cursors
+    pub(crate) fn page<T>(
+        &self,
+        req: &HttpRequest,
+        mut items: Vec<T>,
+        total: i64,
+        key: impl Fn(&T) -> i32,
+    ) -> Page<T> {
+        let Some(cursor) = self.cursor else {
+            let more = i64::from(self.page) * i64::from(self.limit) < total;
+            return Page {
+                items,
+                total,
+                page: Some(self.page),
+                limit: self.limit,
+                next: more.then(|| link(req, "page", &(self.page + 1).to_string())),
+                prev: (self.page > 1).then(|| link(req, "page", &(self.page - 1).to_string())),
+            };
+        };
+
+        let more = items.len() > self.limit as usize;
+        items.truncate(self.limit as usize);
+        if let Cursor::Before(_) = cursor {
+            items.reverse();
+        }
+
+        // Whether there are rows past the last item and before the first
+        let (after, before) = match cursor {
+            Cursor::Start => (more, false),
+            Cursor::After(_) => (more, true),
+            Cursor::Before(_) => (true, more),
+        };
+
+        let next = match items.last() {
+            Some(last) if after => Some(link(req, "cursor", &format!("after-{}", key(last)))),
+            _ => None,
+        };
+        let prev = match items.first() {
+            Some(first) if before => Some(link(req, "cursor", &format!("before-{}", key(first)))),
+            _ => None,
+        };
+
+        Page {
+            items,
+            total,
+            page: None,
+            limit: self.limit,
+            next,
+            prev,
+        }
+    }
+}
+
+// The request's own path and query with `page` or `cursor` swapped for the
+// given one
+fn link(req: &HttpRequest, name: &str, value: &str) -> String {
+    let mut query: Vec<&str> = req
+        .query_string()
+        .split('&')
+        .filter(|pair| {
+            let key = pair.split('=').next().unwrap_or_default();
+            !pair.is_empty() && key != "page" && key != "cursor"
+        })
+        .collect();
+
+    let pair = format!("{}={}", name, value);
+    query.push(&pair);
+
+    format!("{}?{}", req.path(), query.join("&"))
+}
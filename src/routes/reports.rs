@@ -0,0 +1,251 @@
+use actix_web::{get, web, HttpResponse};
+use chrono::NaiveDate;
+use serde::Serialize;
+use serde_json::json;
+use sqlx::{mysql::MySqlArguments, query::QueryAs, MySql};
+
+use super::bulk;
+use super::pagination::MAX_LIMIT;
+use crate::error::ApiError;
+use crate::views::reports::{
+    CategoryLoans, LoanLength, ReportFormat, ReportParams, TopBook, TopMember,
+};
+
+pub fn reports_config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/reports")
+            .service(top_books)
+            .service(top_members)
+            .service(loans_by_category)
+            .service(loan_length),
+    );
+}
+
+// Rows in the top books and members when the request doesn't give a limit
+const DEFAULT_LIMIT: u32 = 10;
+
+// Column orders of the CSV downloads, matching the fields of each report row
+const TOP_BOOK_HEADERS: &[&str] = &[
+    "book_id",
+    "title",
+    "author",
+    "category",
+    "loans",
+    "borrowers",
+];
+const TOP_MEMBER_HEADERS: &[&str] = &[
+    "member_id",
+    "first_name",
+    "last_name",
+    "loans",
+    "late_loans",
+];
+const CATEGORY_LOANS_HEADERS: &[&str] = &["month", "category", "loans"];
+const LOAN_LENGTH_HEADERS: &[&str] = &["month", "loans", "average_days", "longest_days"];
+
+/// The period of a report. Voided borrowings never count.
+#[derive(Clone, Copy)]
+struct DateRange {
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+}
+
+impl DateRange {
+    fn new(params: &ReportParams) -> Result<Self, ApiError> {
+        if let (Some(from), Some(to)) = (params.from, params.to) {
+            if to < from {
+                return Err(ApiError::BadRequest(
+                    "The end of the period can't be before its start".to_string(),
+                ));
+            }
+        }
+
+        Ok(DateRange {
+            from: params.from,
+            to: params.to,
+        })
+    }
+
+    /// Condition on the borrowings in `br` whose `column` falls in the period
+    fn sql(&self, column: &str) -> String {
+        format!(
+            "br.voided_at IS NULL AND (? IS NULL OR {0} >= ?) AND (? IS NULL OR {0} <= ?)",
+            column
+        )
+    }
+
+    /// Binds the parameters for `sql` in order
+    fn bind<'q, O>(
+        self,
+        query: QueryAs<'q, MySql, O, MySqlArguments>,
+    ) -> QueryAs<'q, MySql, O, MySqlArguments> {
+        query
+            .bind(self.from)
+            .bind(self.from)
+            .bind(self.to)
+            .bind(self.to)
+    }
+}
+
+/// The rows as JSON, or as a CSV download when asked for
+fn respond<T: Serialize>(
+    params: &ReportParams,
+    filename: &str,
+    headers: &[&str],
+    rows: Vec<T>,
+) -> Result<HttpResponse, ApiError> {
+    match params.format.unwrap_or_default() {
+        ReportFormat::Json => Ok(HttpResponse::Ok().json(json!(rows))),
+        ReportFormat::Csv => bulk::csv_rows(filename, headers, &rows),
+    }
+}
+
+/// Books lent most often in the period, by the date they were borrowed
+#[get("/top-books")]
+async fn top_books(
+    params: web::Query<ReportParams>,
+    pool: web::Data<sqlx::MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    let range = DateRange::new(&params)?;
+
+    let q = format!(
+        r#"
+        SELECT
+            b.id AS book_id, b.title, b.author, b.category,
+            COUNT(*) AS loans,
+            COUNT(DISTINCT br.member_id) AS borrowers
+        FROM BORROWINGS br
+        JOIN COPIES c ON c.id = br.copy_id
+        JOIN BOOKS b ON b.id = c.book_id
+        WHERE {}
+        GROUP BY b.id, b.title, b.author, b.category
+        ORDER BY loans DESC, b.id
+        LIMIT ?
+        "#,
+        range.sql("br.borrow_date")
+    );
+
+    let rows: Vec<TopBook> = range
+        .bind(sqlx::query_as(&q))
+        .bind(params.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT))
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    respond(&params, "top-books.csv", TOP_BOOK_HEADERS, rows)
+}
+
+/// Members who borrowed the most in the period. Borrowings of deleted
+/// members are left out.
+#[get("/top-members")]
+async fn top_members(
+    params: web::Query<ReportParams>,
+    pool: web::Data<sqlx::MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    let range = DateRange::new(&params)?;
+
+    let q = format!(
+        r#"
+        SELECT
+            m.id AS member_id, m.first_name, m.last_name,
+            COUNT(*) AS loans,
+            CAST(SUM(COALESCE(br.return_date, CURRENT_DATE) > br.due_date) AS SIGNED)
+                AS late_loans
+        FROM BORROWINGS br
+        JOIN MEMBERS m ON m.id = br.member_id
+        WHERE {}
+        GROUP BY m.id, m.first_name, m.last_name
+        ORDER BY loans DESC, m.id
+        LIMIT ?
+        "#,
+        range.sql("br.borrow_date")
+    );
+
+    let rows: Vec<TopMember> = range
+        .bind(sqlx::query_as(&q))
+        .bind(params.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT))
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    respond(&params, "top-members.csv", TOP_MEMBER_HEADERS, rows)
+}
+
+/// Loans per category for each month of the period
+#[get("/loans-by-category")]
+async fn loans_by_category(
+    params: web::Query<ReportParams>,
+    pool: web::Data<sqlx::MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    let range = DateRange::new(&params)?;
+
+    let q = format!(
+        r#"
+        SELECT
+            DATE_FORMAT(br.borrow_date, '%Y-%m') AS month,
+            b.category,
+            COUNT(*) AS loans
+        FROM BORROWINGS br
+        JOIN COPIES c ON c.id = br.copy_id
+        JOIN BOOKS b ON b.id = c.book_id
+        WHERE {}
+        GROUP BY month, b.category
+        ORDER BY month, b.category
+        "#,
+        range.sql("br.borrow_date")
+    );
+
+    let rows: Vec<CategoryLoans> = range
+        .bind(sqlx::query_as(&q))
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    respond(
+        &params,
+        "loans-by-category.csv",
+        CATEGORY_LOANS_HEADERS,
+        rows,
+    )
+}
+
+/// Average and longest loan for each month, counting the loans returned in
+/// the period by their return date
+#[get("/loan-length")]
+async fn loan_length(
+    params: web::Query<ReportParams>,
+    pool: web::Data<sqlx::MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    let range = DateRange::new(&params)?;
+
+    // The average is worked out here, MySQL's AVG would come back as a DECIMAL
+    let q = format!(
+        r#"
+        SELECT
+            DATE_FORMAT(br.return_date, '%Y-%m') AS month,
+            COUNT(*),
+            CAST(SUM(DATEDIFF(br.return_date, br.borrow_date)) AS SIGNED),
+            CAST(MAX(DATEDIFF(br.return_date, br.borrow_date)) AS SIGNED)
+        FROM BORROWINGS br
+        WHERE br.return_date IS NOT NULL AND {}
+        GROUP BY month
+        ORDER BY month
+        "#,
+        range.sql("br.return_date")
+    );
+
+    let months: Vec<(String, i64, i64, i64)> = range
+        .bind(sqlx::query_as(&q))
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    let rows: Vec<LoanLength> = months
+        .into_iter()
+        .map(|(month, loans, total_days, longest_days)| LoanLength {
+            month,
+            loans,
+            // Rounded to a tenth of a day
+            average_days: (total_days as f64 / loans as f64 * 10.0).round() / 10.0,
+            longest_days,
+        })
+        .collect();
+
+    respond(&params, "loan-length.csv", LOAN_LENGTH_HEADERS, rows)
+}
@@ -0,0 +1,261 @@
+use actix_web::{
+    cookie::{Cookie, SameSite},
+    delete, get, post, put, web, HttpRequest, HttpResponse,
+};
+use serde_json::json;
+
+use crate::auth::{self, Admin, SESSION_COOKIE};
+use crate::config::LibraryConfig;
+use crate::error::ApiError;
+use crate::views::staff::{LoginRequest, NewStaff, Session, Staff, UpdateStaff};
+
+pub fn auth_config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/auth")
+            .service(login)
+            .service(logout)
+            .service(get_session),
+    );
+}
+
+pub fn staff_config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/staff")
+            .service(get_staff)
+            .service(create_staff)
+            .service(get_staff_by_id)
+            .service(update_staff)
+            .service(delete_staff),
+    );
+}
+
+#[post("/login")]
+async fn login(
+    data: web::Json<LoginRequest>,
+    pool: web::Data<sqlx::MySqlPool>,
+    config: web::Data<LibraryConfig>,
+) -> Result<HttpResponse, ApiError> {
+    let invalid = || ApiError::Unauthorized("Invalid username or password".to_string());
+
+    // Unknown usernames and wrong passwords get the same answer, in the same
+    // time
+    let account = sqlx::query!(
+        r#"SELECT id, password_hash FROM STAFF WHERE username = ?"#,
+        data.username
+    )
+    .fetch_optional(pool.get_ref())
+    .await?;
+
+    // Argon2 is deliberately slow, so keep it off the async workers
+    let password = data.password.clone();
+    let password_hash = account
+        .as_ref()
+        .map(|account| account.password_hash.clone());
+    if !web::block(move || auth::verify_login(&password, password_hash.as_deref())).await? {
+        return Err(invalid());
+    }
+    let account = account.ok_or_else(invalid)?;
+
+    let (token, expires_at) =
+        auth::create_session(pool.get_ref(), account.id, config.session_hours).await?;
+
+    let staff = fetch_staff(pool.get_ref(), account.id)
+        .await?
+        .ok_or(ApiError::Internal)?;
+
+    let cookie = Cookie::build(SESSION_COOKIE, token.clone())
+        .path("/")
+        .http_only(true)
+        .same_site(SameSite::Strict)
+        .max_age(actix_web::cookie::time::Duration::hours(
+            config.session_hours,
+        ))
+        .finish();
+
+    Ok(HttpResponse::Ok().cookie(cookie).json(json!(Session {
+        token,
+        expires_at,
+        staff,
+    })))
+}
+
+#[post("/logout")]
+async fn logout(
+    req: HttpRequest,
+    pool: web::Data<sqlx::MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    if let Some(token) = auth::session_token(&req, SESSION_COOKIE) {
+        sqlx::query!(
+            r#"DELETE FROM STAFF_SESSIONS WHERE token_hash = ?"#,
+            auth::hash_token(&token)
+        )
+        .execute(pool.get_ref())
+        .await?;
+    }
+
+    let mut cookie = Cookie::build(SESSION_COOKIE, "").path("/").finish();
+    cookie.make_removal();
+
+    Ok(HttpResponse::NoContent().cookie(cookie).finish())
+}
+
+#[get("/session")]
+async fn get_session(staff: Staff) -> HttpResponse {
+    HttpResponse::Ok().json(json!(staff))
+}
+
+async fn fetch_staff(pool: &sqlx::MySqlPool, id: i32) -> Result<Option<Staff>, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+        SELECT id, username, role, created_at
+        FROM STAFF
+        WHERE id = ?
+        "#,
+    )
+    .bind(id)
+    .fetch_optional(pool)
+    .await
+}
+
+#[get("/")]
+async fn get_staff(
+    _admin: Admin,
+    pool: web::Data<sqlx::MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    let results: Vec<Staff> = sqlx::query_as(
+        r#"
+        SELECT id, username, role, created_at
+        FROM STAFF
+        ORDER BY username
+        "#,
+    )
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    Ok(HttpResponse::Ok().json(json!(results)))
+}
+
+#[post("/")]
+async fn create_staff(
+    _admin: Admin,
+    data: web::Json<NewStaff>,
+    pool: web::Data<sqlx::MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    if data.username.trim().is_empty() || data.password.is_empty() {
+        return Err(ApiError::BadRequest(
+            "A username and password are required".to_string(),
+        ));
+    }
+
+    let password = data.password.clone();
+    let password_hash = web::block(move || auth::hash_password(&password)).await??;
+
+    sqlx::query!(
+        r#"
+            INSERT INTO STAFF (username, password_hash, role)
+            VALUES (?, ?, ?)
+        "#,
+        data.username.trim(),
+        password_hash,
+        data.role.as_str()
+    )
+    .execute(pool.get_ref())
+    .await
+    .map_err(|error| match error {
+        sqlx::Error::Database(error) if error.is_unique_violation() => {
+            ApiError::Conflict("Username is already taken".to_string())
+        }
+        error => error.into(),
+    })?;
+
+    Ok(HttpResponse::Created().finish())
+}
+
+#[get("/{id}")]
+async fn get_staff_by_id(
+    _admin: Admin,
+    id: web::Path<i32>,
+    pool: web::Data<sqlx::MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    let staff = fetch_staff(pool.get_ref(), id.into_inner())
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Staff account not found".to_string()))?;
+
+    Ok(HttpResponse::Ok().json(json!(staff)))
+}
+
+#[put("/{id}")]
+async fn update_staff(
+    Admin(admin): Admin,
+    id: web::Path<i32>,
+    data: web::Json<UpdateStaff>,
+    pool: web::Data<sqlx::MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    let id = id.into_inner();
+
+    let staff = fetch_staff(pool.get_ref(), id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Staff account not found".to_string()))?;
+
+    // Stops the last admin from locking everyone out by accident
+    if admin.id == id && data.role.is_some_and(|role| role != staff.role) {
+        return Err(ApiError::Conflict(
+            "You cannot change your own role".to_string(),
+        ));
+    }
+
+    let password_hash = match data.password.clone() {
+        Some(password) if password.is_empty() => {
+            return Err(ApiError::BadRequest("Password cannot be empty".to_string()))
+        }
+        Some(password) => Some(web::block(move || auth::hash_password(&password)).await??),
+        None => None,
+    };
+
+    sqlx::query!(
+        r#"
+            UPDATE STAFF
+            SET password_hash = COALESCE(?, password_hash), role = ?
+            WHERE id = ?
+        "#,
+        password_hash,
+        data.role.unwrap_or(staff.role).as_str(),
+        id
+    )
+    .execute(pool.get_ref())
+    .await?;
+
+    // A new password signs the account out everywhere
+    if password_hash.is_some() {
+        sqlx::query!(r#"DELETE FROM STAFF_SESSIONS WHERE staff_id = ?"#, id)
+            .execute(pool.get_ref())
+            .await?;
+    }
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[delete("/{id}")]
+async fn delete_staff(
+    Admin(admin): Admin,
+    id: web::Path<i32>,
+    pool: web::Data<sqlx::MySqlPool>,
+) -> Result<HttpResponse, ApiError> {
+    let id = id.into_inner();
+
+    if admin.id == id {
+        return Err(ApiError::Conflict(
+            "You cannot delete your own account".to_string(),
+        ));
+    }
+
+    let result = sqlx::query!(r#"DELETE FROM STAFF WHERE id = ?"#, id)
+        .execute(pool.get_ref())
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(ApiError::NotFound("Staff account not found".to_string()));
+    }
+
+    Ok(HttpResponse::NoContent().finish())
+}
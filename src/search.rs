@@ -0,0 +1,283 @@
+use std::collections::HashMap;
+
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+// InnoDB doesn't index words shorter than innodb_ft_min_token_size (3 by
+// default), so requiring one would match nothing
+const MIN_WORD_LENGTH: usize = 3;
+
+// INFORMATION_SCHEMA.INNODB_FT_DEFAULT_STOPWORD, which aren't indexed either
+const STOPWORDS: &[&str] = &[
+    "a", "about", "an", "are", "as", "at", "be", "by", "com", "de", "en", "for", "from", "how",
+    "i", "in", "is", "it", "la", "of", "on", "or", "that", "the", "this", "to", "was", "what",
+    "when", "where", "who", "will", "with", "und", "www",
+];
+
+// Most "did you mean" suggestions given for one search
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Turns a search box query into a boolean mode FULLTEXT query. Every word
+/// has to match, a "quoted phrase" has to match as a phrase, and a word
+/// ending in `*` matches any word starting with it, e.g.
+/// `"great gatsby" fitz*` becomes `+"great gatsby" +fitz*`. Other operators
+/// are dropped, along with stopwords and words too short to be indexed.
+/// Returns `None` when nothing searchable is left.
+pub fn boolean_query(q: &str) -> Option<String> {
+    build_query(q, |word| format!("+{}", word))
+}
+
+/// Like [`boolean_query`], but each plain word also matches the words in the
+/// catalog it is a likely typo of or differs from only in accents, e.g.
+/// `fitzgerold` becomes `+(fitzgerold fitzgerald)`. Phrases and prefixes are
+/// left as they are.
+pub fn fuzzy_query(q: &str, vocabulary: &Vocabulary) -> Option<String> {
+    build_query(q, |word| {
+        let mut words = vec![word.to_string()];
+        for correction in vocabulary.corrections(word) {
+            let correction = correction.to_lowercase();
+            if !words.contains(&correction) {
+                words.push(correction);
+            }
+        }
+
+        match words.len() {
+            1 => format!("+{}", word),
+            _ => format!("+({})", words.join(" ")),
+        }
+    })
+}
+
+/// "Did you mean" queries for a search that found nothing, made by swapping
+/// the words that aren't in the catalog for the closest ones that are. The
+/// first suggestion uses the closest match for every word, the rest try the
+/// runners up one word at a time.
+pub fn suggestions(q: &str, vocabulary: &Vocabulary) -> Vec<String> {
+    // Corrections for each searchable word in q, in order, or None when the
+    // word is fine as it is
+    let mut corrections: Vec<Option<Vec<&str>>> = vec![];
+    replace_words(q, |word| {
+        let word_corrections = vocabulary.corrections(word);
+        corrections.push(
+            match vocabulary.contains(word) || word_corrections.is_empty() {
+                true => None,
+                false => Some(word_corrections),
+            },
+        );
+        None
+    });
+
+    // Which correction each word uses in each suggestion
+    let mut choices = vec![vec![0; corrections.len()]];
+    for (i, word_corrections) in corrections.iter().enumerate() {
+        for j in 1..word_corrections.as_ref().map_or(0, Vec::len) {
+            let mut choice = vec![0; corrections.len()];
+            choice[i] = j;
+            choices.push(choice);
+        }
+    }
+
+    let mut suggestions: Vec<String> = vec![];
+    for choice in choices {
+        let mut i = 0;
+        let suggestion = replace_words(q, |_| {
+            let replacement = corrections[i]
+                .as_ref()
+                .map(|word_corrections| word_corrections[choice[i]].to_string());
+            i += 1;
+            replacement
+        });
+
+        if suggestion != q && !suggestions.contains(&suggestion) {
+            suggestions.push(suggestion);
+        }
+    }
+
+    suggestions.truncate(MAX_SUGGESTIONS);
+    suggestions
+}
+
+// Builds a boolean mode query, with `word` writing out each plain word
+fn build_query(q: &str, word: impl Fn(&str) -> String) -> Option<String> {
+    let mut terms = vec![];
+
+    // Splitting on quotes leaves the phrases at the odd positions
+    for (i, part) in q.split('"').enumerate() {
+        if i % 2 == 1 {
+            let phrase = words(part).join(" ");
+            if !phrase.is_empty() {
+                terms.push(format!("+\"{}\"", phrase));
+            }
+            continue;
+        }
+
+        for token in part.split_whitespace() {
+            let prefix = token.ends_with('*');
+            let pieces = words(token);
+
+            for (j, piece) in pieces.iter().enumerate() {
+                // Only the last piece of e.g. "sci-fi*" is a prefix
+                if prefix && j == pieces.len() - 1 {
+                    terms.push(format!("+{}*", piece));
+                } else if is_searchable(piece) {
+                    terms.push(word(piece));
+                }
+            }
+        }
+    }
+
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" "))
+    }
+}
+
+// Copies q, swapping each searchable plain word (not in a phrase or used as a
+// prefix) for whatever `replace` returns, lowercased words are passed in
+fn replace_words(q: &str, mut replace: impl FnMut(&str) -> Option<String>) -> String {
+    let mut result = String::new();
+    let mut word = String::new();
+    let mut in_phrase = false;
+
+    let mut chars = q.chars().peekable();
+    while let Some(c) = chars.next() {
+        if !c.is_alphanumeric() {
+            if c == '"' {
+                in_phrase = !in_phrase;
+            }
+            result.push(c);
+            continue;
+        }
+
+        word.push(c);
+        if chars.peek().is_some_and(|next| next.is_alphanumeric()) {
+            continue;
+        }
+
+        let lowercase = word.to_lowercase();
+        let replacement =
+            match !in_phrase && chars.peek() != Some(&'*') && is_searchable(&lowercase) {
+                true => replace(&lowercase),
+                false => None,
+            };
+        result.push_str(&replacement.unwrap_or_else(|| word.clone()));
+        word.clear();
+    }
+
+    result
+}
+
+fn is_searchable(word: &str) -> bool {
+    word.chars().count() >= MIN_WORD_LENGTH && !STOPWORDS.contains(&word)
+}
+
+// The lowercased words of value. Anything that isn't a letter or digit splits
+// words, which drops punctuation along with the boolean mode operators.
+fn words(value: &str) -> Vec<String> {
+    value
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Lowercases a word and strips its accents, so `Márquez` and `marquez`
+/// compare equal
+pub fn fold(word: &str) -> String {
+    word.nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(char::to_lowercase)
+        .map(|c| match c {
+            // Letters with no decomposition
+            'ø' => "o".to_string(),
+            'ł' => "l".to_string(),
+            'đ' => "d".to_string(),
+            'æ' => "ae".to_string(),
+            'œ' => "oe".to_string(),
+            'ß' => "ss".to_string(),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+/// The searchable words of the catalog, used to correct typos
+#[derive(Default)]
+pub struct Vocabulary {
+    // Folded word to the way it is most often written and how often it
+    // appears
+    words: HashMap<String, (String, u32)>,
+}
+
+impl Vocabulary {
+    pub fn new<'a>(texts: impl IntoIterator<Item = &'a str>) -> Vocabulary {
+        let mut spellings: HashMap<String, HashMap<String, u32>> = HashMap::new();
+
+        for text in texts {
+            for word in text.split(|c: char| !c.is_alphanumeric()) {
+                if is_searchable(&word.to_lowercase()) {
+                    *spellings
+                        .entry(fold(word))
+                        .or_default()
+                        .entry(word.to_string())
+                        .or_default() += 1;
+                }
+            }
+        }
+
+        let words = spellings
+            .into_iter()
+            .map(|(folded, spellings)| {
+                let count = spellings.values().sum();
+                let spelling = spellings
+                    .into_iter()
+                    .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(&a.0)))
+                    .map(|(spelling, _)| spelling)
+                    .unwrap_or_default();
+                (folded, (spelling, count))
+            })
+            .collect();
+
+        Vocabulary { words }
+    }
+
+    /// Whether the word is in the catalog written the same way, ignoring case
+    pub fn contains(&self, word: &str) -> bool {
+        self.words
+            .get(&fold(word))
+            .is_some_and(|(spelling, _)| spelling.to_lowercase() == word.to_lowercase())
+    }
+
+    /// Words in the catalog within a few edits of `word` or the same apart
+    /// from accents, closest and then most common first. Longer words are
+    /// allowed more edits: none up to two letters, one up to five and two
+    /// after that.
+    pub fn corrections(&self, word: &str) -> Vec<&str> {
+        let folded = fold(word);
+        let length = folded.chars().count();
+        let max_distance = match length {
+            0..=2 => 0,
+            3..=5 => 1,
+            _ => 2,
+        };
+
+        let mut corrections: Vec<(&str, usize, u32)> = self
+            .words
+            .iter()
+            .filter(|(candidate, _)| candidate.chars().count().abs_diff(length) <= max_distance)
+            .map(|(candidate, (spelling, count))| {
+                let distance = strsim::osa_distance(&folded, candidate);
+                (spelling.as_str(), distance, *count)
+            })
+            .filter(|(spelling, distance, _)| {
+                *distance <= max_distance && spelling.to_lowercase() != word.to_lowercase()
+            })
+            .collect();
+
+        corrections.sort_by(|a, b| a.1.cmp(&b.1).then(b.2.cmp(&a.2)).then(a.0.cmp(b.0)));
+        corrections
+            .into_iter()
+            .map(|(spelling, _, _)| spelling)
+            .collect()
+    }
+}
@@ -0,0 +1,80 @@
+use phonenumber::{country, Mode};
+use serde::{Deserialize, Deserializer};
+use validator::ValidationError;
+
+use crate::isbn::Isbn;
+use crate::views::Status;
+
+// Numbers written without a +country code are read as US numbers
+const DEFAULT_REGION: country::Id = country::Id::US;
+
+/// Serde helper that trims whitespace from a string field
+pub fn trimmed<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(String::deserialize(deserializer)?.trim().to_string())
+}
+
+/// Like [`trimmed`] for optional fields. A blank value counts as missing.
+pub fn trimmed_opt<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(Option::<String>::deserialize(deserializer)?
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty()))
+}
+
+/// Like [`trimmed`] for optional fields that can't be cleared. A blank value
+/// is kept as an empty string so validation can reject it.
+pub fn trimmed_some<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(Option::<String>::deserialize(deserializer)?.map(|value| value.trim().to_string()))
+}
+
+/// Serde helper that tells a field set to null (`Some(None)`) apart from one
+/// that was left out, which `#[serde(default)]` turns into `None`
+pub fn nullable<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Ok(Some(Option::<T>::deserialize(deserializer)?))
+}
+
+/// Custom validator for phone numbers in any common format
+pub fn phone(value: &str) -> Result<(), ValidationError> {
+    match phonenumber::parse(Some(DEFAULT_REGION), value) {
+        Ok(number) if phonenumber::is_valid(&number) => Ok(()),
+        _ => Err(ValidationError::new("phone").with_message("Phone number is not valid".into())),
+    }
+}
+
+/// Custom validator for ISBN-10 and ISBN-13 numbers, checksum included
+pub fn isbn(value: &str) -> Result<(), ValidationError> {
+    match Isbn::parse(value) {
+        Some(_) => Ok(()),
+        None => Err(ValidationError::new("isbn").with_message("ISBN is not valid".into())),
+    }
+}
+
+/// Custom validator for the status a book is withdrawn with
+pub fn withdrawn(status: &Status) -> Result<(), ValidationError> {
+    match status.is_withdrawn() {
+        true => Ok(()),
+        false => Err(ValidationError::new("withdrawn")
+            .with_message("Status must be withdrawn, lost, damaged or in_repair".into())),
+    }
+}
+
+/// Rewrites a phone number that passed [`phone`] in E.164 form, e.g.
+/// `(415) 555-2671` becomes `+14155552671`
+pub fn to_e164(value: &str) -> String {
+    match phonenumber::parse(Some(DEFAULT_REGION), value) {
+        Ok(number) => number.format().mode(Mode::E164).to_string(),
+        Err(_) => value.to_string(),
+    }
+}
@@ -0,0 +1,102 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{prelude::FromRow, types::Json};
+
+// The kinds of record the audit log follows
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditEntity {
+    Book,
+    Copy,
+    Member,
+    Borrowing,
+}
+
+impl AuditEntity {
+    pub fn as_str(&self) -> &str {
+        match self {
+            AuditEntity::Book => "book",
+            AuditEntity::Copy => "copy",
+            AuditEntity::Member => "member",
+            AuditEntity::Borrowing => "borrowing",
+        }
+    }
+}
+
+impl TryFrom<String> for AuditEntity {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            "book" => Ok(AuditEntity::Book),
+            "copy" => Ok(AuditEntity::Copy),
+            "member" => Ok(AuditEntity::Member),
+            "borrowing" => Ok(AuditEntity::Borrowing),
+            _ => Err(format!("Unknown audit entity: {}", value)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditAction {
+    Create,
+    Update,
+    Delete,
+}
+
+impl AuditAction {
+    pub fn as_str(&self) -> &str {
+        match self {
+            AuditAction::Create => "create",
+            AuditAction::Update => "update",
+            AuditAction::Delete => "delete",
+        }
+    }
+}
+
+impl TryFrom<String> for AuditAction {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            "create" => Ok(AuditAction::Create),
+            "update" => Ok(AuditAction::Update),
+            "delete" => Ok(AuditAction::Delete),
+            _ => Err(format!("Unknown audit action: {}", value)),
+        }
+    }
+}
+
+// A change to a record. `staff_id` is who made it, or `member_id` when a
+// member made it through the portal. `staff_username` still names the staff
+// member after their account is deleted. `before` is null for a create and
+// `after` for a delete.
+#[derive(Serialize, Deserialize, FromRow)]
+pub struct AuditEntry {
+    pub id: i32,
+    pub staff_id: Option<i32>,
+    pub staff_username: Option<String>,
+    pub member_id: Option<i32>,
+    #[sqlx(try_from = "String")]
+    pub entity: AuditEntity,
+    pub entity_id: i32,
+    #[sqlx(try_from = "String")]
+    pub action: AuditAction,
+    pub before: Option<Json<Value>>,
+    pub after: Option<Json<Value>>,
+    pub created_at: NaiveDateTime,
+}
+
+// Filters for the audit log. `id` picks one record and needs `entity`.
+#[derive(Serialize, Deserialize)]
+pub struct AuditSearchParams {
+    pub entity: Option<AuditEntity>,
+    pub id: Option<i32>,
+    pub staff_id: Option<i32>,
+    pub limit: Option<u32>,
+    pub page: Option<u32>,
+    // Pages with a keyset cursor instead, empty for the first page
+    pub cursor: Option<String>,
+}
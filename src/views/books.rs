@@ -1,7 +1,10 @@
+use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
 use sqlx::prelude::FromRow;
+use validator::Validate;
 
-use super::{Order, Status};
+use super::{page::Page, Order, Status};
+use crate::validation::{self, trimmed, trimmed_opt, trimmed_some};
 
 #[derive(Serialize, Deserialize, FromRow)]
 pub struct Book {
@@ -9,47 +12,184 @@ pub struct Book {
     pub title: String,
     pub author: String,
     pub category: Option<String>,
+    pub isbn_10: Option<String>,
+    pub isbn_13: Option<String>,
+    pub publisher: Option<String>,
+    pub publication_year: Option<i16>,
     pub status: Status,
+    pub available_copies: i64,
+    pub total_copies: i64,
+    // Why and when the book was taken out of circulation, null while it's in
+    // circulation
+    pub withdrawn_reason: Option<String>,
+    pub withdrawn_at: Option<NaiveDate>,
+}
+
+// A book in the book list. `score` is how relevant the book is to the `q`
+// search and is left out when not searching.
+#[derive(Serialize, Deserialize, FromRow)]
+pub struct BookMatch {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub book: Book,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score: Option<f64>,
+}
+
+// Response of the book list, a page of books with two extras. `suggestions`
+// holds "did you mean" searches when a `q` search found nothing, and
+// `facets` is only there when asked for.
+#[derive(Serialize, Deserialize)]
+pub struct BookList {
+    #[serde(flatten)]
+    pub page: Page<BookMatch>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub suggestions: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub facets: Option<BookFacets>,
+}
+
+// How many of the matching books have each value, most common first. A
+// `value` of null counts the books with no value.
+#[derive(Serialize, Deserialize)]
+pub struct FacetCount<T> {
+    pub value: Option<T>,
+    pub count: i64,
 }
 
 #[derive(Serialize, Deserialize)]
+pub struct BookFacets {
+    pub category: Vec<FacetCount<String>>,
+    pub status: Vec<FacetCount<String>>,
+    pub author: Vec<FacetCount<String>>,
+    // Decades as their first year, e.g. 1990 for 1990 to 1999
+    pub decade: Vec<FacetCount<i64>>,
+}
+
+// Lengths match the BOOKS columns
+#[derive(Serialize, Deserialize, Validate)]
 pub struct NewBook {
+    #[serde(deserialize_with = "trimmed")]
+    #[validate(length(min = 1, max = 100, message = "Title must be 1 to 100 characters"))]
     pub title: String,
+    #[serde(deserialize_with = "trimmed")]
+    #[validate(length(min = 1, max = 100, message = "Author must be 1 to 100 characters"))]
     pub author: String,
+    #[serde(default, deserialize_with = "trimmed_opt")]
+    #[validate(length(max = 50, message = "Category must be at most 50 characters"))]
     pub category: Option<String>,
+    // Either form, the other one is filled in when it exists
+    #[serde(default, deserialize_with = "trimmed_opt")]
+    #[validate(custom(function = "validation::isbn"))]
+    pub isbn: Option<String>,
+    #[serde(default, deserialize_with = "trimmed_opt")]
+    #[validate(length(max = 100, message = "Publisher must be at most 100 characters"))]
+    pub publisher: Option<String>,
+    #[validate(range(
+        min = 1000,
+        max = 9999,
+        message = "Publication year must be a four digit year"
+    ))]
+    pub publication_year: Option<i16>,
+    // Number of copies to create along with the title, defaults to one
+    #[validate(range(min = 1, max = 100, message = "Copies must be 1 to 100"))]
+    pub copies: Option<u32>,
 }
 
-#[derive(Serialize, Deserialize)]
+// A row of a CSV import. Besides the columns of NewBook it reads the ones the
+// export writes, so an exported file can be imported again: `isbn_13` or
+// `isbn_10` stand in for `isbn` and `total_copies` for `copies`.
+#[derive(Deserialize)]
+pub struct BookCsvRow {
+    pub title: String,
+    pub author: String,
+    pub category: Option<String>,
+    pub isbn: Option<String>,
+    pub isbn_10: Option<String>,
+    pub isbn_13: Option<String>,
+    pub publisher: Option<String>,
+    pub publication_year: Option<i16>,
+    pub copies: Option<u32>,
+    pub total_copies: Option<u32>,
+}
+
+impl From<BookCsvRow> for NewBook {
+    fn from(row: BookCsvRow) -> Self {
+        NewBook {
+            title: row.title,
+            author: row.author,
+            category: row.category,
+            isbn: row.isbn.or(row.isbn_13).or(row.isbn_10),
+            publisher: row.publisher,
+            publication_year: row.publication_year,
+            // A book exported with no copies left gets the usual one
+            copies: row.copies.or(row.total_copies.filter(|copies| *copies > 0)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Validate)]
 pub struct UpdateBook {
+    #[serde(default, deserialize_with = "trimmed_some")]
+    #[validate(length(min = 1, max = 100, message = "Title must be 1 to 100 characters"))]
     pub title: Option<String>,
+    #[serde(default, deserialize_with = "trimmed_some")]
+    #[validate(length(min = 1, max = 100, message = "Author must be 1 to 100 characters"))]
     pub author: Option<String>,
+    #[serde(default, deserialize_with = "trimmed_opt")]
+    #[validate(length(max = 50, message = "Category must be at most 50 characters"))]
     pub category: Option<String>,
-    pub status: Option<Status>,
+    #[serde(default, deserialize_with = "trimmed_opt")]
+    #[validate(custom(function = "validation::isbn"))]
+    pub isbn: Option<String>,
+    #[serde(default, deserialize_with = "trimmed_opt")]
+    #[validate(length(max = 100, message = "Publisher must be at most 100 characters"))]
+    pub publisher: Option<String>,
+    #[validate(range(
+        min = 1000,
+        max = 9999,
+        message = "Publication year must be a four digit year"
+    ))]
+    pub publication_year: Option<i16>,
+}
+
+// Takes a book out of circulation with one of the withdrawn statuses. `date`
+// defaults to today.
+#[derive(Serialize, Deserialize, Validate)]
+pub struct WithdrawBook {
+    #[validate(custom(function = "validation::withdrawn"))]
+    pub status: Status,
+    #[serde(deserialize_with = "trimmed")]
+    #[validate(length(min = 1, max = 255, message = "Reason must be 1 to 255 characters"))]
+    pub reason: String,
+    pub date: Option<NaiveDate>,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct SearchParams {
+    // Searches the title, author and category at once, see search::boolean_query
+    pub q: Option<String>,
+    // Lets `q` match words with typos or different accents
+    pub fuzzy: Option<bool>,
+    // Adds counts by category, status, author and decade to the response
+    pub facets: Option<bool>,
     pub title: Option<String>,
     pub author: Option<String>,
     pub category: Option<String>,
+    pub isbn: Option<String>,
+    // Withdrawn books are left out unless this asks for them, or is `all`
     pub status: Option<Status>,
     pub order: Option<Order>,
     pub order_by: Option<String>,
     pub limit: Option<u32>,
     pub page: Option<u32>,
+    // Pages with a keyset cursor instead, empty for the first page
+    pub cursor: Option<String>,
 }
 
 impl SearchParams {
-    pub fn get_offset(&self) -> u32 {
-        if self.page.is_none() || self.page.unwrap() == 0 {
-            0
-        } else {
-            (self.page.unwrap() - 1) * self.limit.unwrap_or(10)
-        }
-    }
-
     pub fn get_order_by(&self) -> String {
-        const ALLOWED_COLUMNS: [&str; 5] = ["id", "title", "author", "category", "status"];
+        const ALLOWED_COLUMNS: [&str; 6] = ["id", "title", "author", "category", "status", "score"];
 
         match &self.order_by {
             Some(column) if ALLOWED_COLUMNS.contains(&column.as_str()) => column.clone(),
@@ -64,8 +204,4 @@ impl SearchParams {
             self.order.clone().unwrap()
         }
     }
-
-    pub fn get_status(&self) -> Status {
-        self.status.clone().unwrap_or(Status::All)
-    }
 }
@@ -1,23 +1,135 @@
-use chrono::NaiveDate;
+use chrono::{NaiveDate, NaiveDateTime};
 use serde::{Deserialize, Serialize};
-use sqlx::prelude::FromRow;
+use serde_json::Value;
+use sqlx::{prelude::FromRow, types::Json};
+use validator::Validate;
 
 use super::{Order, Status};
+use crate::validation::{nullable, trimmed, trimmed_opt};
 
 #[derive(Serialize, Deserialize, FromRow)]
 pub struct Borrowing {
     pub id: i32,
-    pub book_id: Option<i32>,
+    pub copy_id: Option<i32>,
     pub member_id: Option<i32>,
     pub borrow_date: NaiveDate,
+    pub due_date: NaiveDate,
     pub return_date: Option<NaiveDate>,
+    pub renewal_count: i32,
+    // Set when an admin voided the borrowing, it then no longer counts as a loan
+    pub voided_at: Option<NaiveDateTime>,
+    pub void_reason: Option<String>,
+    // Set when an admin lent the book although the member broke these rules
+    pub override_staff_id: Option<i32>,
+    pub overridden_rules: Option<Json<Vec<BorrowingRule>>>,
+    #[sqlx(skip)]
+    pub renewals: Vec<Renewal>,
+    #[sqlx(skip)]
+    pub corrections: Vec<Correction>,
+}
+
+// One extension of a loan's due date
+#[derive(Serialize, Deserialize, FromRow, Default)]
+pub struct Renewal {
+    pub id: i32,
+    pub borrowing_id: i32,
+    pub previous_due_date: NaiveDate,
+    pub new_due_date: NaiveDate,
+    pub renewed_at: NaiveDateTime,
+}
+
+// A rule a member has to meet to borrow, see routes::eligibility
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BorrowingRule {
+    MembershipExpired,
+    // The member already has as many loans as their member type allows
+    LoanLimit,
+    OverdueLoans,
+    // The member owes more in fines than LibraryConfig::max_balance_cents
+    UnpaidFines,
+}
+
+impl BorrowingRule {
+    pub fn as_str(&self) -> &str {
+        match self {
+            BorrowingRule::MembershipExpired => "membership_expired",
+            BorrowingRule::LoanLimit => "loan_limit",
+            BorrowingRule::OverdueLoans => "overdue_loans",
+            BorrowingRule::UnpaidFines => "unpaid_fines",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CorrectionAction {
+    Edit,
+    Void,
+}
+
+impl CorrectionAction {
+    pub fn as_str(&self) -> &str {
+        match self {
+            CorrectionAction::Edit => "edit",
+            CorrectionAction::Void => "void",
+        }
+    }
+}
+
+impl TryFrom<String> for CorrectionAction {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            "edit" => Ok(CorrectionAction::Edit),
+            "void" => Ok(CorrectionAction::Void),
+            _ => Err(format!("Unknown correction action: {}", value)),
+        }
+    }
+}
+
+// A change an admin made to a borrowing. `changes` maps each changed field to
+// its `old` and `new` value.
+#[derive(Serialize, Deserialize, FromRow)]
+pub struct Correction {
+    pub id: i32,
+    pub borrowing_id: i32,
+    pub staff_id: Option<i32>,
+    #[sqlx(try_from = "String")]
+    pub action: CorrectionAction,
+    pub changes: Json<Value>,
+    pub reason: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+// Fixes to a borrowing, only the fields given are changed. A `return_date` of
+// null reopens a returned loan.
+#[derive(Serialize, Deserialize, Validate)]
+pub struct CorrectBorrowing {
+    pub member_id: Option<i32>,
+    pub borrow_date: Option<NaiveDate>,
+    pub due_date: Option<NaiveDate>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub return_date: Option<Option<NaiveDate>>,
+    #[serde(default, deserialize_with = "trimmed_opt")]
+    #[validate(length(max = 255, message = "Reason must be at most 255 characters"))]
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Validate)]
+pub struct VoidBorrowing {
+    #[serde(deserialize_with = "trimmed")]
+    #[validate(length(min = 1, max = 255, message = "Reason must be 1 to 255 characters"))]
+    pub reason: String,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct NewBorrowing {
-    pub book_id: i32,
+    pub copy_id: i32,
     pub member_id: i32,
     pub borrow_date: NaiveDate,
+    pub due_date: NaiveDate,
     pub return_date: Option<NaiveDate>,
 }
 
@@ -26,6 +138,8 @@ pub struct UpdateBorrowing {
     pub return_date: Option<NaiveDate>,
 }
 
+// Filters for the borrowing list. `from_date` and `to_date` bound the borrow
+// date and `due_from` and `due_to` the due date, all inclusive.
 #[derive(Serialize, Deserialize)]
 pub struct BorrowingSearchParams {
     pub book_id: Option<i32>,
@@ -33,50 +147,81 @@ pub struct BorrowingSearchParams {
     pub status: Option<Status>,
     pub from_date: Option<NaiveDate>,
     pub to_date: Option<NaiveDate>,
+    pub due_from: Option<NaiveDate>,
+    pub due_to: Option<NaiveDate>,
+    // Only loans that are overdue right now, or with false only those that aren't
+    pub overdue: Option<bool>,
+    // Loans at least this many days late, including ones returned late
+    pub min_overdue_days: Option<u32>,
     pub order: Option<Order>,
     pub order_by: Option<String>,
     pub limit: Option<u32>,
     pub page: Option<u32>,
+    // Pages with a keyset cursor instead, empty for the first page
+    pub cursor: Option<String>,
 }
 
 impl BorrowingSearchParams {
-    pub fn get_offset(&self) -> u32 {
-        if self.page.is_none() || self.page.unwrap() == 0 {
-            0
-        } else {
-            (self.page.unwrap() - 1) * self.limit.unwrap_or(10)
-        }
-    }
-
-    pub fn get_order_by(&self) -> String {
-        const ALLOWED_COLUMNS: [&str; 5] =
-            ["id", "book_id", "member_id", "borrow_date", "return_date"];
-
-        match &self.order_by {
-            Some(column) if ALLOWED_COLUMNS.contains(&column.as_str()) => column.clone(),
-            _ => "id".to_string(),
+    /// The BORROWED_BOOKS column to sort by, the borrow date unless another
+    /// allowed column is asked for
+    pub fn get_order_by(&self) -> &str {
+        match self.order_by.as_deref() {
+            Some("id") => "borrowing_id",
+            Some("book_id") => "id",
+            Some("member_id") => "borrower_id",
+            Some("title") => "title",
+            Some("borrower") => "borrower",
+            Some("due_date") => "due_date",
+            Some("return_date") => "return_date",
+            Some("overdue_days") => "overdue_days",
+            _ => "borrow_date",
         }
     }
 
     pub fn get_order(&self) -> Order {
         self.order.clone().unwrap_or(Order::ASC)
     }
+
+    pub fn get_status(&self) -> Status {
+        self.status.clone().unwrap_or(Status::All)
+    }
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Validate)]
 pub struct BorrowRequest {
-    pub book_id: i32,
+    pub book_id: Option<i32>,
+    // Lend this specific copy instead of the first available copy of the book
+    pub copy_id: Option<i32>,
+    pub barcode: Option<String>,
+    // Overrides the category loan period for this borrow only
+    #[validate(range(min = 1, max = 365, message = "Loan period must be 1 to 365 days"))]
+    pub loan_days: Option<u32>,
+    // Lends the book even if the member breaks the borrowing rules, admins only
+    #[serde(default, rename = "override")]
+    pub override_rules: bool,
+}
+
+#[derive(Deserialize)]
+pub struct ReturnRequest {
+    pub copy_id: Option<i32>,
+    pub barcode: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, FromRow)]
 pub struct BorrowedBook {
     pub id: i32,
+    pub borrowing_id: i32,
     pub title: String,
     pub author: String,
+    pub copy_id: i32,
+    pub barcode: Option<String>,
     pub borrower: String,
     pub borrower_id: i32,
     pub borrow_date: NaiveDate,
+    pub due_date: NaiveDate,
     pub return_date: Option<NaiveDate>,
+    pub overdue_days: i64,
+    pub renewal_count: i32,
     pub status: Status,
 }
 
@@ -85,19 +230,13 @@ pub struct BorrowParams {
     pub current: Option<bool>,
     pub page: Option<u32>,
     pub limit: Option<u32>,
+    // Pages with a keyset cursor instead, empty for the first page
+    pub cursor: Option<String>,
     pub order: Option<Order>,
     pub status: Option<Status>,
 }
 
 impl BorrowParams {
-    pub fn get_offset(&self) -> u32 {
-        if self.page.is_none() || self.page.unwrap() == 0 {
-            0
-        } else {
-            (self.page.unwrap() - 1) * self.limit.unwrap_or(10)
-        }
-    }
-
     pub fn get_order(&self) -> Order {
         self.order.clone().unwrap_or(Order::ASC)
     }
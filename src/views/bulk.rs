@@ -0,0 +1,69 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+// File formats for imports and exports, not every endpoint takes every one
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    #[default]
+    Csv,
+    // MARC 21 records in ISO 2709 transmission format
+    Marc,
+    Marcxml,
+}
+
+impl Format {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Format::Csv => "csv",
+            Format::Marc => "marc",
+            Format::Marcxml => "marcxml",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ImportParams {
+    pub format: Option<Format>,
+    // Check the file and report what would change without saving anything
+    pub dry_run: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ExportParams {
+    pub format: Option<Format>,
+}
+
+// A row that could not be imported, `line` is the line in the uploaded file
+// or for MARC files the position of the record
+#[derive(Serialize, Deserialize)]
+pub struct ImportError {
+    pub line: u64,
+    pub message: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportAction {
+    Create,
+    Update,
+}
+
+// What a dry run would do with one row
+#[derive(Serialize, Deserialize)]
+pub struct ImportPreview {
+    pub line: u64,
+    pub action: ImportAction,
+    pub record: Value,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    pub created: u32,
+    pub updated: u32,
+    pub errors: Vec<ImportError>,
+    // Only filled in for dry runs
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub preview: Vec<ImportPreview>,
+}
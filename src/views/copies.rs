@@ -0,0 +1,29 @@
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+
+use super::Status;
+
+// A physical copy of a book, the unit that is actually lent out
+#[derive(Serialize, Deserialize, FromRow)]
+pub struct Copy {
+    pub id: i32,
+    pub book_id: i32,
+    pub barcode: Option<String>,
+    pub shelf_location: Option<String>,
+    pub condition: Option<String>,
+    pub status: Status,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NewCopy {
+    pub barcode: Option<String>,
+    pub shelf_location: Option<String>,
+    pub condition: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct UpdateCopy {
+    pub barcode: Option<String>,
+    pub shelf_location: Option<String>,
+    pub condition: Option<String>,
+}
@@ -0,0 +1,140 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    error::BoxDynError,
+    mysql::{MySqlTypeInfo, MySqlValueRef},
+};
+use validator::Validate;
+
+use crate::validation::{trimmed, trimmed_opt};
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub enum FineKind {
+    #[serde(rename = "late", alias = "LATE", alias = "Late")]
+    Late,
+    #[serde(rename = "lost", alias = "LOST", alias = "Lost")]
+    Lost,
+    #[serde(rename = "damaged", alias = "DAMAGED", alias = "Damaged")]
+    Damaged,
+    #[serde(rename = "other", alias = "OTHER", alias = "Other")]
+    Other,
+}
+
+impl FineKind {
+    pub fn as_str(&self) -> &str {
+        match self {
+            FineKind::Late => "late",
+            FineKind::Lost => "lost",
+            FineKind::Damaged => "damaged",
+            FineKind::Other => "other",
+        }
+    }
+}
+
+impl TryFrom<String> for FineKind {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        match s.as_str() {
+            "late" => Ok(FineKind::Late),
+            "lost" => Ok(FineKind::Lost),
+            "damaged" => Ok(FineKind::Damaged),
+            "other" => Ok(FineKind::Other),
+            _ => Err(format!("Unknown fine kind: {}", s)),
+        }
+    }
+}
+
+impl sqlx::decode::Decode<'_, sqlx::MySql> for FineKind {
+    fn decode(value: MySqlValueRef<'_>) -> Result<Self, BoxDynError> {
+        let s = <String as sqlx::decode::Decode<sqlx::MySql>>::decode(value)?;
+        Ok(FineKind::try_from(s)?)
+    }
+}
+
+impl sqlx::Type<sqlx::MySql> for FineKind {
+    fn type_info() -> MySqlTypeInfo {
+        <String as sqlx::Type<sqlx::MySql>>::type_info()
+    }
+}
+
+// All amounts are stored and returned in cents to avoid rounding errors
+#[derive(Serialize, Deserialize)]
+pub struct Fine {
+    pub id: i32,
+    // Fines stay on the ledger with no member after the member is deleted
+    pub member_id: Option<i32>,
+    pub borrowing_id: Option<i32>,
+    pub kind: FineKind,
+    pub amount_cents: i64,
+    pub paid_cents: i64,
+    pub waived_cents: i64,
+    pub balance_cents: i64,
+    pub reason: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Validate)]
+pub struct NewFine {
+    pub member_id: i32,
+    pub borrowing_id: Option<i32>,
+    pub kind: FineKind,
+    #[validate(range(min = 1, message = "Fine amount must be positive"))]
+    pub amount_cents: i64,
+    #[serde(default, deserialize_with = "trimmed_opt")]
+    #[validate(length(max = 255, message = "Reason must be at most 255 characters"))]
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct FineTransaction {
+    pub id: i32,
+    pub fine_id: i32,
+    pub kind: String,
+    pub amount_cents: i64,
+    pub reason: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct FineDetails {
+    #[serde(flatten)]
+    pub fine: Fine,
+    pub transactions: Vec<FineTransaction>,
+}
+
+#[derive(Serialize, Deserialize, Validate)]
+pub struct FinePayment {
+    #[validate(range(min = 1, message = "Payment amount must be positive"))]
+    pub amount_cents: i64,
+    #[serde(default, deserialize_with = "trimmed_opt")]
+    #[validate(length(max = 255, message = "Reason must be at most 255 characters"))]
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Validate)]
+pub struct FineWaiver {
+    // Waives whatever is still owed when left out
+    #[validate(range(min = 1, message = "Waiver amount must be positive"))]
+    pub amount_cents: Option<i64>,
+    #[serde(deserialize_with = "trimmed")]
+    #[validate(length(min = 1, max = 255, message = "Reason must be 1 to 255 characters"))]
+    pub reason: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MemberBalance {
+    pub member_id: i32,
+    pub charged_cents: i64,
+    pub paid_cents: i64,
+    pub waived_cents: i64,
+    pub balance_cents: i64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct FineSearchParams {
+    pub member_id: Option<i32>,
+    pub outstanding: Option<bool>,
+    pub limit: Option<u32>,
+    pub page: Option<u32>,
+}
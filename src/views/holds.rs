@@ -0,0 +1,88 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub enum HoldStatus {
+    // In the queue for the next returned copy
+    #[serde(rename = "waiting", alias = "WAITING", alias = "Waiting")]
+    Waiting,
+    // A copy has been set aside and is waiting to be picked up
+    #[serde(rename = "ready", alias = "READY", alias = "Ready")]
+    Ready,
+    #[serde(rename = "fulfilled", alias = "FULFILLED", alias = "Fulfilled")]
+    Fulfilled,
+    #[serde(rename = "cancelled", alias = "CANCELLED", alias = "Cancelled")]
+    Cancelled,
+    #[serde(rename = "expired", alias = "EXPIRED", alias = "Expired")]
+    Expired,
+}
+
+impl HoldStatus {
+    pub fn as_str(&self) -> &str {
+        match self {
+            HoldStatus::Waiting => "waiting",
+            HoldStatus::Ready => "ready",
+            HoldStatus::Fulfilled => "fulfilled",
+            HoldStatus::Cancelled => "cancelled",
+            HoldStatus::Expired => "expired",
+        }
+    }
+}
+
+impl TryFrom<String> for HoldStatus {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        match s.as_str() {
+            "waiting" => Ok(HoldStatus::Waiting),
+            "ready" => Ok(HoldStatus::Ready),
+            "fulfilled" => Ok(HoldStatus::Fulfilled),
+            "cancelled" => Ok(HoldStatus::Cancelled),
+            "expired" => Ok(HoldStatus::Expired),
+            _ => Err(format!("Unknown hold status: {}", s)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, FromRow)]
+pub struct Hold {
+    pub id: i32,
+    pub book_id: i32,
+    pub title: String,
+    pub member_id: i32,
+    pub copy_id: Option<i32>,
+    pub position: i32,
+    #[sqlx(try_from = "String")]
+    pub status: HoldStatus,
+    pub created_at: NaiveDateTime,
+    pub ready_at: Option<NaiveDateTime>,
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NewHold {
+    pub book_id: i32,
+    pub member_id: i32,
+}
+
+// A member placing a hold for themselves through /api/me
+#[derive(Serialize, Deserialize)]
+pub struct PatronHold {
+    pub book_id: i32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct HoldPosition {
+    // 1 is the front of the queue
+    pub position: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct HoldSearchParams {
+    pub book_id: Option<i32>,
+    pub member_id: Option<i32>,
+    pub status: Option<HoldStatus>,
+    pub limit: Option<u32>,
+    pub page: Option<u32>,
+}
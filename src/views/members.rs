@@ -1,7 +1,10 @@
+use chrono::{NaiveDate, NaiveDateTime};
 use serde::{Deserialize, Serialize};
 use sqlx::prelude::FromRow;
+use validator::Validate;
 
 use super::Order;
+use crate::validation::{self, nullable, trimmed, trimmed_opt, trimmed_some};
 
 // Member structs
 #[derive(Serialize, Deserialize, FromRow)]
@@ -11,22 +14,135 @@ pub struct Member {
     pub last_name: String,
     pub email: String,
     pub phone: Option<String>,
+    pub card_number: Option<String>,
+    #[sqlx(try_from = "String")]
+    pub member_type: MemberType,
+    // Members can't borrow after this date, null when it never expires
+    pub membership_expires_at: Option<NaiveDate>,
+    // Deactivated members can't borrow, place holds or sign in, anonymised
+    // ones are deactivated with their personal details scrubbed
+    pub deactivated_at: Option<NaiveDateTime>,
+    pub anonymised_at: Option<NaiveDateTime>,
 }
 
-#[derive(Serialize, Deserialize)]
+// Decides how many books a member can have out at once, see
+// LibraryConfig::max_loans
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum MemberType {
+    #[default]
+    Adult,
+    Child,
+    Student,
+    Senior,
+}
+
+impl MemberType {
+    pub fn as_str(&self) -> &str {
+        match self {
+            MemberType::Adult => "adult",
+            MemberType::Child => "child",
+            MemberType::Student => "student",
+            MemberType::Senior => "senior",
+        }
+    }
+}
+
+impl TryFrom<String> for MemberType {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        match s.as_str() {
+            "adult" => Ok(MemberType::Adult),
+            "child" => Ok(MemberType::Child),
+            "student" => Ok(MemberType::Student),
+            "senior" => Ok(MemberType::Senior),
+            _ => Err(format!("Unknown member type: {}", s)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum MemberStatus {
+    #[default]
+    Active,
+    Deactivated,
+    Anonymised,
+    All,
+}
+
+impl MemberStatus {
+    pub fn as_str(&self) -> &str {
+        match self {
+            MemberStatus::Active => "active",
+            MemberStatus::Deactivated => "deactivated",
+            MemberStatus::Anonymised => "anonymised",
+            MemberStatus::All => "all",
+        }
+    }
+}
+
+// Lengths match the MEMBERS columns. Phone numbers are stored in E.164 form
+// so the longest valid number still fits in VARCHAR(20).
+#[derive(Serialize, Deserialize, Validate)]
 pub struct NewMember {
+    #[serde(deserialize_with = "trimmed")]
+    #[validate(length(min = 1, max = 100, message = "First name must be 1 to 100 characters"))]
     pub first_name: String,
+    #[serde(deserialize_with = "trimmed")]
+    #[validate(length(min = 1, max = 100, message = "Last name must be 1 to 100 characters"))]
     pub last_name: String,
+    #[serde(deserialize_with = "trimmed")]
+    #[validate(
+        email(message = "Email is not valid"),
+        length(max = 100, message = "Email must be at most 100 characters")
+    )]
     pub email: String,
+    #[serde(default, deserialize_with = "trimmed_opt")]
+    #[validate(custom(function = "validation::phone"))]
     pub phone: Option<String>,
+    // Adult when left out
+    pub member_type: Option<MemberType>,
+    pub membership_expires_at: Option<NaiveDate>,
 }
 
-#[derive(Serialize, Deserialize)]
+// A `membership_expires_at` of null makes the membership never expire
+#[derive(Serialize, Deserialize, Validate)]
 pub struct UpdateMember {
+    #[serde(default, deserialize_with = "trimmed_some")]
+    #[validate(length(min = 1, max = 100, message = "First name must be 1 to 100 characters"))]
     pub first_name: Option<String>,
+    #[serde(default, deserialize_with = "trimmed_some")]
+    #[validate(length(min = 1, max = 100, message = "Last name must be 1 to 100 characters"))]
     pub last_name: Option<String>,
+    #[serde(default, deserialize_with = "trimmed_opt")]
+    #[validate(
+        email(message = "Email is not valid"),
+        length(max = 100, message = "Email must be at most 100 characters")
+    )]
     pub email: Option<String>,
+    #[serde(default, deserialize_with = "trimmed_opt")]
+    #[validate(custom(function = "validation::phone"))]
     pub phone: Option<String>,
+    pub member_type: Option<MemberType>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub membership_expires_at: Option<Option<NaiveDate>>,
+}
+
+// Library card used to sign in to the self-service portal, given both when
+// staff issue the card and when the member signs in with it
+#[derive(Serialize, Deserialize)]
+pub struct MemberCard {
+    pub card_number: String,
+    pub pin: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MemberSession {
+    pub token: String,
+    pub expires_at: NaiveDateTime,
+    pub member: Member,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -35,21 +151,17 @@ pub struct MemberSearchParams {
     pub last_name: Option<String>,
     pub email: Option<String>,
     pub phone: Option<String>,
+    // Active members unless asked otherwise
+    pub status: Option<MemberStatus>,
     pub order: Option<Order>, // Reusing BookOrder from your example
     pub order_by: Option<String>,
     pub limit: Option<u32>,
     pub page: Option<u32>,
+    // Pages with a keyset cursor instead, empty for the first page
+    pub cursor: Option<String>,
 }
 
 impl MemberSearchParams {
-    pub fn get_offset(&self) -> u32 {
-        if self.page.is_none() || self.page.unwrap() == 0 {
-            0
-        } else {
-            (self.page.unwrap() - 1) * self.limit.unwrap_or(10)
-        }
-    }
-
     pub fn get_order_by(&self) -> String {
         const ALLOWED_COLUMNS: [&str; 5] = ["id", "first_name", "last_name", "email", "phone"];
 
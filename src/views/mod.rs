@@ -8,9 +8,17 @@ use sqlx::{
     mysql::{MySqlTypeInfo, MySqlValueRef},
 };
 
+pub mod audit;
 pub mod books;
 pub mod borrowings;
+pub mod bulk;
+pub mod copies;
+pub mod fines;
+pub mod holds;
 pub mod members;
+pub mod page;
+pub mod reports;
+pub mod staff;
 
 #[derive(Serialize, Deserialize, Clone)]
 pub enum Order {
@@ -62,6 +70,19 @@ pub enum Status {
     Available,
     #[serde(rename = "borrowed", alias = "BORROWED", alias = "Borrowed")]
     Borrowed,
+    #[serde(rename = "overdue", alias = "OVERDUE", alias = "Overdue")]
+    Overdue,
+    #[serde(rename = "on_hold", alias = "ON_HOLD", alias = "OnHold")]
+    OnHold,
+    // A book taken out of circulation, see Status::is_withdrawn
+    #[serde(rename = "withdrawn", alias = "WITHDRAWN", alias = "Withdrawn")]
+    Withdrawn,
+    #[serde(rename = "lost", alias = "LOST", alias = "Lost")]
+    Lost,
+    #[serde(rename = "damaged", alias = "DAMAGED", alias = "Damaged")]
+    Damaged,
+    #[serde(rename = "in_repair", alias = "IN_REPAIR", alias = "InRepair")]
+    InRepair,
     #[serde(rename = "all", alias = "ALL", alias = "All")]
     All,
 }
@@ -71,6 +92,12 @@ impl Status {
         match self {
             Status::Available => Some("available"),
             Status::Borrowed => Some("borrowed"),
+            Status::Overdue => Some("overdue"),
+            Status::OnHold => Some("on_hold"),
+            Status::Withdrawn => Some("withdrawn"),
+            Status::Lost => Some("lost"),
+            Status::Damaged => Some("damaged"),
+            Status::InRepair => Some("in_repair"),
             Status::All => None,
         }
     }
@@ -79,6 +106,12 @@ impl Status {
         match self {
             Status::Available => "returned",
             Status::Borrowed => "borrowed",
+            Status::Overdue => "overdue",
+            Status::OnHold => "on_hold",
+            Status::Withdrawn => "withdrawn",
+            Status::Lost => "lost",
+            Status::Damaged => "damaged",
+            Status::InRepair => "in_repair",
             Status::All => "all",
         }
     }
@@ -87,9 +120,24 @@ impl Status {
         match self {
             Status::Available => "available",
             Status::Borrowed => "borrowed",
+            Status::Overdue => "overdue",
+            Status::OnHold => "on_hold",
+            Status::Withdrawn => "withdrawn",
+            Status::Lost => "lost",
+            Status::Damaged => "damaged",
+            Status::InRepair => "in_repair",
             Status::All => "all",
         }
     }
+
+    /// Whether this is one of the statuses of a book that has been taken out
+    /// of circulation
+    pub fn is_withdrawn(&self) -> bool {
+        matches!(
+            self,
+            Status::Withdrawn | Status::Lost | Status::Damaged | Status::InRepair
+        )
+    }
 }
 
 impl fmt::Display for Status {
@@ -106,6 +154,12 @@ impl std::str::FromStr for Status {
             "returned" => Ok(Status::Available),
             "available" => Ok(Status::Available),
             "borrowed" => Ok(Status::Borrowed),
+            "overdue" => Ok(Status::Overdue),
+            "on_hold" => Ok(Status::OnHold),
+            "withdrawn" => Ok(Status::Withdrawn),
+            "lost" => Ok(Status::Lost),
+            "damaged" => Ok(Status::Damaged),
+            "in_repair" => Ok(Status::InRepair),
             _ => Err(format!("Unknown status: {}", s)),
         }
     }
@@ -125,6 +179,12 @@ impl From<Status> for String {
         match status {
             Status::Available => "available".to_string(),
             Status::Borrowed => "borrowed".to_string(),
+            Status::Overdue => "overdue".to_string(),
+            Status::OnHold => "on_hold".to_string(),
+            Status::Withdrawn => "withdrawn".to_string(),
+            Status::Lost => "lost".to_string(),
+            Status::Damaged => "damaged".to_string(),
+            Status::InRepair => "in_repair".to_string(),
             Status::All => "all".to_string(),
         }
     }
@@ -0,0 +1,15 @@
+use serde::{Deserialize, Serialize};
+
+// One page of a list endpoint. `next` and `prev` are links to the
+// neighbouring pages with the same filters, or null at either end. `page` is
+// null when paging with a cursor.
+#[derive(Serialize, Deserialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    // Every row matching the filters, across all pages
+    pub total: i64,
+    pub page: Option<u32>,
+    pub limit: u32,
+    pub next: Option<String>,
+    pub prev: Option<String>,
+}
@@ -0,0 +1,61 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+// The period a report covers, both ends inclusive and either one optional.
+// `limit` only applies to the top books and members.
+#[derive(Serialize, Deserialize)]
+pub struct ReportParams {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    pub limit: Option<u32>,
+    pub format: Option<ReportFormat>,
+}
+
+// A book by how often it was lent, `borrowers` counts each member once
+#[derive(Serialize, Deserialize, FromRow)]
+pub struct TopBook {
+    pub book_id: i32,
+    pub title: String,
+    pub author: String,
+    pub category: Option<String>,
+    pub loans: i64,
+    pub borrowers: i64,
+}
+
+// A member by how many books they borrowed, `late_loans` counts the ones
+// returned late or still out past their due date
+#[derive(Serialize, Deserialize, FromRow)]
+pub struct TopMember {
+    pub member_id: i32,
+    pub first_name: String,
+    pub last_name: String,
+    pub loans: i64,
+    pub late_loans: i64,
+}
+
+// Loans of a category in a month, `month` is written as 2024-03. A null
+// category counts the books without one.
+#[derive(Serialize, Deserialize, FromRow)]
+pub struct CategoryLoans {
+    pub month: String,
+    pub category: Option<String>,
+    pub loans: i64,
+}
+
+// How long the loans returned in a month were out, in days
+#[derive(Serialize, Deserialize)]
+pub struct LoanLength {
+    pub month: String,
+    pub loans: i64,
+    pub average_days: f64,
+    pub longest_days: i64,
+}
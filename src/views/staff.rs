@@ -0,0 +1,75 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+
+/// Staff roles, ordered from least to most privileged
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    // Can look things up but not change anything
+    #[serde(rename = "read_only", alias = "READ_ONLY", alias = "ReadOnly")]
+    ReadOnly,
+    // Runs the desk: lending, returns, holds, fines and catalogue edits
+    #[serde(rename = "librarian", alias = "LIBRARIAN", alias = "Librarian")]
+    Librarian,
+    // Can also delete records and manage staff accounts
+    #[serde(rename = "admin", alias = "ADMIN", alias = "Admin")]
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Role::ReadOnly => "read_only",
+            Role::Librarian => "librarian",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+impl TryFrom<String> for Role {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        match s.as_str() {
+            "read_only" => Ok(Role::ReadOnly),
+            "librarian" => Ok(Role::Librarian),
+            "admin" => Ok(Role::Admin),
+            _ => Err(format!("Unknown role: {}", s)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, FromRow, Clone)]
+pub struct Staff {
+    pub id: i32,
+    pub username: String,
+    #[sqlx(try_from = "String")]
+    pub role: Role,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NewStaff {
+    pub username: String,
+    pub password: String,
+    pub role: Role,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct UpdateStaff {
+    pub password: Option<String>,
+    pub role: Option<Role>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct LoginRequest {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Session {
+    pub token: String,
+    pub expires_at: NaiveDateTime,
+    pub staff: Staff,
+}
@@ -0,0 +1,209 @@
+mod common;
+
+use actix_web::{
+    http::StatusCode,
+    middleware::from_fn,
+    test,
+    web::{scope, Data},
+    App,
+};
+use serde_json::{json, Value};
+
+use csci211_project::auth;
+use csci211_project::config::LibraryConfig;
+use csci211_project::routes::{
+    audit::audit_config, copies::copies_config, members::members_config,
+};
+use csci211_project::views::staff::Role;
+
+#[actix_web::test]
+async fn changes_to_a_member_can_be_traced_back() {
+    let Some(pool) = common::pool().await else {
+        return;
+    };
+
+    let (staff_id, token) = common::sign_in(&pool, Role::Admin, "correct horse").await;
+    let username: String = sqlx::query_scalar("SELECT username FROM STAFF WHERE id = ?")
+        .bind(staff_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(Data::new(pool.clone()))
+            .app_data(Data::new(LibraryConfig::default()))
+            .service(
+                scope("/api")
+                    .wrap(from_fn(auth::authenticate))
+                    .configure(members_config)
+                    .configure(audit_config),
+            ),
+    )
+    .await;
+
+    let email = format!("{}@example.com", common::unique("audit"));
+    let req = test::TestRequest::post()
+        .uri("/api/members/")
+        .insert_header(common::bearer(&token))
+        .set_json(json!({ "first_name": "Ada", "last_name": "Lovelace", "email": email }))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::CREATED
+    );
+
+    let member_id: i32 = sqlx::query_scalar("SELECT id FROM MEMBERS WHERE email = ?")
+        .bind(&email)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+
+    let new_email = format!("{}@example.com", common::unique("audit"));
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/members/{}", member_id))
+        .insert_header(common::bearer(&token))
+        .set_json(json!({ "email": new_email }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let history = |query: String| {
+        test::TestRequest::get()
+            .uri(&format!("/api/audit/?{}", query))
+            .insert_header(common::bearer(&token))
+            .to_request()
+    };
+
+    // The create and the update, oldest first, with who made them
+    let body: Value =
+        test::call_and_read_body_json(&app, history(format!("entity=member&id={}", member_id)))
+            .await;
+    assert_eq!(body["total"], 2);
+    let entries = body["items"].as_array().unwrap();
+    assert_eq!(entries[0]["action"], "create");
+    assert_eq!(entries[0]["before"], Value::Null);
+    assert_eq!(entries[0]["after"]["email"], email);
+    assert_eq!(entries[1]["action"], "update");
+    assert_eq!(entries[1]["staff_id"], staff_id);
+    assert_eq!(entries[1]["staff_username"], username);
+    assert_eq!(entries[1]["before"]["email"], email);
+    assert_eq!(entries[1]["after"]["email"], new_email);
+
+    // A record can only be picked out along with its kind
+    assert_eq!(
+        test::call_service(&app, history(format!("id={}", member_id)))
+            .await
+            .status(),
+        StatusCode::BAD_REQUEST
+    );
+
+    // Anonymising the member takes their details out of the log too
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/members/{}/anonymise", member_id))
+        .insert_header(common::bearer(&token))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::NO_CONTENT
+    );
+
+    let body: Value =
+        test::call_and_read_body_json(&app, history(format!("entity=member&id={}", member_id)))
+            .await;
+    assert_eq!(body["total"], 3);
+    for entry in body["items"].as_array().unwrap() {
+        for state in [&entry["before"], &entry["after"]] {
+            assert!(state.get("email").is_none());
+            assert!(state.get("first_name").is_none());
+        }
+    }
+    assert_ne!(body["items"][2]["after"]["anonymised_at"], Value::Null);
+
+    // The entries still name who made them once their account is gone
+    common::remove_staff(&pool, staff_id).await;
+    let authors: Vec<(Option<i32>, Option<String>)> = sqlx::query_as(
+        "SELECT staff_id, staff_username FROM AUDIT_LOG WHERE entity = 'member' AND entity_id = ?",
+    )
+    .bind(member_id)
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    assert_eq!(authors, vec![(None, Some(username)); 3]);
+
+    sqlx::query("DELETE FROM AUDIT_LOG WHERE entity = 'member' AND entity_id = ?")
+        .bind(member_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("DELETE FROM MEMBERS WHERE id = ?")
+        .bind(member_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+}
+
+#[actix_web::test]
+async fn changes_to_a_copy_are_logged() {
+    let Some(pool) = common::pool().await else {
+        return;
+    };
+
+    let book_id = common::insert_book(&pool, 1).await;
+    let copy_id = common::copy_ids(&pool, book_id).await[0];
+    let (staff_id, token) = common::sign_in(&pool, Role::Admin, "correct horse").await;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(Data::new(pool.clone()))
+            .app_data(Data::new(LibraryConfig::default()))
+            .service(
+                scope("/api")
+                    .wrap(from_fn(auth::authenticate))
+                    .configure(copies_config)
+                    .configure(audit_config),
+            ),
+    )
+    .await;
+
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/copies/{}", copy_id))
+        .insert_header(common::bearer(&token))
+        .set_json(json!({ "shelf_location": "A1" }))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::NO_CONTENT
+    );
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/copies/{}", copy_id))
+        .insert_header(common::bearer(&token))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::NO_CONTENT
+    );
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/audit/?entity=copy&id={}", copy_id))
+        .insert_header(common::bearer(&token))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["total"], 2);
+    let entries = body["items"].as_array().unwrap();
+    assert_eq!(entries[0]["action"], "update");
+    assert_eq!(entries[0]["staff_id"], staff_id);
+    assert_eq!(entries[0]["before"]["shelf_location"], Value::Null);
+    assert_eq!(entries[0]["after"]["shelf_location"], "A1");
+    assert_eq!(entries[1]["action"], "delete");
+    assert_eq!(entries[1]["before"]["shelf_location"], "A1");
+    assert_eq!(entries[1]["after"], Value::Null);
+
+    sqlx::query("DELETE FROM AUDIT_LOG WHERE entity = 'copy' AND entity_id = ?")
+        .bind(copy_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    common::cleanup(&pool, book_id, &[]).await;
+    common::remove_staff(&pool, staff_id).await;
+}
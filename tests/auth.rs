@@ -0,0 +1,182 @@
+mod common;
+
+use actix_web::{
+    http::StatusCode,
+    middleware::from_fn,
+    test,
+    web::{scope, Data},
+    App,
+};
+use serde_json::json;
+
+use csci211_project::auth;
+use csci211_project::config::LibraryConfig;
+use csci211_project::routes::{borrowings::borrowings_config, members::members_config, staff};
+use csci211_project::views::staff::Role;
+
+#[actix_web::test]
+async fn requests_without_a_session_are_unauthorized() {
+    let Some(pool) = common::pool().await else {
+        return;
+    };
+
+    let app = test::init_service(
+        App::new().app_data(Data::new(pool.clone())).service(
+            scope("/api")
+                .wrap(from_fn(auth::authenticate))
+                .configure(members_config),
+        ),
+    )
+    .await;
+
+    let req = test::TestRequest::get().uri("/api/members/").to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::UNAUTHORIZED
+    );
+
+    let req = test::TestRequest::get()
+        .uri("/api/members/")
+        .insert_header(common::bearer("not-a-real-token"))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::UNAUTHORIZED
+    );
+}
+
+#[actix_web::test]
+async fn login_issues_a_session() {
+    let Some(pool) = common::pool().await else {
+        return;
+    };
+
+    let (staff_id, _) = common::sign_in(&pool, Role::Librarian, "correct horse").await;
+    let username: String = sqlx::query_scalar("SELECT username FROM STAFF WHERE id = ?")
+        .bind(staff_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(Data::new(pool.clone()))
+            .app_data(Data::new(LibraryConfig::default()))
+            .service(
+                scope("/api")
+                    .wrap(from_fn(auth::authenticate))
+                    .configure(staff::auth_config),
+            ),
+    )
+    .await;
+
+    for (username, password) in [
+        (username.as_str(), "wrong"),
+        ("no-such-user", "correct horse"),
+    ] {
+        let req = test::TestRequest::post()
+            .uri("/api/auth/login")
+            .set_json(json!({ "username": username, "password": password }))
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, req).await.status(),
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    let req = test::TestRequest::post()
+        .uri("/api/auth/login")
+        .set_json(json!({ "username": username, "password": "correct horse" }))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let body: serde_json::Value = test::read_body_json(res).await;
+    let token = body["token"].as_str().unwrap();
+
+    let req = test::TestRequest::get()
+        .uri("/api/auth/session")
+        .insert_header(common::bearer(token))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let body: serde_json::Value = test::read_body_json(res).await;
+    assert_eq!(body["id"], staff_id);
+    assert_eq!(body["role"], "librarian");
+
+    common::remove_staff(&pool, staff_id).await;
+}
+
+#[actix_web::test]
+async fn roles_are_checked_per_route() {
+    let Some(pool) = common::pool().await else {
+        return;
+    };
+
+    let book_id = common::insert_book(&pool, 1).await;
+    let member_id = common::insert_member(&pool).await;
+    let (read_only_id, read_only) = common::sign_in(&pool, Role::ReadOnly, "password").await;
+    let (librarian_id, librarian) = common::sign_in(&pool, Role::Librarian, "password").await;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(Data::new(pool.clone()))
+            .app_data(Data::new(LibraryConfig::default()))
+            .service(
+                scope("/api")
+                    .wrap(from_fn(auth::authenticate))
+                    .configure(members_config)
+                    .configure(borrowings_config),
+            ),
+    )
+    .await;
+
+    // Read-only staff can look but not lend
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/members/{}", member_id))
+        .insert_header(common::bearer(&read_only))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/borrows/{}", member_id))
+        .insert_header(common::bearer(&read_only))
+        .set_json(json!({ "book_id": book_id }))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::FORBIDDEN
+    );
+
+    // Librarians can lend but only admins can delete members
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/borrows/{}", member_id))
+        .insert_header(common::bearer(&librarian))
+        .set_json(json!({ "book_id": book_id }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/members/{}", member_id))
+        .insert_header(common::bearer(&librarian))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::FORBIDDEN
+    );
+
+    common::cleanup(&pool, book_id, &[member_id]).await;
+    common::remove_staff(&pool, read_only_id).await;
+    common::remove_staff(&pool, librarian_id).await;
+}
+
+#[actix_web::test]
+async fn sign_ins_without_an_account_are_refused() {
+    let hash = auth::hash_password("correct horse").unwrap();
+
+    assert!(auth::verify_login("correct horse", Some(&hash)));
+    assert!(!auth::verify_login("wrong", Some(&hash)));
+    assert!(!auth::verify_login("correct horse", None));
+    assert!(!auth::verify_login("", None));
+}
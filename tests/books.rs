@@ -0,0 +1,601 @@
+mod common;
+
+use actix_web::{
+    http::StatusCode,
+    middleware::from_fn,
+    test,
+    web::{scope, Data},
+    App,
+};
+use serde_json::{json, Value};
+
+use csci211_project::auth;
+use csci211_project::routes::books::books_config;
+use csci211_project::views::staff::Role;
+
+#[actix_web::test]
+async fn books_can_be_found_by_either_isbn() {
+    let Some(pool) = common::pool().await else {
+        return;
+    };
+
+    let (staff_id, token) = common::sign_in(&pool, Role::Librarian, "correct horse").await;
+
+    // Clear out a book left behind by an earlier failed run
+    sqlx::query("DELETE FROM BOOKS WHERE isbn_13 = '9780306406157'")
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let app = test::init_service(
+        App::new().app_data(Data::new(pool.clone())).service(
+            scope("/api")
+                .wrap(from_fn(auth::authenticate))
+                .configure(books_config),
+        ),
+    )
+    .await;
+
+    let book = json!({
+        "title": common::unique("book"),
+        "author": "Test Author",
+        "isbn": "0-306-40615-2",
+    });
+
+    let req = test::TestRequest::post()
+        .uri("/api/books/")
+        .insert_header(common::bearer(&token))
+        .set_json(&book)
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::CREATED
+    );
+
+    let req = test::TestRequest::get()
+        .uri("/api/books/isbn/978-0-306-40615-7")
+        .insert_header(common::bearer(&token))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let found: Value = test::read_body_json(res).await;
+    assert_eq!(found["isbn_10"], "0306406152");
+    assert_eq!(found["isbn_13"], "9780306406157");
+
+    let req = test::TestRequest::get()
+        .uri("/api/books/?isbn=0306406152")
+        .insert_header(common::bearer(&token))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    let results = body["items"].as_array().unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0]["id"], found["id"]);
+
+    // The same ISBN can't be added twice
+    let req = test::TestRequest::post()
+        .uri("/api/books/")
+        .insert_header(common::bearer(&token))
+        .set_json(&book)
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::CONFLICT
+    );
+
+    let req = test::TestRequest::get()
+        .uri("/api/books/isbn/0306406153")
+        .insert_header(common::bearer(&token))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::UNPROCESSABLE_ENTITY
+    );
+
+    sqlx::query("DELETE FROM BOOKS WHERE id = ?")
+        .bind(found["id"].as_i64())
+        .execute(&pool)
+        .await
+        .unwrap();
+    common::remove_staff(&pool, staff_id).await;
+}
+
+#[actix_web::test]
+async fn books_can_be_searched_across_title_and_author() {
+    let Some(pool) = common::pool().await else {
+        return;
+    };
+
+    let (staff_id, token) = common::sign_in(&pool, Role::ReadOnly, "correct horse").await;
+
+    // FULLTEXT only indexes letters and digits, so the tag can't have hyphens
+    let tag = common::unique("search").replace('-', "x");
+
+    let mut ids = vec![];
+    for (title, author) in [
+        (format!("The Great Gatsby {}", tag), "F. Scott Fitzgerald"),
+        (format!("Gatsby Revisited {}", tag), "Some Critic"),
+        (
+            format!("Tender Is the Night {}", tag),
+            "F. Scott Fitzgerald",
+        ),
+    ] {
+        let result = sqlx::query("INSERT INTO BOOKS (title, author) VALUES (?, ?)")
+            .bind(title)
+            .bind(author)
+            .execute(&pool)
+            .await
+            .unwrap();
+        ids.push(result.last_insert_id() as i64);
+    }
+
+    let app = test::init_service(
+        App::new().app_data(Data::new(pool.clone())).service(
+            scope("/api")
+                .wrap(from_fn(auth::authenticate))
+                .configure(books_config),
+        ),
+    )
+    .await;
+
+    let search = |q: String| {
+        test::TestRequest::get()
+            .uri(&format!("/api/books/?q={}", q))
+            .insert_header(common::bearer(&token))
+            .to_request()
+    };
+
+    // Every word has to match, in any of the columns
+    let body: Value =
+        test::call_and_read_body_json(&app, search(format!("gatsby+fitzgerald+{}", tag))).await;
+    let results = body["items"].as_array().unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0]["id"], ids[0]);
+    assert!(results[0]["score"].is_number());
+
+    let body: Value =
+        test::call_and_read_body_json(&app, search(format!("%22great+gatsby%22+{}", tag))).await;
+    let results = body["items"].as_array().unwrap();
+    assert_eq!(results.len(), 1);
+
+    let body: Value = test::call_and_read_body_json(&app, search(format!("fitz*+{}", tag))).await;
+    let results = body["items"].as_array().unwrap();
+    assert_eq!(results.len(), 2);
+
+    // Most relevant first
+    let body: Value = test::call_and_read_body_json(&app, search(tag.clone())).await;
+    let results = body["items"].as_array().unwrap();
+    assert_eq!(results.len(), 3);
+    let scores: Vec<f64> = results
+        .iter()
+        .map(|book| book["score"].as_f64().unwrap())
+        .collect();
+    assert!(scores.windows(2).all(|pair| pair[0] >= pair[1]));
+
+    // Without a search there is no score
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/books/?title={}", tag))
+        .insert_header(common::bearer(&token))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    let results = body["items"].as_array().unwrap();
+    assert_eq!(results.len(), 3);
+    assert!(results[0].get("score").is_none());
+
+    // A typo finds nothing, but suggests the right spelling
+    let body: Value =
+        test::call_and_read_body_json(&app, search(format!("fitzgerold+{}", tag))).await;
+    assert_eq!(body["items"].as_array().unwrap().len(), 0);
+    assert_eq!(body["suggestions"][0], format!("Fitzgerald {}", tag));
+
+    // and is matched anyway in fuzzy mode
+    let body: Value =
+        test::call_and_read_body_json(&app, search(format!("fitzgerold+{}&fuzzy=true", tag))).await;
+    assert_eq!(body["items"].as_array().unwrap().len(), 2);
+
+    for id in ids {
+        sqlx::query("DELETE FROM BOOKS WHERE id = ?")
+            .bind(id)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+    common::remove_staff(&pool, staff_id).await;
+}
+
+#[actix_web::test]
+async fn facets_count_the_filtered_books() {
+    let Some(pool) = common::pool().await else {
+        return;
+    };
+
+    let (staff_id, token) = common::sign_in(&pool, Role::ReadOnly, "correct horse").await;
+
+    let tag = common::unique("facets");
+
+    let mut ids = vec![];
+    for (category, year) in [
+        (Some("Poetry"), Some(1994)),
+        (Some("Poetry"), Some(1999)),
+        (Some("Drama"), Some(2003)),
+        (None, None),
+    ] {
+        let result = sqlx::query(
+            "INSERT INTO BOOKS (title, author, category, publication_year) VALUES (?, ?, ?, ?)",
+        )
+        .bind(format!("{} {}", tag, ids.len()))
+        .bind("Test Author")
+        .bind(category)
+        .bind(year)
+        .execute(&pool)
+        .await
+        .unwrap();
+        ids.push(result.last_insert_id() as i64);
+    }
+
+    // Only the first book has a copy on the shelf
+    sqlx::query("INSERT INTO COPIES (book_id) VALUES (?)")
+        .bind(ids[0])
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let app = test::init_service(
+        App::new().app_data(Data::new(pool.clone())).service(
+            scope("/api")
+                .wrap(from_fn(auth::authenticate))
+                .configure(books_config),
+        ),
+    )
+    .await;
+
+    // Facets cover every matching book, not just the page
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/books/?title={}&facets=true&limit=1", tag))
+        .insert_header(common::bearer(&token))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["items"].as_array().unwrap().len(), 1);
+
+    let facets = &body["facets"];
+    assert_eq!(
+        facets["category"],
+        json!([
+            { "value": "Poetry", "count": 2 },
+            { "value": null, "count": 1 },
+            { "value": "Drama", "count": 1 },
+        ])
+    );
+    assert_eq!(
+        facets["status"],
+        json!([
+            { "value": "borrowed", "count": 3 },
+            { "value": "available", "count": 1 },
+        ])
+    );
+    assert_eq!(
+        facets["author"],
+        json!([{ "value": "Test Author", "count": 4 }])
+    );
+    assert_eq!(
+        facets["decade"],
+        json!([
+            { "value": 1990, "count": 2 },
+            { "value": null, "count": 1 },
+            { "value": 2000, "count": 1 },
+        ])
+    );
+
+    // and follow the filters
+    let req = test::TestRequest::get()
+        .uri(&format!(
+            "/api/books/?title={}&category=Poetry&facets=true",
+            tag
+        ))
+        .insert_header(common::bearer(&token))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(
+        body["facets"]["category"],
+        json!([{ "value": "Poetry", "count": 2 }])
+    );
+
+    // Without asking there are no facets
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/books/?title={}", tag))
+        .insert_header(common::bearer(&token))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body.get("facets").is_none());
+
+    for id in ids {
+        sqlx::query("DELETE FROM BOOKS WHERE id = ?")
+            .bind(id)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+    common::remove_staff(&pool, staff_id).await;
+}
+
+#[actix_web::test]
+async fn pages_link_to_each_other_and_cursors_walk_every_book() {
+    let Some(pool) = common::pool().await else {
+        return;
+    };
+
+    let (staff_id, token) = common::sign_in(&pool, Role::ReadOnly, "correct horse").await;
+
+    let tag = common::unique("pages");
+
+    let mut ids = vec![];
+    for i in 0..5 {
+        let result = sqlx::query("INSERT INTO BOOKS (title, author) VALUES (?, ?)")
+            .bind(format!("{} {}", tag, i))
+            .bind("Test Author")
+            .execute(&pool)
+            .await
+            .unwrap();
+        ids.push(result.last_insert_id() as i64);
+    }
+
+    let app = test::init_service(
+        App::new().app_data(Data::new(pool.clone())).service(
+            scope("/api")
+                .wrap(from_fn(auth::authenticate))
+                .configure(books_config),
+        ),
+    )
+    .await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/books/?title={}&limit=2&page=2", tag))
+        .insert_header(common::bearer(&token))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["total"], 5);
+    assert_eq!(body["page"], 2);
+    assert_eq!(body["limit"], 2);
+    assert_eq!(body["items"].as_array().unwrap().len(), 2);
+    assert_eq!(
+        body["next"],
+        format!("/api/books/?title={}&limit=2&page=3", tag)
+    );
+    assert_eq!(
+        body["prev"],
+        format!("/api/books/?title={}&limit=2&page=1", tag)
+    );
+
+    // Following the cursors visits every book once, in id order
+    let mut seen = vec![];
+    let mut uri = format!("/api/books/?title={}&limit=2&cursor=", tag);
+    loop {
+        let req = test::TestRequest::get()
+            .uri(&uri)
+            .insert_header(common::bearer(&token))
+            .to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["total"], 5);
+        assert_eq!(body["page"], Value::Null);
+        for book in body["items"].as_array().unwrap() {
+            seen.push(book["id"].as_i64().unwrap());
+        }
+
+        match body["next"].as_str() {
+            Some(next) => uri = next.to_string(),
+            None => break,
+        }
+    }
+    assert_eq!(seen, ids);
+
+    // and going back from the last page gives the one before it
+    let req = test::TestRequest::get()
+        .uri(&format!(
+            "/api/books/?title={}&limit=2&cursor=before-{}",
+            tag, ids[4]
+        ))
+        .insert_header(common::bearer(&token))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    let items: Vec<i64> = body["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|book| book["id"].as_i64().unwrap())
+        .collect();
+    assert_eq!(items, ids[2..4]);
+
+    let req = test::TestRequest::get()
+        .uri("/api/books/?cursor=sideways")
+        .insert_header(common::bearer(&token))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::BAD_REQUEST
+    );
+
+    // Oversized pages are cut down, and pages too far out to count refused
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/books/?title={}&limit=100000", tag))
+        .insert_header(common::bearer(&token))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["limit"], 100);
+    assert_eq!(body["items"].as_array().unwrap().len(), 5);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/books/?page={}&limit=100", u32::MAX))
+        .insert_header(common::bearer(&token))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::BAD_REQUEST
+    );
+
+    for id in ids {
+        sqlx::query("DELETE FROM BOOKS WHERE id = ?")
+            .bind(id)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+    common::remove_staff(&pool, staff_id).await;
+}
+
+#[actix_web::test]
+async fn books_with_a_history_are_withdrawn_rather_than_deleted() {
+    let Some(pool) = common::pool().await else {
+        return;
+    };
+
+    let (staff_id, token) = common::sign_in(&pool, Role::Admin, "correct horse").await;
+
+    let book_id = common::insert_book(&pool, 1).await;
+    let member_id = common::insert_member(&pool).await;
+    let copy_id = common::copy_ids(&pool, book_id).await[0];
+
+    let borrowing_id = sqlx::query(
+        r#"
+        INSERT INTO BORROWINGS (copy_id, member_id, borrow_date, due_date)
+        VALUES (?, ?, CURRENT_DATE, DATE_ADD(CURRENT_DATE, INTERVAL 14 DAY))
+        "#,
+    )
+    .bind(copy_id)
+    .bind(member_id)
+    .execute(&pool)
+    .await
+    .unwrap()
+    .last_insert_id();
+    sqlx::query("UPDATE COPIES SET status = 'borrowed' WHERE id = ?")
+        .bind(copy_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let app = test::init_service(
+        App::new().app_data(Data::new(pool.clone())).service(
+            scope("/api")
+                .wrap(from_fn(auth::authenticate))
+                .configure(books_config),
+        ),
+    )
+    .await;
+
+    let withdraw = |status: &str| {
+        test::TestRequest::post()
+            .uri(&format!("/api/books/{}/withdraw", book_id))
+            .insert_header(common::bearer(&token))
+            .set_json(json!({ "status": status, "reason": "Not seen since stocktake" }))
+            .to_request()
+    };
+
+    // A title can be changed but not blanked
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/books/{}", book_id))
+        .insert_header(common::bearer(&token))
+        .set_json(json!({ "title": " ", "author": "" }))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body: Value = test::read_body_json(res).await;
+    assert!(body["details"]["title"].is_array());
+    assert!(body["details"]["author"].is_array());
+
+    // Only the withdrawn statuses are accepted
+    assert_eq!(
+        test::call_service(&app, withdraw("available"))
+            .await
+            .status(),
+        StatusCode::UNPROCESSABLE_ENTITY
+    );
+
+    // and not while a copy is out
+    assert_eq!(
+        test::call_service(&app, withdraw("lost")).await.status(),
+        StatusCode::CONFLICT
+    );
+
+    sqlx::query("UPDATE BORROWINGS SET return_date = CURRENT_DATE WHERE id = ?")
+        .bind(borrowing_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("UPDATE COPIES SET status = 'available' WHERE id = ?")
+        .bind(copy_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        test::call_service(&app, withdraw("lost")).await.status(),
+        StatusCode::NO_CONTENT
+    );
+
+    // The book drops out of the catalog unless asked for
+    let title: String = sqlx::query_scalar("SELECT title FROM BOOKS WHERE id = ?")
+        .bind(book_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    let list = |status: &str| {
+        test::TestRequest::get()
+            .uri(&format!("/api/books/?title={}{}", title, status))
+            .insert_header(common::bearer(&token))
+            .to_request()
+    };
+
+    let body: Value = test::call_and_read_body_json(&app, list("")).await;
+    assert_eq!(body["total"], 0);
+
+    let body: Value = test::call_and_read_body_json(&app, list("&status=lost")).await;
+    assert_eq!(body["total"], 1);
+    assert_eq!(body["items"][0]["status"], "lost");
+    assert_eq!(
+        body["items"][0]["withdrawn_reason"],
+        "Not seen since stocktake"
+    );
+
+    let body: Value = test::call_and_read_body_json(&app, list("&status=all")).await;
+    assert_eq!(body["total"], 1);
+
+    // Its history keeps it from being deleted
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/books/{}", book_id))
+        .insert_header(common::bearer(&token))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::CONFLICT
+    );
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/books/{}/reinstate", book_id))
+        .insert_header(common::bearer(&token))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::NO_CONTENT
+    );
+
+    let body: Value = test::call_and_read_body_json(&app, list("")).await;
+    assert_eq!(body["items"][0]["status"], "available");
+
+    // A book that was never lent can be deleted, once
+    let unused_id = common::insert_book(&pool, 1).await;
+    let delete = || {
+        test::TestRequest::delete()
+            .uri(&format!("/api/books/{}", unused_id))
+            .insert_header(common::bearer(&token))
+            .to_request()
+    };
+    assert_eq!(
+        test::call_service(&app, delete()).await.status(),
+        StatusCode::NO_CONTENT
+    );
+    assert_eq!(
+        test::call_service(&app, delete()).await.status(),
+        StatusCode::NOT_FOUND
+    );
+
+    common::cleanup(&pool, book_id, &[member_id]).await;
+    common::remove_staff(&pool, staff_id).await;
+}
@@ -0,0 +1,981 @@
+mod common;
+
+use std::collections::HashMap;
+
+use actix_web::{
+    http::StatusCode,
+    middleware::from_fn,
+    test,
+    web::{scope, Data},
+    App,
+};
+use futures::future::join_all;
+use serde_json::{json, Value};
+
+use csci211_project::auth;
+use csci211_project::config::LibraryConfig;
+use csci211_project::routes::borrowings::borrowings_config;
+use csci211_project::views::staff::Role;
+
+const CONCURRENT_CHECKOUTS: usize = 16;
+
+async fn open_borrowings(pool: &sqlx::MySqlPool, book_id: i32) -> Vec<i32> {
+    sqlx::query_scalar(
+        r#"
+        SELECT br.copy_id FROM BORROWINGS br
+        JOIN COPIES c ON c.id = br.copy_id
+        WHERE c.book_id = ? AND br.return_date IS NULL
+        "#,
+    )
+    .bind(book_id)
+    .fetch_all(pool)
+    .await
+    .expect("Failed to fetch borrowings")
+}
+
+async fn copy_statuses(pool: &sqlx::MySqlPool, book_id: i32) -> Vec<String> {
+    sqlx::query_scalar("SELECT status FROM COPIES WHERE book_id = ? ORDER BY id")
+        .bind(book_id)
+        .fetch_all(pool)
+        .await
+        .expect("Failed to fetch copy statuses")
+}
+
+async fn fine_balance(pool: &sqlx::MySqlPool, fine_id: i32) -> i64 {
+    sqlx::query_scalar("SELECT balance_cents FROM FINE_BALANCES WHERE id = ?")
+        .bind(fine_id)
+        .fetch_one(pool)
+        .await
+        .expect("Failed to fetch fine balance")
+}
+
+/// The amount and balance of a borrowing's late fee, if one was charged
+async fn late_fine(pool: &sqlx::MySqlPool, borrowing_id: u64) -> Option<(i64, i64)> {
+    sqlx::query_as(
+        r#"
+        SELECT amount_cents, balance_cents FROM FINE_BALANCES
+        WHERE borrowing_id = ? AND kind = 'late'
+        "#,
+    )
+    .bind(borrowing_id)
+    .fetch_optional(pool)
+    .await
+    .expect("Failed to fetch late fine")
+}
+
+/// Has one member per request try to borrow the book at the same time and
+/// returns the response statuses
+async fn checkout_concurrently(
+    pool: &sqlx::MySqlPool,
+    book_id: i32,
+) -> (Vec<StatusCode>, Vec<i32>) {
+    let (staff_id, token) = common::sign_in(pool, Role::Librarian, "password").await;
+
+    let mut member_ids = Vec::new();
+    for _ in 0..CONCURRENT_CHECKOUTS {
+        member_ids.push(common::insert_member(pool).await);
+    }
+
+    let app = test::init_service(
+        App::new()
+            .app_data(Data::new(pool.clone()))
+            .app_data(Data::new(LibraryConfig::default()))
+            .service(
+                scope("/api")
+                    .wrap(from_fn(auth::authenticate))
+                    .configure(borrowings_config),
+            ),
+    )
+    .await;
+
+    let requests = member_ids.iter().map(|member_id| {
+        let req = test::TestRequest::post()
+            .uri(&format!("/api/borrows/{}", member_id))
+            .insert_header(common::bearer(&token))
+            .set_json(json!({ "book_id": book_id }))
+            .to_request();
+        test::call_service(&app, req)
+    });
+
+    let statuses = join_all(requests)
+        .await
+        .iter()
+        .map(|res| res.status())
+        .collect();
+
+    common::remove_staff(pool, staff_id).await;
+
+    (statuses, member_ids)
+}
+
+#[actix_web::test]
+async fn concurrent_checkouts_lend_a_copy_only_once() {
+    let Some(pool) = common::pool().await else {
+        return;
+    };
+
+    let book_id = common::insert_book(&pool, 1).await;
+    let (statuses, member_ids) = checkout_concurrently(&pool, book_id).await;
+
+    let lent = statuses.iter().filter(|s| **s == StatusCode::OK).count();
+    let refused = statuses
+        .iter()
+        .filter(|s| **s == StatusCode::CONFLICT)
+        .count();
+
+    assert_eq!(
+        lent, 1,
+        "exactly one checkout should succeed: {:?}",
+        statuses
+    );
+    assert_eq!(refused, CONCURRENT_CHECKOUTS - 1);
+    assert_eq!(open_borrowings(&pool, book_id).await.len(), 1);
+    assert_eq!(copy_statuses(&pool, book_id).await, vec!["borrowed"]);
+
+    common::cleanup(&pool, book_id, &member_ids).await;
+}
+
+#[actix_web::test]
+async fn concurrent_checkouts_lend_each_copy_once() {
+    let Some(pool) = common::pool().await else {
+        return;
+    };
+
+    let book_id = common::insert_book(&pool, 3).await;
+    let (statuses, member_ids) = checkout_concurrently(&pool, book_id).await;
+
+    let lent = statuses.iter().filter(|s| **s == StatusCode::OK).count();
+
+    assert_eq!(
+        lent, 3,
+        "one checkout per copy should succeed: {:?}",
+        statuses
+    );
+
+    let mut lent_copies = open_borrowings(&pool, book_id).await;
+    lent_copies.sort();
+    assert_eq!(lent_copies, common::copy_ids(&pool, book_id).await);
+    assert!(copy_statuses(&pool, book_id)
+        .await
+        .iter()
+        .all(|status| status == "borrowed"));
+
+    common::cleanup(&pool, book_id, &member_ids).await;
+}
+
+#[actix_web::test]
+async fn concurrent_returns_close_the_loan_only_once() {
+    let Some(pool) = common::pool().await else {
+        return;
+    };
+
+    let book_id = common::insert_book(&pool, 1).await;
+    let copy_id = common::copy_ids(&pool, book_id).await[0];
+    let member_id = common::insert_member(&pool).await;
+    let (staff_id, token) = common::sign_in(&pool, Role::Librarian, "password").await;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(Data::new(pool.clone()))
+            .app_data(Data::new(LibraryConfig::default()))
+            .service(
+                scope("/api")
+                    .wrap(from_fn(auth::authenticate))
+                    .configure(borrowings_config),
+            ),
+    )
+    .await;
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/borrows/{}", member_id))
+        .insert_header(common::bearer(&token))
+        .set_json(json!({ "copy_id": copy_id }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let requests = (0..CONCURRENT_CHECKOUTS).map(|_| {
+        let req = test::TestRequest::post()
+            .uri("/api/borrows/return/")
+            .insert_header(common::bearer(&token))
+            .set_json(json!({ "copy_id": copy_id }))
+            .to_request();
+        test::call_service(&app, req)
+    });
+
+    let statuses: Vec<StatusCode> = join_all(requests)
+        .await
+        .iter()
+        .map(|res| res.status())
+        .collect();
+
+    let returned = statuses.iter().filter(|s| **s == StatusCode::OK).count();
+
+    assert_eq!(
+        returned, 1,
+        "exactly one return should succeed: {:?}",
+        statuses
+    );
+    assert!(open_borrowings(&pool, book_id).await.is_empty());
+    assert_eq!(copy_statuses(&pool, book_id).await, vec!["available"]);
+
+    common::cleanup(&pool, book_id, &[member_id]).await;
+    common::remove_staff(&pool, staff_id).await;
+}
+
+#[actix_web::test]
+async fn checkout_of_unknown_book_is_not_found() {
+    let Some(pool) = common::pool().await else {
+        return;
+    };
+
+    let member_id = common::insert_member(&pool).await;
+    let (staff_id, token) = common::sign_in(&pool, Role::Librarian, "password").await;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(Data::new(pool.clone()))
+            .app_data(Data::new(LibraryConfig::default()))
+            .service(
+                scope("/api")
+                    .wrap(from_fn(auth::authenticate))
+                    .configure(borrowings_config),
+            ),
+    )
+    .await;
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/borrows/{}", member_id))
+        .insert_header(common::bearer(&token))
+        .set_json(json!({ "book_id": i32::MAX }))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+    common::cleanup(&pool, i32::MAX, &[member_id]).await;
+    common::remove_staff(&pool, staff_id).await;
+}
+
+#[actix_web::test]
+async fn borrowings_can_be_filtered_by_book_and_dates() {
+    let Some(pool) = common::pool().await else {
+        return;
+    };
+
+    let book_id = common::insert_book(&pool, 2).await;
+    let copy_ids = common::copy_ids(&pool, book_id).await;
+    let member_id = common::insert_member(&pool).await;
+    let (staff_id, token) = common::sign_in(&pool, Role::ReadOnly, "password").await;
+
+    // Borrowed in March and returned six days late, then borrowed in May and
+    // never returned
+    let mut borrowing_ids = vec![];
+    for (copy_id, borrow_date, due_date, return_date) in [
+        (copy_ids[0], "2024-03-05", "2024-03-19", Some("2024-03-25")),
+        (copy_ids[1], "2024-05-01", "2024-05-15", None),
+    ] {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO BORROWINGS (copy_id, member_id, borrow_date, due_date, return_date)
+            VALUES (?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(copy_id)
+        .bind(member_id)
+        .bind(borrow_date)
+        .bind(due_date)
+        .bind(return_date)
+        .execute(&pool)
+        .await
+        .unwrap();
+        borrowing_ids.push(result.last_insert_id() as i64);
+    }
+
+    let app = test::init_service(
+        App::new()
+            .app_data(Data::new(pool.clone()))
+            .app_data(Data::new(LibraryConfig::default()))
+            .service(
+                scope("/api")
+                    .wrap(from_fn(auth::authenticate))
+                    .configure(borrowings_config),
+            ),
+    )
+    .await;
+
+    let list = |query: String| {
+        let req = test::TestRequest::get()
+            .uri(&format!("/api/borrows/?book_id={}&{}", book_id, query))
+            .insert_header(common::bearer(&token))
+            .to_request();
+        test::call_and_read_body_json::<_, _, Value>(&app, req)
+    };
+    let ids = |body: &Value| -> Vec<i64> {
+        body["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|borrowed| borrowed["borrowing_id"].as_i64().unwrap())
+            .collect()
+    };
+
+    // Who borrowed the book in March
+    let body = list("from_date=2024-03-01&to_date=2024-03-31".to_string()).await;
+    assert_eq!(ids(&body), borrowing_ids[..1]);
+    assert_eq!(body["items"][0]["borrower_id"], member_id);
+    assert_eq!(body["total"], 1);
+
+    let body = list("overdue=true".to_string()).await;
+    assert_eq!(ids(&body), borrowing_ids[1..]);
+
+    let body = list("due_from=2024-05-01".to_string()).await;
+    assert_eq!(ids(&body), borrowing_ids[1..]);
+
+    // Returning late still counts towards the days overdue
+    let body = list("min_overdue_days=5&order_by=due_date&order=desc".to_string()).await;
+    assert_eq!(ids(&body), vec![borrowing_ids[1], borrowing_ids[0]]);
+
+    let body = list(format!("member_id={}&status=returned", member_id)).await;
+    assert_eq!(ids(&body), borrowing_ids[..1]);
+
+    common::cleanup(&pool, book_id, &[member_id]).await;
+    common::remove_staff(&pool, staff_id).await;
+}
+
+#[actix_web::test]
+async fn admins_can_correct_and_void_borrowings() {
+    let Some(pool) = common::pool().await else {
+        return;
+    };
+
+    let book_id = common::insert_book(&pool, 1).await;
+    let copy_id = common::copy_ids(&pool, book_id).await[0];
+    let member_id = common::insert_member(&pool).await;
+    let other_member_id = common::insert_member(&pool).await;
+    let (staff_id, token) = common::sign_in(&pool, Role::Admin, "password").await;
+
+    let borrowing_id = sqlx::query(
+        r#"
+        INSERT INTO BORROWINGS (copy_id, member_id, borrow_date, due_date)
+        VALUES (?, ?, '2024-01-01', '2024-01-15')
+        "#,
+    )
+    .bind(copy_id)
+    .bind(member_id)
+    .execute(&pool)
+    .await
+    .unwrap()
+    .last_insert_id();
+    sqlx::query("UPDATE COPIES SET status = 'borrowed' WHERE id = ?")
+        .bind(copy_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(Data::new(pool.clone()))
+            .app_data(Data::new(LibraryConfig::default()))
+            .service(
+                scope("/api")
+                    .wrap(from_fn(auth::authenticate))
+                    .configure(borrowings_config),
+            ),
+    )
+    .await;
+
+    let fine_id = sqlx::query(
+        "INSERT INTO FINES (member_id, borrowing_id, kind, amount_cents) VALUES (?, ?, 'damaged', 500)",
+    )
+    .bind(member_id)
+    .bind(borrowing_id)
+    .execute(&pool)
+    .await
+    .unwrap()
+    .last_insert_id();
+
+    // The loan can't go to a member who is gone or has left
+    let left_member_id = common::insert_member(&pool).await;
+    sqlx::query("UPDATE MEMBERS SET deactivated_at = NOW() WHERE id = ?")
+        .bind(left_member_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    for (to, status) in [
+        (-1, StatusCode::NOT_FOUND),
+        (left_member_id, StatusCode::CONFLICT),
+    ] {
+        let req = test::TestRequest::put()
+            .uri(&format!("/api/borrows/loans/{}", borrowing_id))
+            .insert_header(common::bearer(&token))
+            .set_json(json!({ "member_id": to }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), status);
+    }
+
+    // Reassigning the loan keeps the old member in the corrections, and its
+    // fines go to the new member
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/borrows/loans/{}", borrowing_id))
+        .insert_header(common::bearer(&token))
+        .set_json(json!({ "member_id": other_member_id, "reason": "Wrong card scanned" }))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["member_id"], other_member_id);
+    let correction = &body["corrections"][0];
+    assert_eq!(correction["action"], "edit");
+    assert_eq!(correction["staff_id"], staff_id);
+    assert_eq!(correction["reason"], "Wrong card scanned");
+    assert_eq!(
+        correction["changes"],
+        json!({ "member_id": { "old": member_id, "new": other_member_id } })
+    );
+    let fined: i32 = sqlx::query_scalar("SELECT member_id FROM FINES WHERE id = ?")
+        .bind(fine_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(fined, other_member_id);
+
+    // Recording the return puts the copy back on the shelf, clearing it
+    // lends the copy out again
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/borrows/loans/{}", borrowing_id))
+        .insert_header(common::bearer(&token))
+        .set_json(json!({ "return_date": "2024-01-10" }))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["return_date"], "2024-01-10");
+    assert_eq!(copy_statuses(&pool, book_id).await, vec!["available"]);
+
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/borrows/loans/{}", borrowing_id))
+        .insert_header(common::bearer(&token))
+        .set_json(json!({ "return_date": null }))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["return_date"], Value::Null);
+    assert_eq!(body["corrections"].as_array().unwrap().len(), 3);
+    assert_eq!(copy_statuses(&pool, book_id).await, vec!["borrowed"]);
+
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/borrows/loans/{}", borrowing_id))
+        .insert_header(common::bearer(&token))
+        .set_json(json!({ "due_date": "2023-12-31" }))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::BAD_REQUEST
+    );
+
+    assert_eq!(fine_balance(&pool, fine_id as i32).await, 500);
+
+    // Voiding frees the copy and hides the loan, but keeps the row
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/borrows/loans/{}/void", borrowing_id))
+        .insert_header(common::bearer(&token))
+        .set_json(json!({ "reason": "Entered twice" }))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["void_reason"], "Entered twice");
+    assert!(body["voided_at"].is_string());
+    assert_eq!(copy_statuses(&pool, book_id).await, vec!["available"]);
+    let kept: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM BORROWINGS WHERE id = ? AND voided_at IS NOT NULL",
+    )
+    .bind(borrowing_id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(kept, 1);
+
+    // and nothing is owed on a loan that shouldn't have existed
+    assert_eq!(fine_balance(&pool, fine_id as i32).await, 0);
+    let waiver: String = sqlx::query_scalar(
+        "SELECT reason FROM FINE_PAYMENTS WHERE fine_id = ? AND kind = 'waiver'",
+    )
+    .bind(fine_id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(waiver, "Entered twice");
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/borrows/?book_id={}", book_id))
+        .insert_header(common::bearer(&token))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["total"], 0);
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/borrows/loans/{}/void", borrowing_id))
+        .insert_header(common::bearer(&token))
+        .set_json(json!({ "reason": "Entered twice" }))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::CONFLICT
+    );
+
+    common::cleanup(
+        &pool,
+        book_id,
+        &[member_id, other_member_id, left_member_id],
+    )
+    .await;
+    common::remove_staff(&pool, staff_id).await;
+}
+
+#[actix_web::test]
+async fn corrected_dates_change_the_late_fee() {
+    let Some(pool) = common::pool().await else {
+        return;
+    };
+
+    let book_id = common::insert_book(&pool, 1).await;
+    let copy_id = common::copy_ids(&pool, book_id).await[0];
+    let member_id = common::insert_member(&pool).await;
+    let (staff_id, token) = common::sign_in(&pool, Role::Admin, "password").await;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(Data::new(pool.clone()))
+            .app_data(Data::new(LibraryConfig::default()))
+            .service(
+                scope("/api")
+                    .wrap(from_fn(auth::authenticate))
+                    .configure(borrowings_config),
+            ),
+    )
+    .await;
+
+    let borrowing_id = sqlx::query(
+        r#"
+        INSERT INTO BORROWINGS (copy_id, member_id, borrow_date, due_date)
+        VALUES (?, ?, '2024-01-01', '2024-01-15')
+        "#,
+    )
+    .bind(copy_id)
+    .bind(member_id)
+    .execute(&pool)
+    .await
+    .unwrap()
+    .last_insert_id();
+
+    let correct = |body: Value| {
+        test::TestRequest::put()
+            .uri(&format!("/api/borrows/loans/{}", borrowing_id))
+            .insert_header(common::bearer(&token))
+            .set_json(body)
+            .to_request()
+    };
+
+    // Recording a late return charges the fee a return would
+    let res = test::call_service(&app, correct(json!({ "return_date": "2024-01-19" }))).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(late_fine(&pool, borrowing_id).await, Some((100, 100)));
+
+    let res = test::call_service(&app, correct(json!({ "return_date": "2024-01-23" }))).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(late_fine(&pool, borrowing_id).await, Some((200, 200)));
+
+    // A later due date lowers the fee, but not below what was paid
+    sqlx::query(
+        r#"
+        INSERT INTO FINE_PAYMENTS (fine_id, kind, amount_cents)
+        SELECT id, 'payment', 50 FROM FINES WHERE borrowing_id = ?
+        "#,
+    )
+    .bind(borrowing_id)
+    .execute(&pool)
+    .await
+    .unwrap();
+    let res = test::call_service(&app, correct(json!({ "due_date": "2024-01-21" }))).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(late_fine(&pool, borrowing_id).await, Some((50, 0)));
+
+    let res = test::call_service(&app, correct(json!({ "due_date": "2024-01-10" }))).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(late_fine(&pool, borrowing_id).await, Some((325, 275)));
+
+    // A reopened loan owes nothing more until it comes back
+    let res = test::call_service(&app, correct(json!({ "return_date": null }))).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(late_fine(&pool, borrowing_id).await, Some((50, 0)));
+    assert_eq!(copy_statuses(&pool, book_id).await, vec!["borrowed"]);
+
+    let res = test::call_service(&app, correct(json!({ "return_date": "2024-01-12" }))).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(late_fine(&pool, borrowing_id).await, Some((50, 0)));
+
+    // The return date is checked against the database's date
+    let res = test::call_service(&app, correct(json!({ "return_date": "9999-12-31" }))).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    sqlx::query("DELETE FROM FINES WHERE borrowing_id = ?")
+        .bind(borrowing_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    common::cleanup(&pool, book_id, &[member_id]).await;
+    common::remove_staff(&pool, staff_id).await;
+}
+
+#[actix_web::test]
+async fn checkouts_follow_the_borrowing_rules_unless_an_admin_overrides_them() {
+    let Some(pool) = common::pool().await else {
+        return;
+    };
+
+    let book_id = common::insert_book(&pool, 2).await;
+    let member_id = common::insert_member(&pool).await;
+    let (librarian_id, librarian) = common::sign_in(&pool, Role::Librarian, "password").await;
+    let (admin_id, admin) = common::sign_in(&pool, Role::Admin, "password").await;
+
+    sqlx::query("UPDATE MEMBERS SET membership_expires_at = '2000-01-01' WHERE id = ?")
+        .bind(member_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let config = LibraryConfig {
+        default_max_loans: 1,
+        ..LibraryConfig::default()
+    };
+    let app = test::init_service(
+        App::new()
+            .app_data(Data::new(pool.clone()))
+            .app_data(Data::new(config))
+            .service(
+                scope("/api")
+                    .wrap(from_fn(auth::authenticate))
+                    .configure(borrowings_config),
+            ),
+    )
+    .await;
+
+    let borrow = |token: &str, body: Value| {
+        test::TestRequest::post()
+            .uri(&format!("/api/borrows/{}", member_id))
+            .insert_header(common::bearer(token))
+            .set_json(body)
+            .to_request()
+    };
+
+    // Every broken rule is named in the details
+    let res = test::call_service(&app, borrow(&librarian, json!({ "book_id": book_id }))).await;
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body: Value = test::read_body_json(res).await;
+    let rules: Vec<&String> = body["details"].as_object().unwrap().keys().collect();
+    assert_eq!(rules, vec!["membership_expired"]);
+
+    // Only an admin can override them, and the override is kept on the loan
+    let res = test::call_service(
+        &app,
+        borrow(&librarian, json!({ "book_id": book_id, "override": true })),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+    let res = test::call_service(
+        &app,
+        borrow(&admin, json!({ "book_id": book_id, "override": true })),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let (override_staff_id, overridden_rules): (Option<i32>, Option<Value>) = sqlx::query_as(
+        r#"
+        SELECT br.override_staff_id, br.overridden_rules FROM BORROWINGS br
+        JOIN COPIES c ON c.id = br.copy_id
+        WHERE c.book_id = ?
+        "#,
+    )
+    .bind(book_id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(override_staff_id, Some(admin_id));
+    assert_eq!(overridden_rules, Some(json!(["membership_expired"])));
+
+    // Renewed to run until the database's today, the member is now held back
+    // by the limit of one loan
+    sqlx::query("UPDATE MEMBERS SET membership_expires_at = CURRENT_DATE WHERE id = ?")
+        .bind(member_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let res = test::call_service(&app, borrow(&librarian, json!({ "book_id": book_id }))).await;
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body: Value = test::read_body_json(res).await;
+    assert!(body["details"]["loan_limit"].is_array());
+    assert!(body["details"].get("membership_expired").is_none());
+
+    common::cleanup(&pool, book_id, &[member_id]).await;
+    common::remove_staff(&pool, librarian_id).await;
+    common::remove_staff(&pool, admin_id).await;
+}
+
+#[actix_web::test]
+async fn loans_are_renewed_a_limited_number_of_times() {
+    let Some(pool) = common::pool().await else {
+        return;
+    };
+
+    let book_id = common::insert_book(&pool, 1).await;
+    let copy_id = common::copy_ids(&pool, book_id).await[0];
+    let member_id = common::insert_member(&pool).await;
+    let other_member_id = common::insert_member(&pool).await;
+    let (staff_id, token) = common::sign_in(&pool, Role::Librarian, "password").await;
+
+    // Five days overdue
+    let borrowing_id = sqlx::query(
+        r#"
+        INSERT INTO BORROWINGS (copy_id, member_id, borrow_date, due_date)
+        VALUES (?, ?, CURRENT_DATE - INTERVAL 19 DAY, CURRENT_DATE - INTERVAL 5 DAY)
+        "#,
+    )
+    .bind(copy_id)
+    .bind(member_id)
+    .execute(&pool)
+    .await
+    .unwrap()
+    .last_insert_id();
+    sqlx::query("UPDATE COPIES SET status = 'borrowed' WHERE id = ?")
+        .bind(copy_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(Data::new(pool.clone()))
+            .app_data(Data::new(LibraryConfig {
+                default_loan_days: 14,
+                max_renewals: 2,
+                ..LibraryConfig::default()
+            }))
+            .service(
+                scope("/api")
+                    .wrap(from_fn(auth::authenticate))
+                    .configure(borrowings_config),
+            ),
+    )
+    .await;
+
+    let renew = || {
+        test::TestRequest::post()
+            .uri(&format!("/api/borrows/{}/renew", borrowing_id))
+            .insert_header(common::bearer(&token))
+            .to_request()
+    };
+    let days_from_today = |days: i32| {
+        sqlx::query_scalar::<_, String>(
+            "SELECT DATE_FORMAT(CURRENT_DATE + INTERVAL ? DAY, '%Y-%m-%d')",
+        )
+        .bind(days)
+        .fetch_one(&pool)
+    };
+
+    // The overdue loan is extended from today, not from its old due date
+    let renewed: Value = test::call_and_read_body_json(&app, renew()).await;
+    assert_eq!(renewed["due_date"], days_from_today(14).await.unwrap());
+    assert_eq!(renewed["renewal_count"], 1);
+    assert_eq!(
+        renewed["renewals"][0]["previous_due_date"],
+        days_from_today(-5).await.unwrap()
+    );
+
+    // and a loan that isn't due yet from its due date
+    let renewed: Value = test::call_and_read_body_json(&app, renew()).await;
+    assert_eq!(renewed["due_date"], days_from_today(28).await.unwrap());
+    assert_eq!(renewed["renewals"].as_array().unwrap().len(), 2);
+
+    let res = test::call_service(&app, renew()).await;
+    assert_eq!(res.status(), StatusCode::CONFLICT);
+
+    // With the limit out of the way, a member waiting for the book still
+    // stops the renewal
+    sqlx::query("UPDATE BORROWINGS SET renewal_count = 0 WHERE id = ?")
+        .bind(borrowing_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO HOLDS (book_id, member_id, position) VALUES (?, ?, 1)")
+        .bind(book_id)
+        .bind(other_member_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let res = test::call_service(&app, renew()).await;
+    assert_eq!(res.status(), StatusCode::CONFLICT);
+    let body: Value = test::read_body_json(res).await;
+    assert_eq!(body["message"], "Another member is waiting for this book");
+
+    common::cleanup(&pool, book_id, &[member_id, other_member_id]).await;
+    common::remove_staff(&pool, staff_id).await;
+}
+
+#[actix_web::test]
+async fn due_dates_follow_the_loan_periods() {
+    let Some(pool) = common::pool().await else {
+        return;
+    };
+
+    // insert_book files books under "Test", which gets a week here
+    let short_book = common::insert_book(&pool, 1).await;
+    let other_book = common::insert_book(&pool, 2).await;
+    sqlx::query("UPDATE BOOKS SET category = NULL WHERE id = ?")
+        .bind(other_book)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let member_id = common::insert_member(&pool).await;
+    let (staff_id, token) = common::sign_in(&pool, Role::Librarian, "password").await;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(Data::new(pool.clone()))
+            .app_data(Data::new(LibraryConfig {
+                default_loan_days: 21,
+                category_loan_days: HashMap::from([("test".to_string(), 7)]),
+                ..LibraryConfig::default()
+            }))
+            .service(
+                scope("/api")
+                    .wrap(from_fn(auth::authenticate))
+                    .configure(borrowings_config),
+            ),
+    )
+    .await;
+
+    let borrow = |body: Value| {
+        test::TestRequest::post()
+            .uri(&format!("/api/borrows/{}", member_id))
+            .insert_header(common::bearer(&token))
+            .set_json(body)
+            .to_request()
+    };
+
+    for loan_days in [0, 366] {
+        let res = test::call_service(
+            &app,
+            borrow(json!({ "book_id": other_book, "loan_days": loan_days })),
+        )
+        .await;
+        assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body: Value = test::read_body_json(res).await;
+        assert!(body["details"]["loan_days"].is_array());
+    }
+
+    // Each checkout answers with the borrowing it made
+    let mut borrowing_ids = vec![];
+    for body in [
+        json!({ "book_id": short_book }),
+        json!({ "book_id": other_book }),
+        json!({ "book_id": other_book, "loan_days": 3 }),
+    ] {
+        let res = test::call_service(&app, borrow(body)).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let borrowing: Value = test::read_body_json(res).await;
+        assert_eq!(borrowing["member_id"], member_id);
+        assert_eq!(borrowing["renewals"], json!([]));
+        borrowing_ids.push(borrowing["id"].as_i64().unwrap());
+    }
+
+    let periods: Vec<(i64, i32, i64)> = sqlx::query_as(
+        r#"
+        SELECT br.id, c.book_id, DATEDIFF(br.due_date, br.borrow_date) FROM BORROWINGS br
+        JOIN COPIES c ON c.id = br.copy_id
+        WHERE br.member_id = ?
+        ORDER BY br.id
+        "#,
+    )
+    .bind(member_id)
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    assert_eq!(
+        periods,
+        vec![
+            (borrowing_ids[0], short_book, 7),
+            (borrowing_ids[1], other_book, 21),
+            (borrowing_ids[2], other_book, 3)
+        ]
+    );
+
+    common::cleanup(&pool, short_book, &[member_id]).await;
+    common::cleanup(&pool, other_book, &[]).await;
+    common::remove_staff(&pool, staff_id).await;
+}
+
+#[actix_web::test]
+async fn overdue_loans_are_listed_most_overdue_first() {
+    let Some(pool) = common::pool().await else {
+        return;
+    };
+
+    let book_id = common::insert_book(&pool, 3).await;
+    let copy_ids = common::copy_ids(&pool, book_id).await;
+    let member_id = common::insert_member(&pool).await;
+    let (staff_id, token) = common::sign_in(&pool, Role::ReadOnly, "password").await;
+
+    // Due three days ago, due ten days ago and due tomorrow
+    let mut borrowing_ids = vec![];
+    for (copy_id, due_in) in copy_ids.iter().zip([-3, -10, 1]) {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO BORROWINGS (copy_id, member_id, borrow_date, due_date)
+            VALUES (?, ?, CURRENT_DATE - INTERVAL 20 DAY, CURRENT_DATE + INTERVAL ? DAY)
+            "#,
+        )
+        .bind(copy_id)
+        .bind(member_id)
+        .bind(due_in)
+        .execute(&pool)
+        .await
+        .unwrap();
+        borrowing_ids.push(result.last_insert_id() as i64);
+    }
+
+    let app = test::init_service(
+        App::new()
+            .app_data(Data::new(pool.clone()))
+            .app_data(Data::new(LibraryConfig::default()))
+            .service(
+                scope("/api")
+                    .wrap(from_fn(auth::authenticate))
+                    .configure(borrowings_config),
+            ),
+    )
+    .await;
+
+    // Other tests' loans may be overdue too, so only ours are kept
+    let mut overdue = vec![];
+    let mut uri = "/api/borrows/overdue?limit=100".to_string();
+    loop {
+        let req = test::TestRequest::get()
+            .uri(&uri)
+            .insert_header(common::bearer(&token))
+            .to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        for loan in body["items"].as_array().unwrap() {
+            let borrowing_id = loan["borrowing_id"].as_i64().unwrap();
+            if borrowing_ids.contains(&borrowing_id) {
+                assert_eq!(loan["status"], "overdue");
+                overdue.push((borrowing_id, loan["overdue_days"].as_i64().unwrap()));
+            }
+        }
+
+        match body["next"].as_str() {
+            Some(next) => uri = next.to_string(),
+            None => break,
+        }
+    }
+    assert_eq!(overdue, vec![(borrowing_ids[1], 10), (borrowing_ids[0], 3)]);
+
+    common::cleanup(&pool, book_id, &[member_id]).await;
+    common::remove_staff(&pool, staff_id).await;
+}
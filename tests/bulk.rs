@@ -0,0 +1,211 @@
+mod common;
+
+use actix_web::{
+    http::StatusCode,
+    middleware::from_fn,
+    test,
+    web::{scope, Data},
+    App,
+};
+use serde_json::Value;
+
+use csci211_project::auth;
+use csci211_project::routes::books::books_config;
+use csci211_project::routes::members::members_config;
+use csci211_project::views::staff::Role;
+
+#[actix_web::test]
+async fn members_are_imported_all_or_nothing_and_exported() {
+    let Some(pool) = common::pool().await else {
+        return;
+    };
+
+    let (staff_id, token) = common::sign_in(&pool, Role::Librarian, "correct horse").await;
+
+    let app = test::init_service(
+        App::new().app_data(Data::new(pool.clone())).service(
+            scope("/api")
+                .wrap(from_fn(auth::authenticate))
+                .configure(members_config),
+        ),
+    )
+    .await;
+
+    let tag = common::unique("import");
+    let first = format!("{}-a@example.com", tag);
+    let second = format!("{}-b@example.com", tag);
+    let csv = format!(
+        "first_name,last_name,email,phone\nAda,Lovelace,{},(415) 555-2671\nAlan,Turing,{},\n",
+        first, second
+    );
+
+    // A dry run reports what would happen without saving it
+    let req = test::TestRequest::post()
+        .uri("/api/members/import?dry_run=true")
+        .insert_header(common::bearer(&token))
+        .insert_header(("Content-Type", "text/csv"))
+        .set_payload(csv.clone())
+        .to_request();
+    let report: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(report["dry_run"], true);
+    assert_eq!(report["created"], 2);
+
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM MEMBERS WHERE email LIKE ?")
+        .bind(format!("{}%", tag))
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(count, 0);
+
+    // One bad row stops the whole file from being imported
+    let bad = format!("{}Grace,,not-an-email,\n", csv);
+    let req = test::TestRequest::post()
+        .uri("/api/members/import")
+        .insert_header(common::bearer(&token))
+        .insert_header(("Content-Type", "text/csv"))
+        .set_payload(bad)
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body: Value = test::read_body_json(res).await;
+    assert_eq!(body["details"]["errors"][0]["line"], 4);
+
+    let req = test::TestRequest::post()
+        .uri("/api/members/import")
+        .insert_header(common::bearer(&token))
+        .insert_header(("Content-Type", "text/csv"))
+        .set_payload(csv)
+        .to_request();
+    let report: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(report["created"], 2);
+    assert_eq!(report["updated"], 0);
+
+    // Importing an email that is already on file updates that member
+    let update = format!(
+        "first_name,last_name,email,phone\nAugusta,King,{},\n",
+        first
+    );
+    let req = test::TestRequest::post()
+        .uri("/api/members/import")
+        .insert_header(common::bearer(&token))
+        .insert_header(("Content-Type", "text/csv"))
+        .set_payload(update)
+        .to_request();
+    let report: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(report["created"], 0);
+    assert_eq!(report["updated"], 1);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/members/export?email={}", tag))
+        .insert_header(common::bearer(&token))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body = String::from_utf8(test::read_body(res).await.to_vec()).unwrap();
+    let lines: Vec<&str> = body.lines().collect();
+    assert_eq!(
+        lines[0],
+        "id,first_name,last_name,email,phone,card_number,member_type,membership_expires_at,deactivated_at,anonymised_at"
+    );
+    assert_eq!(lines.len(), 3);
+    // The phone number kept from the first import, normalised
+    assert!(lines[1].contains(&format!("Augusta,King,{},+14155552671", first)));
+
+    sqlx::query("DELETE FROM MEMBERS WHERE email LIKE ?")
+        .bind(format!("{}%", tag))
+        .execute(&pool)
+        .await
+        .unwrap();
+    common::remove_staff(&pool, staff_id).await;
+}
+
+#[actix_web::test]
+async fn exported_books_can_be_imported_again() {
+    let Some(pool) = common::pool().await else {
+        return;
+    };
+
+    // Left over from an earlier run that stopped halfway
+    sqlx::query("DELETE FROM BOOKS WHERE isbn_13 = '9780804429573'")
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let (staff_id, token) = common::sign_in(&pool, Role::Librarian, "correct horse").await;
+    let tag = common::unique("round-trip");
+
+    let book_id = sqlx::query(
+        r#"
+        INSERT INTO BOOKS
+            (title, author, category, isbn_10, isbn_13, publisher, publication_year)
+        VALUES (?, 'Test Author', 'Test', '080442957X', '9780804429573', 'Test Press', 1999)
+        "#,
+    )
+    .bind(&tag)
+    .execute(&pool)
+    .await
+    .unwrap()
+    .last_insert_id() as i32;
+    for _ in 0..2 {
+        sqlx::query("INSERT INTO COPIES (book_id) VALUES (?)")
+            .bind(book_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    let app = test::init_service(
+        App::new().app_data(Data::new(pool.clone())).service(
+            scope("/api")
+                .wrap(from_fn(auth::authenticate))
+                .configure(books_config),
+        ),
+    )
+    .await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/books/export?title={}", tag))
+        .insert_header(common::bearer(&token))
+        .to_request();
+    let export = test::call_and_read_body(&app, req).await;
+
+    common::cleanup(&pool, book_id, &[]).await;
+
+    let import = |csv: Vec<u8>| {
+        test::TestRequest::post()
+            .uri("/api/books/import")
+            .insert_header(common::bearer(&token))
+            .insert_header(("Content-Type", "text/csv"))
+            .set_payload(csv)
+            .to_request()
+    };
+
+    let report: Value = test::call_and_read_body_json(&app, import(export.to_vec())).await;
+    assert_eq!(report["created"], 1);
+
+    let (id, isbn_10, isbn_13, publisher, year): (i32, String, String, String, i16) =
+        sqlx::query_as(
+            r#"
+            SELECT id, isbn_10, isbn_13, publisher, publication_year
+            FROM BOOKS
+            WHERE title = ?
+            "#,
+        )
+        .bind(&tag)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(isbn_10, "080442957X");
+    assert_eq!(isbn_13, "9780804429573");
+    assert_eq!(publisher, "Test Press");
+    assert_eq!(year, 1999);
+    assert_eq!(common::copy_ids(&pool, id).await.len(), 2);
+
+    // The same file again finds the book by its ISBN and updates it
+    let report: Value = test::call_and_read_body_json(&app, import(export.to_vec())).await;
+    assert_eq!(report["created"], 0);
+    assert_eq!(report["updated"], 1);
+
+    common::cleanup(&pool, id, &[]).await;
+    common::remove_staff(&pool, staff_id).await;
+}
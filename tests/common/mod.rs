@@ -0,0 +1,157 @@
+#![allow(dead_code)]
+
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use sqlx::MySqlPool;
+
+use csci211_project::auth;
+use csci211_project::migrations;
+use csci211_project::views::staff::Role;
+
+static COUNTER: AtomicU32 = AtomicU32::new(0);
+
+/// Connects to the database in `DATABASE_URL` and brings its schema up to
+/// date, or returns `None` so the calling test can bail out when no database
+/// is available.
+pub async fn pool() -> Option<MySqlPool> {
+    dotenv::dotenv().ok();
+
+    let database_url = match std::env::var("DATABASE_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            eprintln!("DATABASE_URL is not set, skipping database test");
+            return None;
+        }
+    };
+
+    let pool = MySqlPool::connect(&database_url)
+        .await
+        .expect("Failed to connect to DATABASE_URL");
+
+    migrations::run(&pool)
+        .await
+        .expect("Failed to apply database migrations");
+
+    Some(pool)
+}
+
+/// A string that is unique for this test run, used to keep test rows apart.
+pub fn unique(prefix: &str) -> String {
+    format!(
+        "{}-{}-{}",
+        prefix,
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::SeqCst)
+    )
+}
+
+/// Inserts a book with the given number of copies, returning the book id
+pub async fn insert_book(pool: &MySqlPool, copies: usize) -> i32 {
+    let result = sqlx::query("INSERT INTO BOOKS (title, author, category) VALUES (?, ?, ?)")
+        .bind(unique("book"))
+        .bind("Test Author")
+        .bind("Test")
+        .execute(pool)
+        .await
+        .expect("Failed to insert book");
+
+    let book_id = result.last_insert_id() as i32;
+
+    for _ in 0..copies {
+        sqlx::query("INSERT INTO COPIES (book_id, barcode) VALUES (?, ?)")
+            .bind(book_id)
+            .bind(unique("copy"))
+            .execute(pool)
+            .await
+            .expect("Failed to insert copy");
+    }
+
+    book_id
+}
+
+pub async fn copy_ids(pool: &MySqlPool, book_id: i32) -> Vec<i32> {
+    sqlx::query_scalar("SELECT id FROM COPIES WHERE book_id = ? ORDER BY id")
+        .bind(book_id)
+        .fetch_all(pool)
+        .await
+        .expect("Failed to fetch copies")
+}
+
+pub async fn insert_member(pool: &MySqlPool) -> i32 {
+    let result = sqlx::query(
+        "INSERT INTO MEMBERS (first_name, last_name, email, phone) VALUES (?, ?, ?, ?)",
+    )
+    .bind("Test")
+    .bind("Member")
+    .bind(format!("{}@example.com", unique("member")))
+    .bind(None::<String>)
+    .execute(pool)
+    .await
+    .expect("Failed to insert member");
+
+    result.last_insert_id() as i32
+}
+
+pub async fn cleanup(pool: &MySqlPool, book_id: i32, member_ids: &[i32]) {
+    sqlx::query("DELETE FROM BOOKS WHERE id = ?")
+        .bind(book_id)
+        .execute(pool)
+        .await
+        .ok();
+
+    for member_id in member_ids {
+        // Fines outlive their member, so they are cleared first
+        sqlx::query("DELETE FROM FINES WHERE member_id = ?")
+            .bind(member_id)
+            .execute(pool)
+            .await
+            .ok();
+
+        sqlx::query("DELETE FROM MEMBERS WHERE id = ?")
+            .bind(member_id)
+            .execute(pool)
+            .await
+            .ok();
+    }
+}
+
+/// Creates a staff account with the given role and password and signs it in,
+/// returning the account id and session token
+pub async fn sign_in(pool: &MySqlPool, role: Role, password: &str) -> (i32, String) {
+    let result = sqlx::query("INSERT INTO STAFF (username, password_hash, role) VALUES (?, ?, ?)")
+        .bind(unique("staff"))
+        .bind(auth::hash_password(password).expect("Failed to hash password"))
+        .bind(role.as_str())
+        .execute(pool)
+        .await
+        .expect("Failed to insert staff");
+
+    let staff_id = result.last_insert_id() as i32;
+
+    let (token, _) = auth::create_session(pool, staff_id, 1)
+        .await
+        .expect("Failed to create session");
+
+    (staff_id, token)
+}
+
+pub fn bearer(token: &str) -> (&'static str, String) {
+    ("Authorization", format!("Bearer {}", token))
+}
+
+pub async fn remove_staff(pool: &MySqlPool, staff_id: i32) {
+    sqlx::query("DELETE FROM STAFF WHERE id = ?")
+        .bind(staff_id)
+        .execute(pool)
+        .await
+        .ok();
+}
+
+/// Signs a member in to the self-service portal, returning the session token
+pub async fn member_sign_in(pool: &MySqlPool, member_id: i32) -> String {
+    let (token, _) = auth::create_member_session(pool, member_id, 1)
+        .await
+        .expect("Failed to create member session");
+
+    token
+}
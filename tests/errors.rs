@@ -0,0 +1,151 @@
+mod common;
+
+use actix_web::{
+    http::StatusCode,
+    middleware::from_fn,
+    test,
+    web::{self, scope, Data},
+    App,
+};
+use serde_json::{json, Value};
+
+use csci211_project::auth;
+use csci211_project::error;
+use csci211_project::routes::members::members_config;
+use csci211_project::views::staff::Role;
+
+#[actix_web::test]
+async fn errors_share_one_json_shape() {
+    let Some(pool) = common::pool().await else {
+        return;
+    };
+
+    let (staff_id, token) = common::sign_in(&pool, Role::Librarian, "correct horse").await;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(Data::new(pool.clone()))
+            .app_data(web::JsonConfig::default().error_handler(error::json_error))
+            .app_data(web::PathConfig::default().error_handler(error::path_error))
+            .service(
+                scope("/api")
+                    .wrap(from_fn(auth::authenticate))
+                    .configure(members_config),
+            ),
+    )
+    .await;
+
+    // No session at all
+    let req = test::TestRequest::get().uri("/api/members/").to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    let body: Value = test::read_body_json(res).await;
+    assert_eq!(body["code"], "unauthorized");
+    assert!(body["message"].is_string());
+
+    // A member that doesn't exist
+    let req = test::TestRequest::get()
+        .uri("/api/members/0")
+        .insert_header(common::bearer(&token))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    let body: Value = test::read_body_json(res).await;
+    assert_eq!(body["code"], "not_found");
+    assert_eq!(body["message"], "Member not found");
+
+    // A body that isn't valid JSON
+    let req = test::TestRequest::post()
+        .uri("/api/members/")
+        .insert_header(common::bearer(&token))
+        .insert_header(("Content-Type", "application/json"))
+        .set_payload("{not json")
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let body: Value = test::read_body_json(res).await;
+    assert_eq!(body["code"], "bad_request");
+
+    common::remove_staff(&pool, staff_id).await;
+}
+
+#[actix_web::test]
+async fn invalid_members_are_rejected_field_by_field() {
+    let Some(pool) = common::pool().await else {
+        return;
+    };
+
+    let (staff_id, token) = common::sign_in(&pool, Role::Librarian, "correct horse").await;
+
+    let app = test::init_service(
+        App::new().app_data(Data::new(pool.clone())).service(
+            scope("/api")
+                .wrap(from_fn(auth::authenticate))
+                .configure(members_config),
+        ),
+    )
+    .await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/members/")
+        .insert_header(common::bearer(&token))
+        .set_json(json!({
+            "first_name": "   ",
+            "last_name": "Member",
+            "email": "not-an-email",
+            "phone": "12",
+        }))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body: Value = test::read_body_json(res).await;
+    assert_eq!(body["code"], "unprocessable_entity");
+    assert!(body["details"]["first_name"].is_array());
+    assert!(body["details"]["email"].is_array());
+    assert!(body["details"]["phone"].is_array());
+    assert!(body["details"]["last_name"].is_null());
+
+    // Valid input is trimmed and the phone number stored in E.164 form
+    let email = format!("{}@example.com", common::unique("member"));
+    let req = test::TestRequest::post()
+        .uri("/api/members/")
+        .insert_header(common::bearer(&token))
+        .set_json(json!({
+            "first_name": "  Test ",
+            "last_name": "Member",
+            "email": format!(" {} ", email),
+            "phone": "(415) 555-2671",
+        }))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::CREATED
+    );
+
+    let (member_id, first_name, phone): (i32, String, Option<String>) =
+        sqlx::query_as("SELECT id, first_name, phone FROM MEMBERS WHERE email = ?")
+            .bind(&email)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(first_name, "Test");
+    assert_eq!(phone.as_deref(), Some("+14155552671"));
+
+    // A name can be changed but not blanked
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/members/{}", member_id))
+        .insert_header(common::bearer(&token))
+        .set_json(json!({ "first_name": " " }))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body: Value = test::read_body_json(res).await;
+    assert!(body["details"]["first_name"].is_array());
+
+    sqlx::query("DELETE FROM MEMBERS WHERE id = ?")
+        .bind(member_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    common::remove_staff(&pool, staff_id).await;
+}
@@ -0,0 +1,261 @@
+mod common;
+
+use actix_web::{
+    http::StatusCode,
+    middleware::from_fn,
+    test,
+    web::{scope, Data},
+    App,
+};
+use serde_json::{json, Value};
+
+use csci211_project::auth;
+use csci211_project::config::LibraryConfig;
+use csci211_project::routes::{
+    borrowings::borrowings_config, fines::fines_config, members::members_config,
+};
+use csci211_project::views::staff::Role;
+
+/// Two free days, then 25 cents a day up to two dollars
+fn config() -> LibraryConfig {
+    LibraryConfig {
+        fine_grace_days: 2,
+        fine_daily_rate_cents: 25,
+        fine_cap_cents: 200,
+        ..LibraryConfig::default()
+    }
+}
+
+#[actix_web::test]
+async fn late_fines_start_after_the_grace_days_and_stop_at_the_cap() {
+    let config = config();
+
+    assert_eq!(config.late_fine_cents(-3), 0);
+    assert_eq!(config.late_fine_cents(2), 0);
+    assert_eq!(config.late_fine_cents(3), 25);
+    assert_eq!(config.late_fine_cents(6), 100);
+    assert_eq!(config.late_fine_cents(10), 200);
+    assert_eq!(config.late_fine_cents(365), 200);
+}
+
+#[actix_web::test]
+async fn late_returns_are_fined() {
+    let Some(pool) = common::pool().await else {
+        return;
+    };
+
+    let book_id = common::insert_book(&pool, 3).await;
+    let copy_ids = common::copy_ids(&pool, book_id).await;
+    let member_id = common::insert_member(&pool).await;
+    let (staff_id, token) = common::sign_in(&pool, Role::Librarian, "password").await;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(Data::new(pool.clone()))
+            .app_data(Data::new(config()))
+            .service(
+                scope("/api")
+                    .wrap(from_fn(auth::authenticate))
+                    .configure(borrowings_config),
+            ),
+    )
+    .await;
+
+    // Returned within the grace days, three days past them and long enough
+    // to hit the cap
+    let mut borrowing_ids = vec![];
+    for (copy_id, days_late) in copy_ids.iter().zip([2, 5, 30]) {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO BORROWINGS (copy_id, member_id, borrow_date, due_date)
+            VALUES (?, ?, CURRENT_DATE - INTERVAL 40 DAY, CURRENT_DATE - INTERVAL ? DAY)
+            "#,
+        )
+        .bind(copy_id)
+        .bind(member_id)
+        .bind(days_late)
+        .execute(&pool)
+        .await
+        .unwrap();
+        borrowing_ids.push(result.last_insert_id() as i32);
+
+        sqlx::query("UPDATE COPIES SET status = 'borrowed' WHERE id = ?")
+            .bind(copy_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let req = test::TestRequest::post()
+            .uri("/api/borrows/return/")
+            .insert_header(common::bearer(&token))
+            .set_json(json!({ "copy_id": copy_id }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    }
+
+    let mut fined = vec![];
+    for borrowing_id in &borrowing_ids {
+        let fines: Vec<(String, i64)> =
+            sqlx::query_as("SELECT kind, amount_cents FROM FINES WHERE borrowing_id = ?")
+                .bind(borrowing_id)
+                .fetch_all(&pool)
+                .await
+                .unwrap();
+        fined.push(fines);
+    }
+    assert_eq!(
+        fined,
+        vec![
+            vec![],
+            vec![("late".to_string(), 75)],
+            vec![("late".to_string(), 200)],
+        ]
+    );
+
+    common::cleanup(&pool, book_id, &[member_id]).await;
+    common::remove_staff(&pool, staff_id).await;
+}
+
+#[actix_web::test]
+async fn fines_are_paid_off_and_waived() {
+    let Some(pool) = common::pool().await else {
+        return;
+    };
+
+    let member_id = common::insert_member(&pool).await;
+    let (staff_id, token) = common::sign_in(&pool, Role::Librarian, "password").await;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(Data::new(pool.clone()))
+            .app_data(Data::new(LibraryConfig::default()))
+            .service(
+                scope("/api")
+                    .wrap(from_fn(auth::authenticate))
+                    .configure(fines_config)
+                    .configure(members_config),
+            ),
+    )
+    .await;
+
+    let post = |uri: String, body: Value| {
+        test::TestRequest::post()
+            .uri(&uri)
+            .insert_header(common::bearer(&token))
+            .set_json(body)
+            .to_request()
+    };
+
+    let charge = |member_id: i32, kind: &str| {
+        post(
+            "/api/fines/".to_string(),
+            json!({ "member_id": member_id, "kind": kind, "amount_cents": 1000 }),
+        )
+    };
+
+    assert_eq!(
+        test::call_service(&app, charge(-1, "lost")).await.status(),
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(
+        test::call_service(&app, charge(member_id, "late"))
+            .await
+            .status(),
+        StatusCode::BAD_REQUEST
+    );
+    assert_eq!(
+        test::call_service(&app, charge(member_id, "lost"))
+            .await
+            .status(),
+        StatusCode::CREATED
+    );
+
+    let fine_id: i32 = sqlx::query_scalar("SELECT id FROM FINES WHERE member_id = ?")
+        .bind(member_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    let payments = format!("/api/fines/{}/payments", fine_id);
+    let waivers = format!("/api/fines/{}/waivers", fine_id);
+
+    let body: Value =
+        test::call_and_read_body_json(&app, post(payments.clone(), json!({ "amount_cents": 300 })))
+            .await;
+    assert_eq!(body["balance_cents"], 700);
+
+    // Paying more than is owed is refused and changes nothing
+    let res =
+        test::call_service(&app, post(payments.clone(), json!({ "amount_cents": 701 }))).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    // Empty amounts and blank reasons fail validation
+    let res = test::call_service(&app, post(payments.clone(), json!({ "amount_cents": 0 }))).await;
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    let res = test::call_service(&app, post(waivers.clone(), json!({ "reason": " " }))).await;
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    let body: Value = test::call_and_read_body_json(
+        &app,
+        post(
+            waivers.clone(),
+            json!({ "amount_cents": 200, "reason": "Found the cover" }),
+        ),
+    )
+    .await;
+    assert_eq!(body["balance_cents"], 500);
+
+    let balance: (i64, i64, i64, i64) = sqlx::query_as(
+        r#"
+        SELECT amount_cents, paid_cents, waived_cents, balance_cents
+        FROM FINE_BALANCES
+        WHERE id = ?
+        "#,
+    )
+    .bind(fine_id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(balance, (1000, 300, 200, 500));
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/fines/{}", fine_id))
+        .insert_header(common::bearer(&token))
+        .to_request();
+    let fine: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(fine["balance_cents"], 500);
+    assert_eq!(fine["transactions"].as_array().unwrap().len(), 2);
+
+    // A waiver without an amount settles the rest
+    let body: Value = test::call_and_read_body_json(
+        &app,
+        post(waivers.clone(), json!({ "reason": "Long-standing member" })),
+    )
+    .await;
+    assert_eq!(body["balance_cents"], 0);
+
+    let res = test::call_service(&app, post(payments, json!({ "amount_cents": 1 }))).await;
+    assert_eq!(res.status(), StatusCode::CONFLICT);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/members/{}/balance", member_id))
+        .insert_header(common::bearer(&token))
+        .to_request();
+    let balance: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(balance["charged_cents"], 1000);
+    assert_eq!(balance["paid_cents"], 300);
+    assert_eq!(balance["waived_cents"], 700);
+    assert_eq!(balance["balance_cents"], 0);
+
+    sqlx::query("DELETE FROM FINES WHERE member_id = ?")
+        .bind(member_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("DELETE FROM MEMBERS WHERE id = ?")
+        .bind(member_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    common::remove_staff(&pool, staff_id).await;
+}
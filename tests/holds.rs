@@ -0,0 +1,321 @@
+mod common;
+
+use actix_web::{
+    http::StatusCode,
+    middleware::from_fn,
+    test,
+    web::{scope, Data},
+    App,
+};
+use serde_json::{json, Value};
+
+use csci211_project::auth;
+use csci211_project::config::LibraryConfig;
+use csci211_project::routes::{borrowings::borrowings_config, holds::holds_config};
+use csci211_project::views::staff::Role;
+
+/// The status of a hold and the copy set aside for it
+async fn hold_state(pool: &sqlx::MySqlPool, hold_id: i64) -> (String, Option<i32>) {
+    sqlx::query_as("SELECT status, copy_id FROM HOLDS WHERE id = ?")
+        .bind(hold_id)
+        .fetch_one(pool)
+        .await
+        .expect("Failed to fetch hold")
+}
+
+async fn copy_status(pool: &sqlx::MySqlPool, copy_id: i32) -> String {
+    sqlx::query_scalar("SELECT status FROM COPIES WHERE id = ?")
+        .bind(copy_id)
+        .fetch_one(pool)
+        .await
+        .expect("Failed to fetch copy status")
+}
+
+fn hold_request(token: &str, book_id: i32, member_id: i32) -> test::TestRequest {
+    test::TestRequest::post()
+        .uri("/api/holds/")
+        .insert_header(common::bearer(token))
+        .set_json(json!({ "book_id": book_id, "member_id": member_id }))
+}
+
+fn borrow_request(token: &str, book_id: i32, member_id: i32) -> test::TestRequest {
+    test::TestRequest::post()
+        .uri(&format!("/api/borrows/{}", member_id))
+        .insert_header(common::bearer(token))
+        .set_json(json!({ "book_id": book_id }))
+}
+
+fn return_request(token: &str, copy_id: i32) -> test::TestRequest {
+    test::TestRequest::post()
+        .uri("/api/borrows/return/")
+        .insert_header(common::bearer(token))
+        .set_json(json!({ "copy_id": copy_id }))
+}
+
+#[actix_web::test]
+async fn returned_copies_go_to_the_holds_in_queue_order() {
+    let Some(pool) = common::pool().await else {
+        return;
+    };
+
+    let book_id = common::insert_book(&pool, 1).await;
+    let copy_id = common::copy_ids(&pool, book_id).await[0];
+    let mut member_ids = vec![];
+    for _ in 0..5 {
+        member_ids.push(common::insert_member(&pool).await);
+    }
+    let [reader, first, second, third, latecomer] = member_ids[..] else {
+        unreachable!()
+    };
+    let (staff_id, token) = common::sign_in(&pool, Role::Librarian, "password").await;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(Data::new(pool.clone()))
+            .app_data(Data::new(LibraryConfig::default()))
+            .service(
+                scope("/api")
+                    .wrap(from_fn(auth::authenticate))
+                    .configure(borrowings_config)
+                    .configure(holds_config),
+            ),
+    )
+    .await;
+
+    // Holds are only taken while every copy is out
+    let req = hold_request(&token, book_id, first).to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::CONFLICT
+    );
+
+    assert_eq!(
+        test::call_service(&app, borrow_request(&token, book_id, reader).to_request())
+            .await
+            .status(),
+        StatusCode::OK
+    );
+
+    let first_hold: Value =
+        test::call_and_read_body_json(&app, hold_request(&token, book_id, first).to_request())
+            .await;
+    let second_hold: Value =
+        test::call_and_read_body_json(&app, hold_request(&token, book_id, second).to_request())
+            .await;
+    let third_hold: Value =
+        test::call_and_read_body_json(&app, hold_request(&token, book_id, third).to_request())
+            .await;
+    assert_eq!(first_hold["position"], 1);
+    assert_eq!(second_hold["position"], 2);
+    assert_eq!(third_hold["position"], 3);
+    let first_hold = first_hold["id"].as_i64().unwrap();
+    let second_hold = second_hold["id"].as_i64().unwrap();
+    let third_hold = third_hold["id"].as_i64().unwrap();
+
+    // The returned copy is set aside for the front of the queue, for the
+    // configured pickup window
+    let res = test::call_service(&app, return_request(&token, copy_id).to_request()).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(
+        hold_state(&pool, first_hold).await,
+        ("ready".to_string(), Some(copy_id))
+    );
+    assert_eq!(
+        hold_state(&pool, second_hold).await,
+        ("waiting".to_string(), None)
+    );
+    assert_eq!(copy_status(&pool, copy_id).await, "on_hold");
+
+    let pickup_days: i64 =
+        sqlx::query_scalar("SELECT DATEDIFF(expires_at, ready_at) FROM HOLDS WHERE id = ?")
+            .bind(first_hold)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(
+        pickup_days,
+        i64::from(LibraryConfig::default().hold_pickup_days)
+    );
+
+    // A copy set aside is only lent to the member it is held for
+    assert_eq!(
+        test::call_service(
+            &app,
+            borrow_request(&token, book_id, latecomer).to_request()
+        )
+        .await
+        .status(),
+        StatusCode::CONFLICT
+    );
+    assert_eq!(
+        test::call_service(&app, borrow_request(&token, book_id, second).to_request())
+            .await
+            .status(),
+        StatusCode::CONFLICT
+    );
+    assert_eq!(
+        test::call_service(&app, borrow_request(&token, book_id, first).to_request())
+            .await
+            .status(),
+        StatusCode::OK
+    );
+    assert_eq!(hold_state(&pool, first_hold).await.0, "fulfilled");
+
+    let res = test::call_service(&app, return_request(&token, copy_id).to_request()).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(
+        hold_state(&pool, second_hold).await,
+        ("ready".to_string(), Some(copy_id))
+    );
+
+    // Cancelling a ready hold hands its copy on to the next in line
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/holds/{}", second_hold))
+        .insert_header(common::bearer(&token))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::NO_CONTENT
+    );
+    assert_eq!(hold_state(&pool, second_hold).await.0, "cancelled");
+    assert_eq!(
+        hold_state(&pool, third_hold).await,
+        ("ready".to_string(), Some(copy_id))
+    );
+
+    // A hold not picked up in time expires the next time the book is
+    // touched, and with nobody else waiting the copy goes back on the shelf
+    sqlx::query("UPDATE HOLDS SET expires_at = NOW() - INTERVAL 1 HOUR WHERE id = ?")
+        .bind(third_hold)
+        .execute(&pool)
+        .await
+        .unwrap();
+    assert_eq!(
+        test::call_service(
+            &app,
+            borrow_request(&token, book_id, latecomer).to_request()
+        )
+        .await
+        .status(),
+        StatusCode::OK
+    );
+    assert_eq!(
+        hold_state(&pool, third_hold).await,
+        ("expired".to_string(), Some(copy_id))
+    );
+    assert_eq!(copy_status(&pool, copy_id).await, "borrowed");
+
+    common::cleanup(&pool, book_id, &member_ids).await;
+    common::remove_staff(&pool, staff_id).await;
+}
+
+#[actix_web::test]
+async fn staff_can_reorder_the_queue() {
+    let Some(pool) = common::pool().await else {
+        return;
+    };
+
+    let book_id = common::insert_book(&pool, 1).await;
+    let copy_id = common::copy_ids(&pool, book_id).await[0];
+    let mut member_ids = vec![];
+    for _ in 0..4 {
+        member_ids.push(common::insert_member(&pool).await);
+    }
+    let (staff_id, token) = common::sign_in(&pool, Role::Librarian, "password").await;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(Data::new(pool.clone()))
+            .app_data(Data::new(LibraryConfig::default()))
+            .service(
+                scope("/api")
+                    .wrap(from_fn(auth::authenticate))
+                    .configure(borrowings_config)
+                    .configure(holds_config),
+            ),
+    )
+    .await;
+
+    assert_eq!(
+        test::call_service(
+            &app,
+            borrow_request(&token, book_id, member_ids[0]).to_request()
+        )
+        .await
+        .status(),
+        StatusCode::OK
+    );
+
+    let mut hold_ids = vec![];
+    for member_id in &member_ids[1..] {
+        let hold: Value = test::call_and_read_body_json(
+            &app,
+            hold_request(&token, book_id, *member_id).to_request(),
+        )
+        .await;
+        hold_ids.push(hold["id"].as_i64().unwrap());
+    }
+
+    let move_to = |hold_id: i64, position: u32| {
+        test::TestRequest::put()
+            .uri(&format!("/api/holds/{}/position", hold_id))
+            .insert_header(common::bearer(&token))
+            .set_json(json!({ "position": position }))
+            .to_request()
+    };
+
+    assert_eq!(
+        test::call_service(&app, move_to(hold_ids[2], 0))
+            .await
+            .status(),
+        StatusCode::BAD_REQUEST
+    );
+
+    // The last hold jumps to the front, the others keep their order behind it
+    assert_eq!(
+        test::call_service(&app, move_to(hold_ids[2], 1))
+            .await
+            .status(),
+        StatusCode::NO_CONTENT
+    );
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/holds/?book_id={}&status=waiting", book_id))
+        .insert_header(common::bearer(&token))
+        .to_request();
+    let queue: Value = test::call_and_read_body_json(&app, req).await;
+    let order: Vec<(i64, i64)> = queue
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|hold| {
+            (
+                hold["id"].as_i64().unwrap(),
+                hold["position"].as_i64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        order,
+        vec![(hold_ids[2], 1), (hold_ids[0], 2), (hold_ids[1], 3)]
+    );
+
+    // and it is the one the returned copy is set aside for
+    let res = test::call_service(&app, return_request(&token, copy_id).to_request()).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(
+        hold_state(&pool, hold_ids[2]).await,
+        ("ready".to_string(), Some(copy_id))
+    );
+
+    // Holds out of the queue can't be moved
+    assert_eq!(
+        test::call_service(&app, move_to(hold_ids[2], 2))
+            .await
+            .status(),
+        StatusCode::CONFLICT
+    );
+
+    common::cleanup(&pool, book_id, &member_ids).await;
+    common::remove_staff(&pool, staff_id).await;
+}
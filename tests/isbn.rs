@@ -0,0 +1,22 @@
+use csci211_project::isbn::Isbn;
+
+#[test]
+fn isbns_are_checked_and_converted() {
+    let isbn = Isbn::parse("0-306-40615-2").unwrap();
+    assert_eq!(isbn.isbn_10.as_deref(), Some("0306406152"));
+    assert_eq!(isbn.isbn_13, "9780306406157");
+
+    assert_eq!(Isbn::parse("978 0 306 40615 7"), Some(isbn));
+
+    // X stands for a check digit of 10
+    let isbn = Isbn::parse("080442957x").unwrap();
+    assert_eq!(isbn.isbn_10.as_deref(), Some("080442957X"));
+    assert_eq!(isbn.isbn_13, "9780804429573");
+
+    // 979 numbers have no ISBN-10
+    assert_eq!(Isbn::parse("9791090636071").unwrap().isbn_10, None);
+
+    assert_eq!(Isbn::parse("0-306-40615-3"), None);
+    assert_eq!(Isbn::parse("9780306406158"), None);
+    assert_eq!(Isbn::parse("12345"), None);
+}
@@ -0,0 +1,130 @@
+use csci211_project::marc::{self, Record};
+use csci211_project::views::{books::Book, Status};
+
+/// Assembles an ISO 2709 record from control fields and data fields written
+/// as indicators followed by `$`-prefixed subfields, e.g. `"1 $aTitle"`
+fn iso2709(control_fields: &[(&str, &str)], data_fields: &[(&str, &str)]) -> Vec<u8> {
+    let fields: Vec<(&str, Vec<u8>)> = control_fields
+        .iter()
+        .map(|(tag, value)| (*tag, value.as_bytes().to_vec()))
+        .chain(
+            data_fields
+                .iter()
+                .map(|(tag, value)| (*tag, value.replace('$', "\u{1f}").into_bytes())),
+        )
+        .collect();
+
+    let mut directory = String::new();
+    let mut data = vec![];
+    for (tag, mut value) in fields {
+        value.push(0x1e);
+        directory.push_str(&format!("{}{:04}{:05}", tag, value.len(), data.len()));
+        data.extend(value);
+    }
+
+    let base_address = 24 + directory.len() + 1;
+    let length = base_address + data.len() + 1;
+
+    let mut record = format!("{:05}nam a22{:05} i 4500", length, base_address).into_bytes();
+    record.extend(directory.into_bytes());
+    record.push(0x1e);
+    record.extend(data);
+    record.push(0x1d);
+    record
+}
+
+#[test]
+fn iso2709_records_map_onto_books() {
+    let mut file = iso2709(
+        &[
+            ("001", "ocm00001"),
+            ("008", "750101s1936    nyu           000 1 eng  "),
+        ],
+        &[
+            ("020", "  $a0306406152 (pbk.)"),
+            ("100", "1 $aMitchell, Margaret,$d1900-1949."),
+            ("245", "10$aGone with the wind /$cMargaret Mitchell."),
+            ("260", "  $aNew York :$bMacmillan,$cc1936."),
+            ("650", " 0$aSouthern States$xHistory$vFiction."),
+        ],
+    );
+    file.push(b'\n');
+    // A second record with a directory pointing past its end
+    file.extend(b"00050nam a2200037 i 4500245001000000\x1e\x1d");
+    file.extend(iso2709(
+        &[("008", "750101s1998    nyu           000 1 eng  ")],
+        &[("100", "1 $aSmith, J."), ("245", "10$aNotes :$ba study.")],
+    ));
+
+    let records = marc::read_iso2709(&file);
+    assert_eq!(records.len(), 3);
+
+    let book = records[0].as_ref().unwrap().to_book();
+    assert_eq!(book.title, "Gone with the wind");
+    assert_eq!(book.author, "Mitchell, Margaret");
+    assert_eq!(book.category.as_deref(), Some("Southern States"));
+    assert_eq!(book.isbn.as_deref(), Some("9780306406157"));
+    assert_eq!(book.publisher.as_deref(), Some("Macmillan"));
+    assert_eq!(book.publication_year, Some(1936));
+
+    assert!(records[1].is_err());
+
+    // Initials keep their full stop and the year falls back to the 008
+    let book = records[2].as_ref().unwrap().to_book();
+    assert_eq!(book.title, "Notes: a study");
+    assert_eq!(book.author, "Smith, J.");
+    assert_eq!(book.isbn, None);
+    assert_eq!(book.publication_year, Some(1998));
+}
+
+#[test]
+fn books_round_trip_through_marcxml() {
+    let book = Book {
+        id: 7,
+        title: "Salt & <Pepper>".to_string(),
+        author: "Doe, Jane".to_string(),
+        category: Some("Cooking".to_string()),
+        isbn_10: Some("0306406152".to_string()),
+        isbn_13: Some("9780306406157".to_string()),
+        publisher: Some("Example Press".to_string()),
+        publication_year: Some(2004),
+        status: Status::Available,
+        available_copies: 1,
+        total_copies: 1,
+        withdrawn_reason: None,
+        withdrawn_at: None,
+    };
+
+    let xml = format!(
+        "{}{}{}",
+        marc::COLLECTION_START,
+        marc::write_marcxml(&[Record::from(&book)]),
+        marc::COLLECTION_END
+    );
+
+    let records = marc::read_marcxml(xml.as_bytes()).unwrap();
+    assert_eq!(records, vec![Record::from(&book)]);
+    assert_eq!(records[0].control_field("001"), Some("7"));
+
+    let imported = records[0].to_book();
+    assert_eq!(imported.title, book.title);
+    assert_eq!(imported.author, book.author);
+    assert_eq!(imported.category, book.category);
+    assert_eq!(imported.isbn, book.isbn_13);
+    assert_eq!(imported.publisher, book.publisher);
+    assert_eq!(imported.publication_year, book.publication_year);
+
+    // Namespace prefixes are common in records from other systems
+    let prefixed = r#"<marc:collection xmlns:marc="http://www.loc.gov/MARC21/slim">
+        <marc:record>
+            <marc:leader>00000nam a2200000 i 4500</marc:leader>
+            <marc:datafield tag="245" ind1="0" ind2="0">
+                <marc:subfield code="a">Prefixed.</marc:subfield>
+            </marc:datafield>
+        </marc:record>
+    </marc:collection>"#;
+    let records = marc::read_marcxml(prefixed.as_bytes()).unwrap();
+    assert_eq!(records[0].to_book().title, "Prefixed");
+
+    assert!(marc::read_marcxml(b"<collection><record></collection>").is_err());
+}
@@ -0,0 +1,173 @@
+mod common;
+
+use actix_web::{
+    http::StatusCode,
+    middleware::from_fn,
+    test,
+    web::{scope, Data},
+    App,
+};
+use serde_json::json;
+
+use csci211_project::auth;
+use csci211_project::config::LibraryConfig;
+use csci211_project::routes::{
+    borrowings::borrowings_config, me::me_config, members::members_config,
+};
+use csci211_project::views::staff::Role;
+
+#[actix_web::test]
+async fn members_only_see_their_own_loans() {
+    let Some(pool) = common::pool().await else {
+        return;
+    };
+
+    let book_id = common::insert_book(&pool, 2).await;
+    let member_id = common::insert_member(&pool).await;
+    let other_id = common::insert_member(&pool).await;
+    let (staff_id, staff_token) = common::sign_in(&pool, Role::Librarian, "password").await;
+    let member_token = common::member_sign_in(&pool, member_id).await;
+
+    let app = test::init_service(
+        App::new()
+            .app_data(Data::new(pool.clone()))
+            .app_data(Data::new(LibraryConfig::default()))
+            .service(
+                scope("/api")
+                    .wrap(from_fn(auth::authenticate))
+                    .configure(members_config)
+                    .configure(borrowings_config)
+                    .configure(me_config),
+            ),
+    )
+    .await;
+
+    // Lend one copy to each member
+    for id in [member_id, other_id] {
+        let req = test::TestRequest::post()
+            .uri(&format!("/api/borrows/{}", id))
+            .insert_header(common::bearer(&staff_token))
+            .set_json(json!({ "book_id": book_id }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    }
+
+    let req = test::TestRequest::get()
+        .uri("/api/me/loans")
+        .insert_header(common::bearer(&member_token))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let loans: Vec<serde_json::Value> = test::read_body_json(res).await;
+    assert_eq!(loans.len(), 1);
+    assert!(loans.iter().all(|loan| loan["borrower_id"] == member_id));
+
+    // Someone else's loan can't be renewed, or even confirmed to exist
+    let other_loan: i32 = sqlx::query_scalar("SELECT id FROM BORROWINGS WHERE member_id = ?")
+        .bind(other_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/me/loans/{}/renew", other_loan))
+        .insert_header(common::bearer(&member_token))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::NOT_FOUND
+    );
+
+    // Member sessions don't open the staff API and staff sessions don't open /api/me
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/members/{}", other_id))
+        .insert_header(common::bearer(&member_token))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::UNAUTHORIZED
+    );
+
+    let req = test::TestRequest::get()
+        .uri("/api/me/loans")
+        .insert_header(common::bearer(&staff_token))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::UNAUTHORIZED
+    );
+
+    common::cleanup(&pool, book_id, &[member_id, other_id]).await;
+    common::remove_staff(&pool, staff_id).await;
+}
+
+#[actix_web::test]
+async fn members_sign_in_with_their_card_and_pin() {
+    let Some(pool) = common::pool().await else {
+        return;
+    };
+
+    let member_id = common::insert_member(&pool).await;
+    let no_pin_id = common::insert_member(&pool).await;
+    let card_number = common::unique("card");
+    let no_pin_card = common::unique("card");
+    sqlx::query("UPDATE MEMBERS SET card_number = ?, pin_hash = ? WHERE id = ?")
+        .bind(&card_number)
+        .bind(auth::hash_password("1234").unwrap())
+        .bind(member_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("UPDATE MEMBERS SET card_number = ? WHERE id = ?")
+        .bind(&no_pin_card)
+        .bind(no_pin_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(Data::new(pool.clone()))
+            .app_data(Data::new(LibraryConfig::default()))
+            .service(
+                scope("/api")
+                    .wrap(from_fn(auth::authenticate))
+                    .configure(me_config),
+            ),
+    )
+    .await;
+
+    let login = |card_number: &str, pin: &str| {
+        test::TestRequest::post()
+            .uri("/api/me/login")
+            .set_json(json!({ "card_number": card_number, "pin": pin }))
+            .to_request()
+    };
+
+    // A wrong PIN, an unknown card and a card without a PIN all look the same
+    for (card_number, pin) in [
+        (card_number.as_str(), "9999"),
+        ("no-such-card", "1234"),
+        (no_pin_card.as_str(), "1234"),
+    ] {
+        assert_eq!(
+            test::call_service(&app, login(card_number, pin))
+                .await
+                .status(),
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    let session: serde_json::Value =
+        test::call_and_read_body_json(&app, login(&card_number, "1234")).await;
+    assert_eq!(session["member"]["id"], member_id);
+    assert!(session["token"].is_string());
+
+    sqlx::query("DELETE FROM MEMBERS WHERE id IN (?, ?)")
+        .bind(member_id)
+        .bind(no_pin_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+}
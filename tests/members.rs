@@ -0,0 +1,219 @@
+mod common;
+
+use actix_web::{
+    http::StatusCode,
+    middleware::from_fn,
+    test,
+    web::{scope, Data},
+    App,
+};
+use serde_json::{json, Value};
+
+use csci211_project::auth;
+use csci211_project::config::LibraryConfig;
+use csci211_project::routes::members::members_config;
+use csci211_project::views::staff::Role;
+
+#[actix_web::test]
+async fn members_leave_without_losing_their_history() {
+    let Some(pool) = common::pool().await else {
+        return;
+    };
+
+    let book_id = common::insert_book(&pool, 1).await;
+    let copy_id = common::copy_ids(&pool, book_id).await[0];
+    let member_id = common::insert_member(&pool).await;
+    let (staff_id, token) = common::sign_in(&pool, Role::Admin, "password").await;
+
+    // A book still out and a fine still unpaid
+    let borrowing_id = sqlx::query(
+        r#"
+        INSERT INTO BORROWINGS (copy_id, member_id, borrow_date, due_date)
+        VALUES (?, ?, CURRENT_DATE, CURRENT_DATE)
+        "#,
+    )
+    .bind(copy_id)
+    .bind(member_id)
+    .execute(&pool)
+    .await
+    .unwrap()
+    .last_insert_id();
+    sqlx::query("UPDATE COPIES SET status = 'borrowed' WHERE id = ?")
+        .bind(copy_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let fine_id =
+        sqlx::query("INSERT INTO FINES (member_id, kind, amount_cents) VALUES (?, 'damaged', 500)")
+            .bind(member_id)
+            .execute(&pool)
+            .await
+            .unwrap()
+            .last_insert_id();
+
+    let app = test::init_service(
+        App::new()
+            .app_data(Data::new(pool.clone()))
+            .app_data(Data::new(LibraryConfig::default()))
+            .service(
+                scope("/api")
+                    .wrap(from_fn(auth::authenticate))
+                    .configure(members_config),
+            ),
+    )
+    .await;
+
+    let call = |method: test::TestRequest, uri: String| {
+        let req = method
+            .uri(&uri)
+            .insert_header(common::bearer(&token))
+            .to_request();
+        test::call_service(&app, req)
+    };
+
+    let res = call(
+        test::TestRequest::delete(),
+        format!("/api/members/{}", member_id),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::CONFLICT);
+
+    let res = call(
+        test::TestRequest::post(),
+        format!("/api/members/{}/anonymise", member_id),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::CONFLICT);
+
+    // Deactivated members drop out of the list unless asked for
+    let res = call(
+        test::TestRequest::post(),
+        format!("/api/members/{}/deactivate", member_id),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::NO_CONTENT);
+
+    let member: Value = test::read_body_json(
+        call(
+            test::TestRequest::get(),
+            format!("/api/members/{}", member_id),
+        )
+        .await,
+    )
+    .await;
+    let email = member["email"].as_str().unwrap().to_string();
+    assert!(member["deactivated_at"].is_string());
+
+    for (status, total) in [("active", 0), ("deactivated", 1)] {
+        let body: Value = test::read_body_json(
+            call(
+                test::TestRequest::get(),
+                format!("/api/members/?email={}&status={}", email, status),
+            )
+            .await,
+        )
+        .await;
+        assert_eq!(body["total"], total);
+    }
+
+    // Once the book is back and the fine waived the member can be anonymised
+    sqlx::query("UPDATE BORROWINGS SET return_date = CURRENT_DATE WHERE id = ?")
+        .bind(borrowing_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query(
+        "INSERT INTO FINE_PAYMENTS (fine_id, kind, amount_cents) VALUES (?, 'waiver', 500)",
+    )
+    .bind(fine_id)
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let res = call(
+        test::TestRequest::post(),
+        format!("/api/members/{}/anonymise", member_id),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::NO_CONTENT);
+
+    let member: Value = test::read_body_json(
+        call(
+            test::TestRequest::get(),
+            format!("/api/members/{}", member_id),
+        )
+        .await,
+    )
+    .await;
+    assert_eq!(member["first_name"], "Anonymised");
+    assert_eq!(member["email"], format!("anonymised-{}@invalid", member_id));
+    assert_eq!(member["phone"], Value::Null);
+    assert!(member["anonymised_at"].is_string());
+
+    let res = call(
+        test::TestRequest::post(),
+        format!("/api/members/{}/reactivate", member_id),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::CONFLICT);
+
+    // Nothing personal can be written back onto the anonymised record
+    for (uri, body) in [
+        (
+            format!("/api/members/{}", member_id),
+            json!({ "first_name": "Jane", "phone": "4155552671" }),
+        ),
+        (
+            format!("/api/members/{}/card", member_id),
+            json!({ "card_number": common::unique("card"), "pin": "1234" }),
+        ),
+    ] {
+        let req = test::TestRequest::put()
+            .uri(&uri)
+            .insert_header(common::bearer(&token))
+            .set_json(body)
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, req).await.status(),
+            StatusCode::CONFLICT
+        );
+    }
+
+    // Deleting the member keeps the borrowing, without a member,
+    let res = call(
+        test::TestRequest::delete(),
+        format!("/api/members/{}", member_id),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::NO_CONTENT);
+
+    let borrower: Option<i32> = sqlx::query_scalar("SELECT member_id FROM BORROWINGS WHERE id = ?")
+        .bind(borrowing_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(borrower, None);
+
+    // and the fine, with what was paid or waived on it
+    let fined: Option<i32> = sqlx::query_scalar("SELECT member_id FROM FINES WHERE id = ?")
+        .bind(fine_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(fined, None);
+    let balance: (i64, i64) =
+        sqlx::query_as("SELECT waived_cents, balance_cents FROM FINE_BALANCES WHERE id = ?")
+            .bind(fine_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(balance, (500, 0));
+
+    sqlx::query("DELETE FROM FINES WHERE id = ?")
+        .bind(fine_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    common::cleanup(&pool, book_id, &[member_id]).await;
+    common::remove_staff(&pool, staff_id).await;
+}
@@ -0,0 +1,91 @@
+mod common;
+
+use std::str::FromStr;
+
+use chrono::NaiveDate;
+use sqlx::mysql::{MySqlConnectOptions, MySqlPool};
+
+use csci211_project::migrations;
+
+#[actix_web::test]
+async fn a_database_from_the_old_library_tables_is_upgraded() {
+    let Some(pool) = common::pool().await else {
+        return;
+    };
+
+    // A scratch database set up the way the old instructions did it
+    let name = common::unique("baseline").replace('-', "_");
+    sqlx::query(&format!("CREATE DATABASE `{}`", name))
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let url = std::env::var("DATABASE_URL").unwrap();
+    let options = MySqlConnectOptions::from_str(&url).unwrap().database(&name);
+    let baseline = MySqlPool::connect_with(options).await.unwrap();
+
+    sqlx::raw_sql(include_str!("fixtures/libraryTables.sql"))
+        .execute(&baseline)
+        .await
+        .unwrap();
+    sqlx::raw_sql(
+        r#"
+        INSERT INTO BOOKS (id, title, author, category, status) VALUES
+            (1, 'Dune', 'Frank Herbert', 'Fiction', 'borrowed'),
+            (2, 'Emma', 'Jane Austen', 'Fiction', 'available');
+        INSERT INTO MEMBERS (id, first_name, last_name, email)
+            VALUES (1, 'Ada', 'Lovelace', 'ada@example.com');
+        INSERT INTO BORROWINGS (book_id, member_id, borrow_date, return_date) VALUES
+            (1, 1, '2024-03-01', NULL),
+            (2, 1, '2024-02-01', '2024-02-10');
+        "#,
+    )
+    .execute(&baseline)
+    .await
+    .unwrap();
+
+    migrations::run(&baseline)
+        .await
+        .expect("Failed to upgrade the baseline database");
+
+    // Each book became a single copy, out while it had an open loan
+    let copies: Vec<(i32, String)> =
+        sqlx::query_as("SELECT book_id, status FROM COPIES ORDER BY book_id")
+            .fetch_all(&baseline)
+            .await
+            .unwrap();
+    assert_eq!(
+        copies,
+        vec![(1, "borrowed".to_string()), (2, "available".to_string())]
+    );
+
+    // Loans moved to the copies and got the default loan period
+    let loans: Vec<(i32, NaiveDate)> = sqlx::query_as(
+        r#"
+        SELECT c.book_id, br.due_date FROM BORROWINGS br
+        JOIN COPIES c ON c.id = br.copy_id
+        ORDER BY br.id
+        "#,
+    )
+    .fetch_all(&baseline)
+    .await
+    .unwrap();
+    let date = |value: &str| NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap();
+    assert_eq!(
+        loans,
+        vec![(1, date("2024-03-15")), (2, date("2024-02-15"))]
+    );
+
+    let statuses: Vec<String> =
+        sqlx::query_scalar("SELECT status FROM BORROWED_BOOKS ORDER BY borrowing_id")
+            .fetch_all(&baseline)
+            .await
+            .unwrap();
+    assert_eq!(statuses, vec!["overdue", "returned"]);
+
+    baseline.close().await;
+    sqlx::query(&format!("DROP DATABASE `{}`", name))
+        .execute(&pool)
+        .await
+        .unwrap();
+}
@@ -0,0 +1,133 @@
+mod common;
+
+use actix_web::{
+    http::StatusCode,
+    middleware::from_fn,
+    test,
+    web::{scope, Data},
+    App,
+};
+use serde_json::Value;
+
+use csci211_project::auth;
+use csci211_project::routes::reports::reports_config;
+use csci211_project::views::staff::Role;
+
+#[actix_web::test]
+async fn reports_cover_only_the_requested_period() {
+    let Some(pool) = common::pool().await else {
+        return;
+    };
+
+    let book_a = common::insert_book(&pool, 1).await;
+    let book_b = common::insert_book(&pool, 1).await;
+    let copy_a = common::copy_ids(&pool, book_a).await[0];
+    let copy_b = common::copy_ids(&pool, book_b).await[0];
+    let first = common::insert_member(&pool).await;
+    let second = common::insert_member(&pool).await;
+    let (staff_id, token) = common::sign_in(&pool, Role::ReadOnly, "password").await;
+
+    // Three loans in March 1999, one of them returned late, and one in
+    // January that falls outside the period
+    for (copy_id, member_id, borrow_date, due_date, return_date) in [
+        (copy_a, first, "1999-01-05", "1999-01-19", "1999-01-10"),
+        (copy_a, first, "1999-03-01", "1999-03-15", "1999-03-10"),
+        (copy_a, second, "1999-03-11", "1999-03-25", "1999-03-30"),
+        (copy_b, first, "1999-03-20", "1999-04-03", "1999-04-02"),
+    ] {
+        sqlx::query(
+            r#"
+            INSERT INTO BORROWINGS (copy_id, member_id, borrow_date, due_date, return_date)
+            VALUES (?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(copy_id)
+        .bind(member_id)
+        .bind(borrow_date)
+        .bind(due_date)
+        .bind(return_date)
+        .execute(&pool)
+        .await
+        .unwrap();
+    }
+
+    let app = test::init_service(
+        App::new().app_data(Data::new(pool.clone())).service(
+            scope("/api")
+                .wrap(from_fn(auth::authenticate))
+                .configure(reports_config),
+        ),
+    )
+    .await;
+
+    let report = |path: &str| {
+        test::TestRequest::get()
+            .uri(&format!("/api/reports/{}", path))
+            .insert_header(common::bearer(&token))
+            .to_request()
+    };
+    let march = "from=1999-03-01&to=1999-03-31";
+
+    let body: Value =
+        test::call_and_read_body_json(&app, report(&format!("top-books?{}", march))).await;
+    let books = body.as_array().unwrap();
+    assert_eq!(books.len(), 2);
+    assert_eq!(books[0]["book_id"], book_a);
+    assert_eq!(books[0]["loans"], 2);
+    assert_eq!(books[0]["borrowers"], 2);
+    assert_eq!(books[1]["book_id"], book_b);
+
+    // An oversized limit is cut down rather than refused
+    let res = test::call_service(
+        &app,
+        report(&format!("top-books?{}&limit=100000000", march)),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let body: Value =
+        test::call_and_read_body_json(&app, report(&format!("top-members?{}&limit=1", march)))
+            .await;
+    assert_eq!(body.as_array().unwrap().len(), 1);
+    assert_eq!(body[0]["member_id"], first);
+    assert_eq!(body[0]["loans"], 2);
+    assert_eq!(body[0]["late_loans"], 0);
+
+    let body: Value =
+        test::call_and_read_body_json(&app, report(&format!("loans-by-category?{}", march))).await;
+    assert_eq!(body.as_array().unwrap().len(), 1);
+    assert_eq!(body[0]["month"], "1999-03");
+    assert_eq!(body[0]["category"], "Test");
+    assert_eq!(body[0]["loans"], 3);
+
+    // Loans are counted by the month they came back in
+    let body: Value =
+        test::call_and_read_body_json(&app, report("loan-length?from=1999-03-01&to=1999-04-30"))
+            .await;
+    assert_eq!(body.as_array().unwrap().len(), 2);
+    assert_eq!(body[0]["month"], "1999-03");
+    assert_eq!(body[0]["loans"], 2);
+    assert_eq!(body[0]["average_days"], 14.0);
+    assert_eq!(body[0]["longest_days"], 19);
+    assert_eq!(body[1]["month"], "1999-04");
+    assert_eq!(body[1]["average_days"], 13.0);
+
+    let res = test::call_service(&app, report(&format!("top-books?{}&format=csv", march))).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body = String::from_utf8(test::read_body(res).await.to_vec()).unwrap();
+    let lines: Vec<&str> = body.lines().collect();
+    assert_eq!(lines[0], "book_id,title,author,category,loans,borrowers");
+    assert_eq!(lines.len(), 3);
+    assert!(lines[1].starts_with(&format!("{},", book_a)));
+
+    assert_eq!(
+        test::call_service(&app, report("top-books?from=1999-03-31&to=1999-03-01"))
+            .await
+            .status(),
+        StatusCode::BAD_REQUEST
+    );
+
+    common::cleanup(&pool, book_a, &[first, second]).await;
+    common::cleanup(&pool, book_b, &[]).await;
+    common::remove_staff(&pool, staff_id).await;
+}
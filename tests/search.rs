@@ -0,0 +1,76 @@
+use csci211_project::search::{boolean_query, fuzzy_query, suggestions, Vocabulary};
+
+#[test]
+fn search_box_queries_become_boolean_queries() {
+    assert_eq!(
+        boolean_query("Gatsby fitzgerald").as_deref(),
+        Some("+gatsby +fitzgerald")
+    );
+    assert_eq!(
+        boolean_query(r#""The Great Gatsby" fitz*"#).as_deref(),
+        Some(r#"+"the great gatsby" +fitz*"#)
+    );
+
+    // Operators typed by accident can't break the query
+    assert_eq!(
+        boolean_query("sci-fi (classics) ~ @3").as_deref(),
+        Some("+sci +classics")
+    );
+    assert_eq!(
+        boolean_query(r#"war and "peace"#).as_deref(),
+        Some(r#"+war +and +"peace""#)
+    );
+
+    // Punctuation is dropped like the operators are
+    assert_eq!(
+        boolean_query("Tolkien, J.R.R.: hobbit's").as_deref(),
+        Some("+tolkien +hobbit")
+    );
+
+    // Stopwords and short words aren't indexed, so they aren't required
+    assert_eq!(
+        boolean_query("the lord of the rings").as_deref(),
+        Some("+lord +rings")
+    );
+    assert_eq!(boolean_query("  of  ").as_deref(), None);
+    assert_eq!(boolean_query(r#""" ** +"#).as_deref(), None);
+}
+
+#[test]
+fn typos_and_accents_are_forgiven() {
+    let vocabulary = Vocabulary::new([
+        "The Great Gatsby",
+        "F. Scott Fitzgerald",
+        "To Kill a Mockingbird",
+        "Gabriel García Márquez",
+    ]);
+
+    assert_eq!(
+        fuzzy_query("Fitzgerold mockinbird", &vocabulary).as_deref(),
+        Some("+(fitzgerold fitzgerald) +(mockinbird mockingbird)")
+    );
+    assert_eq!(
+        fuzzy_query("garcia marquez", &vocabulary).as_deref(),
+        Some("+(garcia garcía) +(marquez márquez)")
+    );
+    // Phrases and prefixes aren't expanded
+    assert_eq!(
+        fuzzy_query(r#""great gatsbi" fitzg*"#, &vocabulary).as_deref(),
+        Some(r#"+"great gatsbi" +fitzg*"#)
+    );
+
+    assert_eq!(
+        suggestions("the great gatsbi by Fitzgerold", &vocabulary),
+        vec!["the great Gatsby by Fitzgerald"]
+    );
+    assert_eq!(suggestions("garcia", &vocabulary), vec!["García"]);
+
+    // Punctuation doesn't stop a word from being matched
+    assert_eq!(
+        fuzzy_query("fitzgerold, gatsby.", &vocabulary).as_deref(),
+        Some("+(fitzgerold fitzgerald) +gatsby")
+    );
+    // Words in the catalog, or too far from anything in it, are kept
+    assert!(suggestions("gatsby", &vocabulary).is_empty());
+    assert!(suggestions("xylophone", &vocabulary).is_empty());
+}